@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde_json::json;
+use validator::Validate;
+
+use common::{
+    after_sale::{
+        ReqAfterSale, ReqAfterSaleInspect, ReqAfterSaleReship, ReqAfterSaleReview,
+        ReqAfterSaleShipBack,
+    },
+    error::format_errors,
+    jwt::Claims,
+    ApiResponse, PagePer, Pagination,
+};
+
+use crate::models::after_sales::{AfterSale, AfterSaleStatus, AfterSaleType};
+use crate::models::order_items::OrderItems;
+
+/// 售后: 退货退款、换货
+pub struct AfterSaleController;
+
+impl AfterSaleController {
+    // 申请售后
+    pub async fn apply(
+        Extension(claims): Extension<Claims>,
+        Json(payload): Json<ReqAfterSale>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let item =
+            match OrderItems::detail(payload.order_item_id.unwrap(), payload.order_id.unwrap())
+                .await
+            {
+                Ok(item) => item,
+                Err(_) => return ApiResponse::fail_msg("订单不存在".to_string()).json(),
+            };
+        let r#type = match AfterSaleType::try_from(payload.r#type.unwrap()) {
+            Ok(r#type) => r#type,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        match AfterSale::apply(
+            claims.id,
+            &item,
+            r#type,
+            payload.amount.unwrap(),
+            payload.reason.unwrap(),
+            payload.photos.unwrap_or_default(),
+        )
+        .await
+        {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 我的售后
+    pub async fn index(
+        Extension(claims): Extension<Claims>,
+        Query(page_per): Query<PagePer>,
+    ) -> impl IntoResponse {
+        let mut pagination = Pagination::new(vec![], page_per);
+        match AfterSale::index(Some(claims.id), None, &mut pagination).await {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 售后详情及进度
+    pub async fn get(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+    ) -> impl IntoResponse {
+        let result = match AfterSale::get(id, Some(claims.id)).await {
+            Ok(after_sale) => after_sale.detail().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(detail) => ApiResponse::response(Some(detail)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 取消售后
+    pub async fn cancel(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+    ) -> impl IntoResponse {
+        let result = match AfterSale::get(id, Some(claims.id)).await {
+            Ok(after_sale) => after_sale.cancel().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 寄回商品
+    pub async fn ship_back(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqAfterSaleShipBack>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match AfterSale::get(id, Some(claims.id)).await {
+            Ok(after_sale) => {
+                after_sale
+                    .ship_back(payload.company.unwrap(), payload.tracking_no.unwrap())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 后台售后列表
+    pub async fn manage_index(
+        Query(page_per): Query<PagePer>,
+        Query(inner): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let status = inner
+            .get("status")
+            .and_then(|status| status.parse::<i16>().ok());
+        let status = status.and_then(|status| {
+            [
+                AfterSaleStatus::Pending,
+                AfterSaleStatus::Approved,
+                AfterSaleStatus::Rejected,
+                AfterSaleStatus::Returned,
+                AfterSaleStatus::Inspected,
+                AfterSaleStatus::InspectFailed,
+                AfterSaleStatus::Refunded,
+                AfterSaleStatus::Reshipped,
+                AfterSaleStatus::Cancelled,
+            ]
+            .into_iter()
+            .find(|item| *item as i16 == status)
+        });
+
+        let mut pagination = Pagination::new(vec![], page_per);
+        match AfterSale::index(None, status, &mut pagination).await {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 后台售后详情
+    pub async fn manage_get(Path(id): Path<i64>) -> impl IntoResponse {
+        let result = match AfterSale::get(id, None).await {
+            Ok(after_sale) => after_sale.detail().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(detail) => ApiResponse::response(Some(detail)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 商家审核
+    pub async fn review(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqAfterSaleReview>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match AfterSale::get(id, None).await {
+            Ok(after_sale) => {
+                after_sale
+                    .review(
+                        payload.approved.unwrap(),
+                        payload.remark.unwrap_or_default(),
+                        payload.refund_amount,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 仓库验货
+    pub async fn inspect(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqAfterSaleInspect>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match AfterSale::get(id, None).await {
+            Ok(after_sale) => {
+                after_sale
+                    .inspect(payload.passed.unwrap(), payload.remark.unwrap_or_default())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 退款
+    pub async fn refund(Path(id): Path<i64>) -> impl IntoResponse {
+        let result = match AfterSale::get(id, None).await {
+            Ok(after_sale) => after_sale.refund().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(refund_no) => ApiResponse::response(Some(json!({ "refund_no": refund_no }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 换货发出
+    pub async fn reship(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqAfterSaleReship>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match AfterSale::get(id, None).await {
+            Ok(after_sale) => {
+                after_sale
+                    .reship(
+                        payload.company.unwrap(),
+                        payload.carrier.unwrap_or_default(),
+                        payload.tracking_no.unwrap(),
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(shipment_id) => {
+                ApiResponse::response(Some(json!({ "shipment_id": shipment_id }))).json()
+            }
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
@@ -6,7 +6,7 @@ use axum::Json;
 use serde_json::json;
 use validator::Validate;
 
-use common::categories::{ReqCategories, UpdateCategories};
+use common::categories::{ReqCategories, ReqMoveCategory, ReqSortCategories, UpdateCategories};
 use common::error::format_errors;
 use common::ApiResponse;
 
@@ -91,4 +91,73 @@ impl CategoriesController {
             Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
         }
     }
+
+    /// 完整类目树(含商品数量)
+    pub async fn tree() -> impl IntoResponse {
+        match Categories::tree().await {
+            Ok(data) => ApiResponse::response(Some(data)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 批量获取类目, ids=1,2,3
+    pub async fn batch(Query(inner): Query<HashMap<String, String>>) -> impl IntoResponse {
+        let ids = inner
+            .get("ids")
+            .map(|ids| {
+                ids.split(',')
+                    .filter_map(|id| id.trim().parse::<i64>().ok())
+                    .collect::<Vec<i64>>()
+            })
+            .unwrap_or_default();
+        if ids.is_empty() {
+            return ApiResponse::fail_msg("请选择类目".to_string()).json();
+        }
+
+        match Categories::batch(ids).await {
+            Ok(data) => ApiResponse::response(Some(data)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 移动类目到新的父级下
+    pub async fn move_to(
+        Path(id): Path<i64>,
+        Json(inner): Json<ReqMoveCategory>,
+    ) -> impl IntoResponse {
+        if let Err(e) = inner.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Categories::get(id).await {
+            Ok(Some(this)) => match this.move_to(inner.parent_id.unwrap_or(0), inner.sort).await {
+                Ok(bool_val) => ApiResponse::response(Some(json!({ "status": bool_val }))).json(),
+                Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+            },
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+            _ => ApiResponse::fail_msg("类目不存在".to_string()).json(),
+        }
+    }
+
+    /// 同级类目排序
+    pub async fn sort(Json(inner): Json<ReqSortCategories>) -> impl IntoResponse {
+        if let Err(e) = inner.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Categories::sort(inner.parent_id.unwrap_or(0), inner.ids.unwrap()).await {
+            Ok(rows) => ApiResponse::response(Some(json!({ "rows": rows }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 根据 parent_id 修复类目路径
+    pub async fn rebuild_path() -> impl IntoResponse {
+        match Categories::rebuild_path().await {
+            Ok(rows) => ApiResponse::response(Some(json!({ "rows": rows }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
 }
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use axum::{
+    http::{header, HeaderMap, HeaderName},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use common::{cookie, ApiResponse};
+
+use crate::models::cart_items::IncrType;
+use crate::models::guest_cart::{GuestCart, GUEST_CART_COOKIE, GUEST_CART_TTL};
+
+/// 游客购物车, 以签名cookie识别游客
+pub struct GuestCartController;
+
+impl GuestCartController {
+    /// 购物车列表
+    pub async fn carts(headers: HeaderMap) -> impl IntoResponse {
+        let cart = Self::cart(&headers).await;
+        let response = match cart.items().await {
+            Ok(items) => ApiResponse::response(Some(json!({ "items": items }))).json(),
+            Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
+        };
+
+        (Self::cookie(&cart).await, response)
+    }
+
+    /// 加入购物车
+    pub async fn add_cart(
+        headers: HeaderMap,
+        Json(inner): Json<HashMap<String, i64>>,
+    ) -> impl IntoResponse {
+        let cart = Self::cart(&headers).await;
+        let product_id = inner.get("product_id").cloned().unwrap_or_default();
+        let sku_id = inner.get("product_sku_id").cloned().unwrap_or_default();
+        let amount = inner.get("amount").cloned().unwrap_or(1);
+
+        let response = if product_id <= 0 || sku_id <= 0 || amount <= 0 || amount > 10000 {
+            ApiResponse::fail_msg("添加失败,参数错误".to_string()).json()
+        } else {
+            match cart.add(product_id, sku_id, amount as u16).await {
+                Ok(()) => ApiResponse::response(Some(json!({ "product_id": product_id }))).json(),
+                Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
+            }
+        };
+
+        (Self::cookie(&cart).await, response)
+    }
+
+    /// 修改购物车数量, amount 为正数时增加, 负数时减少
+    pub async fn update_cart(
+        headers: HeaderMap,
+        Json(inner): Json<HashMap<String, i64>>,
+    ) -> impl IntoResponse {
+        let cart = Self::cart(&headers).await;
+        let sku_id = inner.get("product_sku_id").cloned().unwrap_or_default();
+        let amount = inner.get("amount").cloned().unwrap_or_default();
+
+        let response = if sku_id <= 0 || amount == 0 || amount.abs() > 10000 {
+            ApiResponse::fail_msg("修改失败,参数错误".to_string()).json()
+        } else {
+            let up_type = if amount > 0 {
+                IncrType::Add
+            } else {
+                IncrType::Reduce
+            };
+            match cart
+                .update_amount(sku_id, up_type, amount.unsigned_abs() as u16)
+                .await
+            {
+                Ok(status) => ApiResponse::response(Some(json!({ "status": status }))).json(),
+                Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
+            }
+        };
+
+        (Self::cookie(&cart).await, response)
+    }
+
+    /// 删除购物车商品, 参数为sku id
+    pub async fn delete_carts(
+        headers: HeaderMap,
+        Json(sku_ids): Json<Vec<i64>>,
+    ) -> impl IntoResponse {
+        let cart = Self::cart(&headers).await;
+        let response = match cart.delete(sku_ids).await {
+            Ok(rows) => ApiResponse::response(Some(json!({ "rows": rows }))).json(),
+            Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
+        };
+
+        (Self::cookie(&cart).await, response)
+    }
+
+    // cookie 签名校验失败时重新生成购物车
+    async fn cart(headers: &HeaderMap) -> GuestCart {
+        match cookie::get_signed(headers, GUEST_CART_COOKIE).await {
+            Some(token) => GuestCart::new(token),
+            None => GuestCart::generate(),
+        }
+    }
+
+    // 每次访问刷新cookie有效期
+    async fn cookie(cart: &GuestCart) -> [(HeaderName, String); 1] {
+        [(
+            header::SET_COOKIE,
+            cookie::set_signed(GUEST_CART_COOKIE, cart.token(), GUEST_CART_TTL).await,
+        )]
+    }
+}
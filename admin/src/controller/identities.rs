@@ -0,0 +1,103 @@
+use axum::extract::{Json, Path};
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::Extension;
+use serde_json::json;
+use validator::Validate;
+
+use common::error::{format_errors, ApiError, ApiResult};
+use common::jwt::Claims;
+use common::oauth;
+use common::request::user::ReqOAuthCode;
+use common::ApiResponse;
+
+use crate::controller::user::AdminController;
+use crate::models::identities::UserIdentity;
+use crate::models::user::Credential;
+
+/// 第三方登录及账号绑定
+pub struct IdentityController;
+
+impl IdentityController {
+    /// 第三方登录, 首次登录自动注册
+    pub async fn login(
+        headers: HeaderMap,
+        Path(provider): Path<String>,
+        Json(payload): Json<ReqOAuthCode>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Self::oauth_login(&provider, &payload.code.unwrap(), &headers).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn oauth_login(
+        provider: &str,
+        code: &str,
+        headers: &HeaderMap,
+    ) -> ApiResult<serde_json::Value> {
+        let from = oauth::source(provider)?;
+        let info = oauth::provider(provider).await?.user_info(code).await?;
+        let user_id = UserIdentity::login_user(&info).await?;
+        let user = Credential::by_id(user_id)
+            .await?
+            .ok_or(ApiError::Error("用户不存在".to_string()))?;
+        user.check_status()?;
+
+        AdminController::complete_login(&user, from, headers).await
+    }
+
+    /// 已绑定的第三方账号
+    pub async fn index(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match UserIdentity::list(user.id).await {
+            Ok(identities) => ApiResponse::response(Some(
+                identities
+                    .iter()
+                    .map(|identity| identity.to_json())
+                    .collect::<Vec<serde_json::Value>>(),
+            ))
+            .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 绑定第三方账号
+    pub async fn link(
+        Extension(user): Extension<Claims>,
+        Path(provider): Path<String>,
+        Json(payload): Json<ReqOAuthCode>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Self::link_identity(user.id, &provider, &payload.code.unwrap()).await {
+            Ok(identity) => ApiResponse::response(Some(identity.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn link_identity(user_id: i64, provider: &str, code: &str) -> ApiResult<UserIdentity> {
+        oauth::source(provider)?;
+        let info = oauth::provider(provider).await?.user_info(code).await?;
+
+        UserIdentity::link(user_id, &info).await
+    }
+
+    /// 解绑第三方账号
+    pub async fn unlink(
+        Extension(user): Extension<Claims>,
+        Path(provider): Path<String>,
+    ) -> impl IntoResponse {
+        match UserIdentity::unlink(user.id, &provider).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde_json::json;
+use tracing::error;
+use validator::Validate;
+
+use common::{
+    error::format_errors,
+    invoice::{ReqInvoiceApply, ReqInvoiceReject, ReqInvoiceTitle},
+    jwt::Claims,
+    ApiResponse, PagePer, Pagination,
+};
+
+use crate::models::invoices::{Invoice, InvoiceStatus, InvoiceTitle};
+use crate::models::orders::Orders;
+
+/// 发票: 用户发票抬头、订单开票
+pub struct InvoiceController;
+
+impl InvoiceController {
+    // 发票抬头列表
+    pub async fn titles(Extension(claims): Extension<Claims>) -> impl IntoResponse {
+        match InvoiceTitle::list(claims.id).await {
+            Ok(titles) => ApiResponse::response(Some(json!(titles))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 新增发票抬头
+    pub async fn create_title(
+        Extension(claims): Extension<Claims>,
+        Json(payload): Json<ReqInvoiceTitle>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match InvoiceTitle::create(claims.id, payload).await {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 修改发票抬头
+    pub async fn update_title(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqInvoiceTitle>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match InvoiceTitle::update(id, claims.id, payload).await {
+            Ok(status) => ApiResponse::response(Some(json!({ "status": status }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 删除发票抬头
+    pub async fn delete_title(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+    ) -> impl IntoResponse {
+        match InvoiceTitle::delete(id, claims.id).await {
+            Ok(status) => ApiResponse::response(Some(json!({ "status": status }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 订单申请开票
+    pub async fn apply(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqInvoiceApply>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let order = match Orders::get(id, claims.id).await {
+            Ok(order) => order,
+            Err(e) => {
+                error!("申请发票查询订单错误: {}", e);
+                return ApiResponse::fail_msg("订单不存在".to_string()).json();
+            }
+        };
+        let result = match InvoiceTitle::get(payload.title_id.unwrap(), claims.id).await {
+            Ok(title) => Invoice::apply(&order, title).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 下载订单发票
+    pub async fn download(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+    ) -> impl IntoResponse {
+        if let Err(e) = Orders::get(id, claims.id).await {
+            error!("下载发票查询订单错误: {}", e);
+            return ApiResponse::<()>::fail_msg("订单不存在".to_string()).response_body();
+        }
+
+        let result = match Invoice::by_order(id).await {
+            Ok(Some(invoice)) => invoice.content().await.map(|content| (invoice.no, content)),
+            Ok(None) => Err("订单未申请发票".into()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok((no, content)) => ApiResponse::<()>::set_content_type(Some("application/pdf"))
+                .header(
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"invoice-{}.pdf\"", no),
+                )
+                .body(Body::from(content))
+                .unwrap(),
+            Err(e) => ApiResponse::<()>::fail_msg(e.to_string()).response_body(),
+        }
+    }
+
+    // 发票管理列表
+    pub async fn index(
+        Query(page_per): Query<PagePer>,
+        Query(inner): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let status = inner
+            .get("status")
+            .and_then(|status| status.parse::<u8>().ok())
+            .and_then(|status| InvoiceStatus::try_from(status).ok());
+
+        let mut pagination = Pagination::new(vec![], page_per);
+        match Invoice::index(status, &mut pagination).await {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 开票
+    pub async fn issue(Path(id): Path<i64>) -> impl IntoResponse {
+        let result = match Invoice::get(id).await {
+            Ok(invoice) => invoice.issue().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 拒绝开票
+    pub async fn reject(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqInvoiceReject>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match Invoice::get(id).await {
+            Ok(invoice) => invoice.reject(payload.reason.unwrap()).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use axum::extract::{Json, Path};
+use axum::response::IntoResponse;
+use axum::Extension;
+use serde_json::json;
+use validator::Validate;
+
+use common::auth::ReqMenu;
+use common::error::{format_errors, ApiResult};
+use common::jwt::Claims;
+use common::ApiResponse;
+
+use crate::models::menus::Menu;
+use crate::models::roles::EffectivePermissions;
+
+/// 后台菜单
+pub struct MenuController;
+
+impl MenuController {
+    /// 全部菜单树
+    pub async fn tree() -> impl IntoResponse {
+        match Menu::tree().await {
+            Ok(tree) => ApiResponse::response(Some(tree)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 当前用户可见的菜单树
+    pub async fn mine(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match Self::visible(user.id).await {
+            Ok(tree) => ApiResponse::response(Some(tree)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn visible(user_id: i64) -> ApiResult<Vec<serde_json::Value>> {
+        let permission_ids =
+            EffectivePermissions::permission_ids(user_id, common::casbin::DEFAULT_DOMAIN).await?;
+
+        Menu::visible(permission_ids).await
+    }
+
+    /// 创建菜单
+    pub async fn create(Json(payload): Json<ReqMenu>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Menu::create(payload).await {
+            Ok(menu) => ApiResponse::response(Some(menu.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 修改菜单
+    pub async fn update(Path(id): Path<i64>, Json(payload): Json<ReqMenu>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Menu::update(id, payload).await {
+            Ok(menu) => ApiResponse::response(Some(menu.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 删除菜单
+    pub async fn delete(Path(id): Path<i64>) -> impl IntoResponse {
+        match Menu::delete(id).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use axum::extract::Json;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::Extension;
+use serde_json::json;
+use validator::Validate;
+
+use common::error::{format_errors, ApiError, ApiResult};
+use common::jwt::Claims;
+use common::request::user::{ReqMfaCode, ReqMfaLogin};
+use common::ApiResponse;
+
+use crate::controller::user::AdminController;
+use crate::models::mfa::{MfaPending, UserMfa};
+use crate::models::user::Credential;
+
+/// 两步验证
+pub struct MfaController;
+
+impl MfaController {
+    /// 生成密钥及扫码地址
+    pub async fn setup(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match Self::provision(user.id, &user.email).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn provision(user_id: i64, email: &str) -> ApiResult<serde_json::Value> {
+        let mfa = UserMfa::setup(user_id).await?;
+        let issuer = common::application_config().await.mfa.issuer.clone();
+
+        Ok(json!({
+            "secret": mfa.secret,
+            "uri": mfa.provisioning_uri(&issuer, email),
+        }))
+    }
+
+    /// 验证后开启, 返回恢复码
+    pub async fn enable(
+        Extension(user): Extension<Claims>,
+        Json(payload): Json<ReqMfaCode>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match UserMfa::get(user.id).await {
+            Ok(Some(mfa)) => mfa.enable(&payload.code.unwrap()).await,
+            Ok(None) => Err(ApiError::Error("请先获取两步验证密钥".to_string())),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(codes) => ApiResponse::response(Some(json!({ "recovery_codes": codes }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 重新生成恢复码
+    pub async fn recovery_codes(
+        Extension(user): Extension<Claims>,
+        Json(payload): Json<ReqMfaCode>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match UserMfa::get(user.id).await {
+            Ok(Some(mfa)) => mfa.regenerate_codes(&payload.code.unwrap()).await,
+            Ok(None) => Err(ApiError::Error("未开启两步验证".to_string())),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(codes) => ApiResponse::response(Some(json!({ "recovery_codes": codes }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 关闭, 要求开启两步验证的管理员不能关闭
+    pub async fn disable(
+        Extension(user): Extension<Claims>,
+        Json(payload): Json<ReqMfaCode>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Self::try_disable(user.id, &payload.code.unwrap()).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn try_disable(user_id: i64, code: &str) -> ApiResult<()> {
+        let user = Credential::by_id(user_id)
+            .await?
+            .ok_or(ApiError::Error("用户不存在".to_string()))?;
+        if user.is_admin() && common::application_config().await.mfa.require_admin {
+            return Err(ApiError::Error("管理员账号必须开启两步验证".to_string()));
+        }
+
+        match UserMfa::get(user_id).await? {
+            Some(mfa) => mfa.disable(code).await,
+            None => Err(ApiError::Error("未开启两步验证".to_string())),
+        }
+    }
+
+    /// 登录时未开启两步验证的管理员, 先获取密钥
+    pub async fn login_setup(Json(payload): Json<ReqMfaLogin>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match MfaPending::get(&payload.mfa_token.unwrap()).await {
+            Ok(pending) => match Credential::by_id(pending.user_id).await {
+                Ok(Some(user)) => Self::provision(user.id, &user.email).await,
+                Ok(None) => Err(ApiError::Error("用户不存在".to_string())),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 登录第二步, 验证通过后签发token. 首次验证时同时开启并返回恢复码
+    pub async fn login_verify(
+        headers: HeaderMap,
+        Json(payload): Json<ReqMfaLogin>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let token = payload.mfa_token.unwrap();
+        match Self::verify_login(&token, &payload.code.unwrap_or_default(), &headers).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn verify_login(
+        token: &str,
+        code: &str,
+        headers: &HeaderMap,
+    ) -> ApiResult<serde_json::Value> {
+        let pending = MfaPending::get(token).await?;
+        let mfa = UserMfa::get(pending.user_id)
+            .await?
+            .ok_or(ApiError::Error("请先获取两步验证密钥".to_string()))?;
+
+        let recovery_codes = match mfa.enabled_at {
+            Some(_) => match mfa.verify(code).await? {
+                true => None,
+                false => {
+                    pending.fail(token).await?;
+                    return Err(ApiError::Error("验证码错误".to_string()));
+                }
+            },
+            None => match mfa.enable(code).await {
+                Ok(codes) => Some(codes),
+                Err(e) => {
+                    pending.fail(token).await?;
+                    return Err(e);
+                }
+            },
+        };
+        MfaPending::consume(token).await?;
+
+        let user = Credential::by_id(mfa.user_id)
+            .await?
+            .ok_or(ApiError::Error("用户不存在".to_string()))?;
+        let mut result = AdminController::sign_in(&user, pending.from.clone(), headers).await?;
+        if let Some(codes) = recovery_codes {
+            result["recovery_codes"] = json!(codes);
+        }
+
+        Ok(result)
+    }
+}
@@ -5,31 +5,43 @@ use std::sync::Arc;
 use async_once::AsyncOnce;
 use axum::{
     body::Body,
-    extract::{Multipart, Path},
-    http::Request,
-    response::IntoResponse,
+    extract::{Multipart, Path, Query},
+    http::{header, HeaderMap, Request},
+    response::{IntoResponse, Response},
     Json,
 };
 use http::StatusCode;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tokio::io::AsyncWriteExt;
 use tracing::{error, info};
 
 use crate::controller::order::DelayOrder;
+use crate::models::favorite_alerts::FavoriteAlert;
+use crate::models::sessions::Session;
 use common::error::{ApiError, ApiResult};
-use common::jwt::{Claims, JWT};
+use common::jwt::Claims;
 use common::rabbitmq::{MQManager, RabbitMQDlxQueue, RabbitMQQueue};
-use common::{redis, ApiResponse, SchoolJson, IMAGES_PATH};
+use common::{picture, redis, ApiResponse, SchoolJson};
 
 pub mod address;
+pub mod after_sales;
 pub mod auth;
 pub mod categories;
 pub mod coupons;
+pub mod guest_cart;
+pub mod identities;
+pub mod invoices;
+pub mod menus;
+pub mod mfa;
 pub mod order;
 pub mod products;
+pub mod purchases;
+pub mod reviews;
+pub mod roles;
+pub mod shipping_templates;
 pub mod user;
+pub mod warehouses;
 
 pub struct CommController;
 
@@ -95,21 +107,28 @@ impl CommController {
         }
     }
 
-    /// 刷新token
+    /// 刷新token, 每次刷新都会更换 refresh_token
     pub async fn refresh_token(mut req: Request<Body>) -> impl IntoResponse {
-        match req.extensions_mut().get_mut::<Claims>() {
-            Some(claims) => match JWT::default().token_info(claims) {
-                Ok((access_token, refresh_token)) => ApiResponse::response(Some(json!({
-                    "access_token": access_token,
-                    "refresh_token":refresh_token,
-                })))
-                    .json(),
-                Err(_) => ApiResponse::fail_msg("refresh_token 刷新失败[02]".to_string()).json(),
-            },
-            None => ApiResponse::fail_msg("refresh_token 刷新失败[01]".to_string()).json(),
+        let claims = match req.extensions_mut().get_mut::<Claims>() {
+            Some(claims) => claims,
+            None => return ApiResponse::fail_msg("refresh_token 刷新失败[01]".to_string()).json(),
+        };
+
+        match Session::refresh(claims).await {
+            Ok((access_token, refresh_token)) => ApiResponse::response(Some(json!({
+                "access_token": access_token,
+                "refresh_token":refresh_token,
+            })))
+                .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
         }
     }
 
+    /// jwt 公钥集合(JWKS)
+    pub async fn jwks() -> impl IntoResponse {
+        Json(common::jwt::jwt().await.jwks())
+    }
+
     /// 获取系统配置
     pub async fn get_application() -> impl IntoResponse {
         let result = common::application_config().await;
@@ -135,17 +154,40 @@ impl CommController {
     }
 
     /// 读取图片内容
-    pub async fn show_image(Path(path): Path<String>) -> impl IntoResponse {
+    ///
+    /// ?w=400 返回不小于该宽度的缩略图; ?format=webp 或 Accept 包含 image/webp 时返回webp格式
+    pub async fn show_image(
+        Path(path): Path<String>,
+        Query(params): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> impl IntoResponse {
         let filepath = common::utils::url_decode(path);
-        let payload_arr = &filepath.split(".").collect::<Vec<&str>>();
-        let ext_name = payload_arr[payload_arr.len() - 1];
-        let content_type = format!("image/{}", ext_name);
+        let width = params.get("w").and_then(|w| w.parse::<u32>().ok());
+        let webp = match params.get("format") {
+            Some(format) => format == "webp",
+            None => headers
+                .get(header::ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .map_or(false, |accept| accept.contains("image/webp")),
+        };
 
-        match tokio::fs::read(format!("{}{}", IMAGES_PATH, filepath)).await {
-            Ok(content) => ApiResponse::<Vec<u8>>::set_content_type(Some(&content_type))
-                .body(Body::from(content))
-                .unwrap()
-                .into_response(),
+        // 对应尺寸、格式的文件不存在时依次降级到原图
+        let candidates = [
+            picture::variant_path(&filepath, width, webp),
+            picture::variant_path(&filepath, width, false),
+            filepath,
+        ];
+        let storage = common::storage().await;
+        let mut result = Err(ApiError::Error("读取文件失败".to_string()));
+        for path in candidates.iter() {
+            if let Ok(content) = storage.get(path).await {
+                result = Self::image_response(content, &headers);
+                break;
+            }
+        }
+
+        match result {
+            Ok(response) => response,
             Err(_e) => ApiResponse::<Vec<u8>>::set_content_type(None)
                 .status(StatusCode::NOT_FOUND)
                 .body(Body::from("读取文件失败"))
@@ -154,27 +196,69 @@ impl CommController {
         }
     }
 
-    /// 上传图片
+    /// 图片响应, 支持 ETag 协商缓存及 Range 分段请求
+    fn image_response(content: Vec<u8>, headers: &HeaderMap) -> ApiResult<Response> {
+        let kind = picture::ImageKind::detect(&content)
+            .ok_or(ApiError::Error("读取文件失败".to_string()))?;
+        let len = content.len() as u64;
+        let etag = format!("\"{}\"", &picture::content_hash(&content)[..16]);
+
+        let builder = http::Response::builder()
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(header::CACHE_CONTROL, "public, max-age=604800")
+            .header(header::ETAG, &etag)
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::VARY, "Accept");
+
+        let if_none_match = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok());
+        if if_none_match.map_or(false, |value| value.split(',').any(|tag| tag.trim() == etag)) {
+            return Ok(builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())
+                .unwrap()
+                .into_response());
+        }
+
+        let builder = builder.header(header::CONTENT_TYPE, kind.content_type());
+        let range = headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok());
+        let response = match range {
+            Some(range) => match picture::parse_range(range, len) {
+                Some((start, end)) => builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+                    .body(Body::from(content[start as usize..=end as usize].to_vec())),
+                None => builder
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                    .body(Body::empty()),
+            },
+            None => builder.body(Body::from(content)),
+        };
+
+        Ok(response.unwrap().into_response())
+    }
+
+    /// 上传图片, 以文件头校验格式, 按内容hash命名去重并生成缩略图
     async fn upload_images(mut multipart: Multipart) -> ApiResult<Option<(String, String)>> {
-        let date = chrono::Local::now().format("%Y-%m/").to_string();
         let mut path: Option<(String, String)> = None;
-        let root_path = common::utils::IMAGES_PATH;
         while let Some(mut field) = multipart.next_field().await? {
-            let content_type = field.content_type().unwrap().to_string();
-            tokio::fs::create_dir_all(format!("{}{}", root_path, date)).await?;
-            if !&content_type.contains("image/") {
-                return Err(ApiError::Error("不允许上传此类型文件".to_string()));
-            }
-
-            let dst = date.to_owned() + field.file_name().unwrap();
-            path = Some(common::utils::image_preview_url(dst.clone()).await);
-
-            let mut new_file = tokio::fs::File::create(format!("{}{}", root_path, dst)).await?;
+            let mut content = Vec::new();
             while let Some(chunk) = field.chunk().await? {
-                new_file.write_all(&chunk).await?;
+                if content.len() + chunk.len() > picture::IMAGE_MAX_SIZE {
+                    return Err(ApiError::Error(format!(
+                        "图片大小不能超过 {}M",
+                        picture::IMAGE_MAX_SIZE / 1024 / 1024
+                    )));
+                }
+                content.extend_from_slice(&chunk);
             }
 
-            new_file.sync_all().await?;
+            let dst = picture::save_image(content).await?;
+            path = Some(common::utils::image_preview_url(dst).await);
         }
 
         Ok(path)
@@ -225,6 +309,10 @@ lazy_static! {
             .add_dlx_queue(Arc::new(Box::new(DelayOrder::default())))
             .await;
 
+        mq_mamnger
+            .add_normal_queue(Arc::new(Box::new(FavoriteAlert::default())))
+            .await;
+
         Arc::new(mq_mamnger)
     });
 }
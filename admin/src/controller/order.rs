@@ -13,18 +13,28 @@ use validator::Validate;
 use common::{
     error::format_errors,
     jwt::Claims,
-    order::{OrderEvaluate, OrderShip, ReqCreateOrder, ReqInstallments},
+    order::{
+        OrderEvaluate, OrderShip, ReqCheckoutCart, ReqCreateOrder, ReqInstallments,
+        ReqReviewFollowUp,
+    },
     rabbitmq::{RabbitMQDlxQueue, RabbitMQQueue},
     ApiResponse, PagePer, Pagination,
 };
 
 use crate::models::{
     address::UserAddress,
+    cart_items::{CartItems, Checkout},
+    favorite_alerts::{FavoriteAlert, SkuWatcher},
     coupons::Coupons,
     installments::{Installments, Status},
+    invoices::Invoice,
     order_items::{ItemProductSku, OrderItems},
     orders::Orders,
     product_skus::ProductSku,
+    reviews::ProductReview,
+    shipments::Shipment,
+    shipping_templates::{FreightItem, ShippingTemplate},
+    warehouses::Warehouse,
 };
 
 pub struct OrderController;
@@ -55,6 +65,11 @@ impl OrderController {
             Ok(items) => items,
             Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
         };
+        // 发票信息, 已开票时可通过 /orders/:id/invoice 下载
+        let invoice = match Invoice::by_order(order.id).await {
+            Ok(invoice) => invoice.map(|invoice| invoice.to_json()),
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
 
         ApiResponse::response(Some(json!({
             "id": order.id,
@@ -63,6 +78,7 @@ impl OrderController {
             "user_id": order.user_id,
             "address": order.address,
             "total_amount":order.total_amount.0/100,
+            "shipping_fee": order.shipping_fee.0 as f64 / 100.0,
             "remark": order.remark,
             "paid_at": order.paid_at,
             "pay_method": order.pay_method.as_ref(),
@@ -76,6 +92,7 @@ impl OrderController {
             "created_at": order.created_at.format("%F %T").to_string(),
             "updated_at": order.updated_at.format("%F %T").to_string(),
             "items": order_items,
+            "invoice": invoice,
         })))
         .json()
     }
@@ -115,7 +132,8 @@ impl OrderController {
             Err(_err) => return ApiResponse::fail_msg("收获地址未找到".to_string()).json(),
         };
 
-        let mut order_items: HashMap<i64, ItemProductSku> = HashMap::new();
+        let mut order_items: Vec<(i64, ItemProductSku)> = Vec::new();
+        let mut freight_items: Vec<FreightItem> = Vec::new();
         let mut total_money = 0i64;
         if let Some(order) = &inner.products {
             for (idx, item) in order.iter().enumerate() {
@@ -136,8 +154,17 @@ impl OrderController {
                                 .json();
                         }
 
-                        total_money += sku.price;
-                        order_items.insert(
+                        // 订单金额以分为单位
+                        let money =
+                            common::cart::line_money(sku.price, item.amount.unwrap() as i64);
+                        total_money += money;
+                        freight_items.push(FreightItem {
+                            product_id: sku.product_id,
+                            weight: sku.weight,
+                            amount: item.amount.unwrap(),
+                            money,
+                        });
+                        order_items.push((
                             sku.product_id,
                             //商品sku相关信息
                             ItemProductSku {
@@ -145,10 +172,11 @@ impl OrderController {
                                 title: sku.title.clone(),
                                 descr: sku.descr.clone(),
                                 amount: item.amount.unwrap() as i16,
-                                price: sku.price,
+                                price: sku.price as i64,
                                 picture: sku.picture.clone(),
+                                money,
                             },
-                        );
+                        ));
                     }
                     None => {
                         return ApiResponse::fail_msg(format!("第{}项商品不存在", idx + 1)).json();
@@ -170,9 +198,14 @@ impl OrderController {
                 }
             }
         }
+        let shipping_fee = match ShippingTemplate::freight(&address, &freight_items).await {
+            Ok(fee) => fee,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
         let result = Orders::create(
             user.id,
             total_money,
+            shipping_fee,
             sqlx::types::Json(address),
             inner.remark.unwrap(),
             coupon_code,
@@ -195,6 +228,51 @@ impl OrderController {
         }
     }
 
+    // 购物车结算
+    pub async fn checkout(
+        Extension(user): Extension<Claims>,
+        Json(inner): Json<ReqCheckoutCart>,
+    ) -> impl IntoResponse {
+        if let Err(e) = inner.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let address = match UserAddress::harvest_addr(inner.address_id.unwrap(), user.id).await {
+            Ok(addr) => addr,
+            Err(_err) => return ApiResponse::fail_msg("收获地址未找到".to_string()).json(),
+        };
+
+        let result = CartItems::checkout(
+            user.id,
+            inner.cart_ids.unwrap(),
+            sqlx::types::Json(address),
+            inner.remark.unwrap_or_default(),
+            inner.coupon_code,
+            inner.confirm_price.unwrap_or(false),
+        )
+        .await;
+        match result {
+            Ok(Checkout::Created(order_id)) => {
+                let delay_order = DelayOrder {
+                    order_id: order_id,
+                    user_id: user.id,
+                    created_at: Some(chrono::Local::now().naive_local()),
+                };
+                if let Err(e) = delay_order.produce(1 * 60 * 1000).await {
+                    error!("订单加入队列失败： {}", e);
+                }
+                ApiResponse::response(Some(json!({ "id": order_id }))).json()
+            }
+            Ok(Checkout::PriceChanged(items)) => ApiResponse::success_code_data(
+                common::FAIL,
+                Some(json!({ "price_changed": items })),
+            )
+            .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
     // 更新订单(收货信息)
     pub async fn update(
         Path(id): Path<i64>,
@@ -236,16 +314,62 @@ impl OrderController {
         let company = payload.express_company.unwrap();
         let no = payload.express_no.unwrap();
         let id = payload.id.unwrap();
-
-        match Orders::ship(claims.id, id, no, company).await {
-            Ok(bool_val) => ApiResponse::response(Some(json!({
-                "status": bool_val,
+        let carrier = payload.carrier.unwrap_or_default();
+        let item_ids = payload.item_ids.unwrap_or_default();
+
+        match Orders::ship(
+            claims.id,
+            id,
+            company,
+            carrier,
+            no,
+            item_ids,
+            payload.warehouse_id,
+        )
+        .await
+        {
+            Ok(shipment_id) => ApiResponse::response(Some(json!({
+                "shipment_id": shipment_id,
             })))
             .json(),
             Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
         }
     }
 
+    // 物流信息
+    pub async fn shipments(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+    ) -> impl IntoResponse {
+        if let Err(e) = Orders::get(id, claims.id).await {
+            error!("物流信息查询订单错误: {}", e);
+            return ApiResponse::fail_msg("订单不存在".to_string()).json();
+        }
+
+        match Shipment::timeline(id).await {
+            Ok(shipments) => ApiResponse::response(Some(json!({ "shipments": shipments }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 订单分仓结果
+    pub async fn allocations(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+    ) -> impl IntoResponse {
+        if let Err(e) = Orders::get(id, claims.id).await {
+            error!("分仓信息查询订单错误: {}", e);
+            return ApiResponse::fail_msg("订单不存在".to_string()).json();
+        }
+
+        match Warehouse::order_plan(id).await {
+            Ok(warehouses) => {
+                ApiResponse::response(Some(json!({ "warehouses": warehouses }))).json()
+            }
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
     // 收货
     pub async fn received(
         Extension(claims): Extension<Claims>,
@@ -286,10 +410,44 @@ impl OrderController {
                 claims.id,
                 payload.score.unwrap() as u8,
                 payload.content.unwrap(),
+                payload.photos.unwrap_or_default(),
+                payload.anonymous.unwrap_or_default(),
+            )
+            .await
+        {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 追评
+    pub async fn follow_up(
+        Extension(claims): Extension<Claims>,
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqReviewFollowUp>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(
+                common::response::FAIL,
+                Some(json!(format_errors(e))),
+            )
+            .json();
+        }
+
+        let review = match ProductReview::get(id).await {
+            Ok(review) => review,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        match review
+            .follow_up(
+                claims.id,
+                payload.content.unwrap(),
+                payload.photos.unwrap_or_default(),
             )
             .await
         {
-            Ok(()) => ApiResponse::success().json(),
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
             Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
         }
     }
@@ -305,8 +463,15 @@ impl OrderController {
         if let Err(e) = OrderItems::evaluate_list(product_id, &mut pagination).await {
             return ApiResponse::fail_msg(e.to_string()).json();
         }
+        let summary = match ProductReview::summary(product_id).await {
+            Ok(summary) => summary,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
 
-        ApiResponse::response(Some(pagination)).json()
+        let mut result = json!(pagination);
+        result["rating"] = json!(summary);
+
+        ApiResponse::response(Some(result)).json()
     }
 
     pub async fn pay_by_installments(
@@ -440,13 +605,30 @@ impl RabbitMQQueue for DelayOrder {
                 };
 
                 if let Ok(mut tx) = common::postgres().await.begin().await {
-                    if let Err(err) = ProductSku::buckle_inventory(items, 1, &mut tx).await {
+                    let product_ids = items
+                        .iter()
+                        .flat_map(|item| item.keys().cloned())
+                        .collect::<Vec<i64>>();
+                    let watcher = SkuWatcher::watch(&product_ids, &mut tx).await;
+                    // 已分仓的订单库存退回原仓库
+                    let restored = match Warehouse::release(order.id, &mut tx).await {
+                        Ok(true) => Ok(()),
+                        Ok(false) => ProductSku::buckle_inventory(items, 1, &mut tx).await,
+                        Err(err) => Err(err),
+                    };
+                    if let Err(err) = restored {
                         error!("订单超时未支付， 增加库存失败： {}", err);
                         tx.rollback().await.unwrap();
                         return;
                     }
 
+                    // 库存恢复后通知到货
+                    let alerts = match watcher {
+                        Ok(watcher) => watcher.changes(&mut tx).await.unwrap_or_default(),
+                        Err(_) => vec![],
+                    };
                     tx.commit().await.unwrap();
+                    FavoriteAlert::publish(alerts).await;
                     info!("-------------------- success --------------------");
                     return;
                 }
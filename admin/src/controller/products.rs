@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
-use axum::extract::{Path, Query};
+use axum::body::Body;
+use axum::extract::{Multipart, Path, Query};
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::postgres::types::PgMoney;
@@ -10,12 +11,20 @@ use sqlx::types::Json as SqlxJson;
 use validator::Validate;
 use validator::ValidationError;
 
+use common::error::{format_errors, ApiError};
+use common::jwt::Claims;
+use common::spreadsheet::{self, SheetFormat};
 use common::{ApiResponse, PagePer, Pagination};
 
 use crate::models::product_property::ProductProperty;
+use crate::models::product_revisions::ProductRevision;
+use crate::models::product_schedules::ProductSchedule;
 use crate::models::{
-    cart_items::CartItems, categories::Categories, favorite_products::FavoriteProducts,
-    product_skus::ProductSku, products::Product,
+    cart_items::CartItems,
+    categories::Categories,
+    favorite_products::FavoriteProducts,
+    product_skus::ProductSku,
+    products::{PType, Product},
 };
 
 pub struct ProductController;
@@ -77,7 +86,10 @@ impl ProductController {
     }
 
     /// 创建商品
-    pub async fn create(Json(payload): Json<ReqProduct>) -> impl IntoResponse {
+    pub async fn create(
+        Extension(user): Extension<Claims>,
+        Json(payload): Json<ReqProduct>,
+    ) -> impl IntoResponse {
         match payload.validate() {
             Ok(_) => (),
             Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
@@ -91,10 +103,12 @@ impl ProductController {
             }
 
             skus.push(ProductSku {
+                id: sku.id.unwrap_or_default(),
                 title: sku.title.clone().unwrap(),
                 description: sku.description.clone().unwrap(),
                 price: sku.price.unwrap(),
                 stock: sku.stock.unwrap(),
+                weight: sku.weight.unwrap_or_default(),
                 ..ProductSku::default()
             })
         }
@@ -144,6 +158,7 @@ impl ProductController {
             },
             PgMoney::from(payload.target_amount.unwrap()),
             payload.end_at,
+            user.id,
         )
         .await;
         match result {
@@ -153,7 +168,10 @@ impl ProductController {
     }
 
     /// 更新商品
-    pub async fn update(Json(payload): Json<ReqProduct>) -> impl IntoResponse {
+    pub async fn update(
+        Extension(user): Extension<Claims>,
+        Json(payload): Json<ReqProduct>,
+    ) -> impl IntoResponse {
         match payload.validate() {
             Ok(_) => (),
             Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
@@ -167,24 +185,29 @@ impl ProductController {
             }
 
             skus.push(ProductSku {
+                id: sku.id.unwrap_or_default(),
                 title: sku.title.clone().unwrap(),
                 description: sku.description.clone().unwrap(),
                 price: sku.price.unwrap(),
                 stock: sku.stock.unwrap(),
+                weight: sku.weight.unwrap_or_default(),
                 ..ProductSku::default()
             })
         }
 
-        let result = Product::update(Product {
-            id: payload.id.unwrap() as i64,
-            title: payload.title.clone().unwrap(),
-            long_title: payload.long_title.clone().unwrap(),
-            description: payload.description.clone().unwrap(),
-            image: payload.image.clone().unwrap(),
-            on_sale: payload.on_sale.unwrap(),
-            skus,
-            ..Product::default()
-        })
+        let result = Product::update(
+            Product {
+                id: payload.id.unwrap() as i64,
+                title: payload.title.clone().unwrap(),
+                long_title: payload.long_title.clone().unwrap(),
+                description: payload.description.clone().unwrap(),
+                image: payload.image.clone().unwrap(),
+                on_sale: payload.on_sale.unwrap(),
+                skus,
+                ..Product::default()
+            },
+            user.id,
+        )
         .await;
         match result {
             Ok(bool_val) => {
@@ -216,6 +239,241 @@ impl ProductController {
         }
     }
 
+    /// 批量导入商品(csv/xlsx), 每行一个sku, 商品名称相同的行归为同一个商品
+    pub async fn import(
+        Extension(user): Extension<Claims>,
+        mut multipart: Multipart,
+    ) -> impl IntoResponse {
+        let mut content: Option<(SheetFormat, Vec<u8>)> = None;
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+            };
+            if field.name() != Some("file") {
+                continue;
+            }
+
+            let format = match SheetFormat::from_filename(field.file_name().unwrap_or_default()) {
+                Ok(format) => format,
+                Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+            };
+            match field.bytes().await {
+                Ok(bytes) => content = Some((format, bytes.to_vec())),
+                Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+            }
+        }
+
+        let rows = match content {
+            Some((format, bytes)) => match spreadsheet::read_rows(format, &bytes) {
+                Ok(rows) => rows,
+                Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+            },
+            None => return ApiResponse::fail_msg("请上传需要导入的文件".to_string()).json(),
+        };
+        if rows.is_empty() {
+            return ApiResponse::fail_msg("导入文件中没有数据".to_string()).json();
+        }
+
+        let categories = match Categories::id_by_path_names().await {
+            Ok(categories) => categories,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        let (products, errors) = import_products(&rows, &categories);
+        if !errors.is_empty() {
+            return ApiResponse {
+                code: common::FAIL,
+                message: Some(ApiError::ArrayMap(errors)),
+                data: None,
+            }
+            .json();
+        }
+
+        match Product::import(products, user.id).await {
+            Ok((created, updated)) => ApiResponse::response(Some(json!({
+                "created": created,
+                "updated": updated,
+            })))
+            .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 导出商品(csv/xlsx), 格式与导入文件一致
+    pub async fn export(Query(payload): Query<HashMap<String, String>>) -> impl IntoResponse {
+        let format = payload
+            .get("format")
+            .map(|v| v.as_str())
+            .unwrap_or("xlsx");
+        let format = match SheetFormat::from_filename(format) {
+            Ok(format) => format,
+            Err(e) => return e.into_response(),
+        };
+
+        let (products, categories) = match (Product::export().await, Categories::path_names().await)
+        {
+            (Ok(products), Ok(categories)) => (products, categories),
+            (Err(e), _) | (_, Err(e)) => return e.into_response(),
+        };
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for (product, crowdfunding) in products.iter() {
+            let (target_amount, end_at) = match crowdfunding {
+                Some(crowdfunding) => (
+                    crowdfunding.target_amount.0.to_string(),
+                    crowdfunding.end_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                ),
+                None => (String::new(), String::new()),
+            };
+            let property = product
+                .property
+                .iter()
+                .map(|p| format!("{}:{}", p.name, p.value))
+                .collect::<Vec<String>>()
+                .join(";");
+            for sku in product.skus.iter() {
+                rows.push(vec![
+                    product.title.clone(),
+                    product.long_title.clone(),
+                    product.description.clone(),
+                    product.image.0.join(","),
+                    product.on_sale.to_string(),
+                    categories
+                        .get(&product.category_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                    match product.r#type {
+                        PType::Normal => "1".to_string(),
+                        PType::Crowdfunding => "2".to_string(),
+                    },
+                    target_amount.clone(),
+                    end_at.clone(),
+                    sku.title.clone(),
+                    sku.description.clone(),
+                    sku.price.to_string(),
+                    sku.stock.to_string(),
+                    sku.weight.to_string(),
+                    property.clone(),
+                ]);
+            }
+        }
+
+        match spreadsheet::write_rows(format, &SHEET_HEADERS, &rows) {
+            Ok(content) => ApiResponse::<Vec<u8>>::set_content_type(Some(format.content_type()))
+                .header(
+                    "Content-Disposition",
+                    format!(
+                        "attachment; filename=products-{}.{}",
+                        chrono::Local::now().format("%Y%m%d%H%M%S"),
+                        format.extension()
+                    ),
+                )
+                .body(Body::from(content))
+                .unwrap()
+                .into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+
+    /// 商品修改记录
+    pub async fn revisions(
+        Path(product_id): Path<i64>,
+        Query(page_per): Query<PagePer>,
+    ) -> impl IntoResponse {
+        let mut pagination: Pagination<ProductRevision> = Pagination::new(vec![], page_per);
+
+        match ProductRevision::index(product_id, &mut pagination).await {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 对比两个修改记录, to 为空时与最新版本对比
+    pub async fn revision_diff(
+        Path(product_id): Path<i64>,
+        Query(payload): Query<HashMap<String, i64>>,
+    ) -> impl IntoResponse {
+        let from = match payload.get("from") {
+            Some(&id) => id,
+            None => return ApiResponse::fail_msg("请选择需要对比的版本".to_string()).json(),
+        };
+        let to = match payload.get("to") {
+            Some(&id) => ProductRevision::get(id, product_id).await,
+            None => ProductRevision::latest(product_id).await,
+        };
+
+        match (ProductRevision::get(from, product_id).await, to) {
+            (Ok(from), Ok(to)) => ApiResponse::response(Some(json!({
+                "from": from.id,
+                "to": to.id,
+                "changes": from.diff(&to),
+            })))
+            .json(),
+            (Err(e), _) | (_, Err(e)) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 恢复到指定的修改记录
+    pub async fn restore_revision(
+        Extension(user): Extension<Claims>,
+        Path((product_id, revision_id)): Path<(i64, i64)>,
+    ) -> impl IntoResponse {
+        let revision = match ProductRevision::get(revision_id, product_id).await {
+            Ok(revision) => revision,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        match revision.restore(user.id).await {
+            Ok(id) => ApiResponse::response(Some(json!({ "revision_id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 商品待执行的定时任务
+    pub async fn schedules(Path(product_id): Path<i64>) -> impl IntoResponse {
+        match ProductSchedule::index(product_id).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 定时上下架、调价
+    pub async fn create_schedule(
+        Extension(user): Extension<Claims>,
+        Path(product_id): Path<i64>,
+        Json(payload): Json<ReqProductSchedule>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = ProductSchedule::create(
+            product_id,
+            user.id,
+            payload.on_sale,
+            payload.sku_prices.unwrap_or_default(),
+            payload.run_at.unwrap(),
+        )
+        .await;
+        match result {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 取消定时任务
+    pub async fn cancel_schedule(
+        Path((product_id, schedule_id)): Path<(i64, i64)>,
+    ) -> impl IntoResponse {
+        match ProductSchedule::cancel(schedule_id, product_id).await {
+            Ok(bool_val) => ApiResponse::response(Some(json!({ "status": bool_val }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
     /// 收藏商品
     pub async fn favorite_product(
         Path((product_id, user_id)): Path<(u64, u64)>,
@@ -228,6 +486,32 @@ impl ProductController {
         }
     }
 
+    /// 设置收藏商品的降价、到货提醒
+    pub async fn favorite_alerts(
+        Path(product_id): Path<i64>,
+        Extension(user): Extension<Claims>,
+        Json(payload): Json<ReqFavoriteAlert>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match FavoriteProducts::update_alerts(
+            user.id,
+            product_id,
+            payload.notify_price_drop,
+            payload.notify_restock,
+            payload.target_price,
+        )
+        .await
+        {
+            Ok(false) => ApiResponse::fail_msg("该商品未收藏".to_string()).json(),
+            Ok(true) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
     /// 取消收藏
     pub async fn un_favorite_product(
         Path((product_id, user_id)): Path<(u64, u64)>,
@@ -266,6 +550,8 @@ pub struct ReqProduct {
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct ReqProductSku {
+    // 已有sku的id, 修改时传入可修改sku名称; 不传时按名称匹配已有的sku
+    pub id: Option<i64>,
     #[validate(length(max = 100))]
     pub title: Option<String>,
     #[validate(required)]
@@ -274,6 +560,28 @@ pub struct ReqProductSku {
     pub price: Option<f64>,
     #[validate(range(min = 1))]
     pub stock: Option<i32>,
+    // 重量(克)
+    #[validate(range(min = 0, max = 1000000))]
+    pub weight: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ReqProductSchedule {
+    pub on_sale: Option<bool>,
+    // {"sku_id": price}
+    #[validate(custom = "positive_prices")]
+    pub sku_prices: Option<HashMap<i64, f64>>,
+    #[validate(required(message = "请选择执行时间"))]
+    pub run_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ReqFavoriteAlert {
+    pub notify_price_drop: Option<bool>,
+    pub notify_restock: Option<bool>,
+    // 期望价格, 为空时任意降价都提醒
+    #[validate(range(min = 0.01, message = "期望价格必须大于0"))]
+    pub target_price: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
@@ -284,6 +592,224 @@ pub struct ReqProductProperty {
     pub value: Option<String>,
 }
 
+/// 导入导出文件表头
+const SHEET_HEADERS: [&str; 15] = [
+    "title",
+    "long_title",
+    "description",
+    "image",
+    "on_sale",
+    "category",
+    "type",
+    "target_amount",
+    "end_at",
+    "sku_title",
+    "sku_description",
+    "sku_price",
+    "sku_stock",
+    "sku_weight",
+    "property",
+];
+
+/// 解析导入的表格数据, 使用与创建商品相同的验证规则, 返回每一行的错误信息
+fn import_products(
+    rows: &[HashMap<String, String>],
+    categories: &HashMap<String, i64>,
+) -> (
+    Vec<(Product, PgMoney, Option<chrono::NaiveDateTime>)>,
+    Vec<HashMap<String, String>>,
+) {
+    let mut errors: Vec<HashMap<String, String>> = Vec::new();
+    let mut row_error = |row_no: usize, field: &str, message: String| {
+        errors.push(HashMap::from([
+            ("row".to_string(), row_no.to_string()),
+            (field.to_string(), message),
+        ]));
+    };
+
+    // 按商品名称分组, 保持文件中的先后顺序
+    let mut groups: Vec<(String, Vec<(usize, &HashMap<String, String>)>)> = Vec::new();
+    for (idx, row) in rows.iter().enumerate() {
+        // 第一行为表头
+        let row_no = idx + 2;
+        let title = row.get("title").cloned().unwrap_or_default();
+        match groups.iter_mut().find(|(t, _)| t == &title) {
+            Some((_, items)) => items.push((row_no, row)),
+            None => groups.push((title, vec![(row_no, row)])),
+        }
+    }
+
+    let mut products = Vec::with_capacity(groups.len());
+    for (title, items) in groups {
+        let (row_no, first) = items[0];
+        let field = |key: &str| first.get(key).cloned().filter(|v| !v.is_empty());
+
+        let mut skus: Vec<ReqProductSku> = Vec::with_capacity(items.len());
+        for (sku_row_no, row) in items.iter() {
+            let sku = ReqProductSku {
+                title: row.get("sku_title").cloned(),
+                description: row.get("sku_description").cloned().filter(|v| !v.is_empty()),
+                price: parse_cell(row, "sku_price", *sku_row_no, &mut row_error),
+                stock: parse_cell(row, "sku_stock", *sku_row_no, &mut row_error),
+                weight: parse_cell(row, "sku_weight", *sku_row_no, &mut row_error),
+            };
+            if let Err(e) = sku.validate() {
+                for err in format_errors(e) {
+                    for (key, message) in err {
+                        row_error(*sku_row_no, &format!("sku_{}", key), message);
+                    }
+                }
+            }
+            skus.push(sku);
+        }
+
+        let property = items
+            .iter()
+            .filter_map(|(_, row)| row.get("property").filter(|v| !v.is_empty()))
+            .next()
+            .map(|value| {
+                value
+                    .split(';')
+                    .filter(|v| !v.trim().is_empty())
+                    .map(|item| {
+                        let (name, value) = item.split_once(':').unwrap_or((item, ""));
+                        ReqProductProperty {
+                            name: Some(name.trim().to_string()),
+                            value: Some(value.trim().to_string()),
+                        }
+                    })
+                    .collect::<Vec<ReqProductProperty>>()
+            });
+        for item in property.iter().flatten() {
+            if let Err(e) = item.validate() {
+                for err in format_errors(e) {
+                    for (key, message) in err {
+                        row_error(row_no, &format!("property_{}", key), message);
+                    }
+                }
+            }
+        }
+
+        let category_id = match field("category") {
+            Some(path) => match categories.get(&path) {
+                Some(&id) => Some(id),
+                None => {
+                    row_error(row_no, "category", format!("类目[{}]不存在", path));
+                    None
+                }
+            },
+            None => Some(0),
+        };
+
+        let payload = ReqProduct {
+            id: None,
+            title: Some(title.clone()),
+            long_title: field("long_title"),
+            image: field("image").map(|v| {
+                SqlxJson(
+                    v.split(',')
+                        .map(|i| i.trim().to_string())
+                        .collect::<Vec<String>>(),
+                )
+            }),
+            description: field("description"),
+            on_sale: parse_cell(first, "on_sale", row_no, &mut row_error),
+            skus: Some(skus),
+            property,
+            category_id,
+            r#type: parse_cell::<u8>(first, "type", row_no, &mut row_error).or(Some(1)),
+            target_amount: parse_cell(first, "target_amount", row_no, &mut row_error),
+            end_at: parse_cell(first, "end_at", row_no, &mut row_error),
+        };
+        if let Err(e) = payload.validate() {
+            for err in format_errors(e) {
+                for (key, message) in err {
+                    row_error(row_no, &key, message);
+                }
+            }
+        }
+
+        let p_type = match payload.r#type {
+            Some(2) => PType::Crowdfunding,
+            _ => PType::Normal,
+        };
+        if p_type == PType::Crowdfunding
+            && (payload.target_amount.is_none() || payload.end_at.is_none())
+        {
+            row_error(row_no, "type", "众筹商品需要填写目标金额及截止时间".to_string());
+        }
+
+        products.push((
+            Product {
+                title,
+                long_title: payload.long_title.unwrap_or_default(),
+                description: payload.description.unwrap_or_default(),
+                image: payload.image.unwrap_or_default(),
+                on_sale: payload.on_sale.unwrap_or_default(),
+                skus: payload
+                    .skus
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|sku| ProductSku {
+                        title: sku.title.unwrap_or_default(),
+                        description: sku.description.unwrap_or_default(),
+                        price: sku.price.unwrap_or_default(),
+                        stock: sku.stock.unwrap_or_default(),
+                        weight: sku.weight.unwrap_or_default(),
+                        ..ProductSku::default()
+                    })
+                    .collect(),
+                property: payload
+                    .property
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|val| ProductProperty {
+                        id: 0,
+                        product_id: 0,
+                        name: val.name.unwrap_or_default(),
+                        value: val.value.unwrap_or_default(),
+                    })
+                    .collect(),
+                category_id: payload.category_id.unwrap_or_default(),
+                r#type: p_type,
+                ..Product::default()
+            },
+            PgMoney::from(payload.target_amount.unwrap_or_default()),
+            payload.end_at,
+        ));
+    }
+
+    (products, errors)
+}
+
+/// 解析单元格数据, 空单元格返回 None
+fn parse_cell<T: std::str::FromStr>(
+    row: &HashMap<String, String>,
+    key: &str,
+    row_no: usize,
+    row_error: &mut impl FnMut(usize, &str, String),
+) -> Option<T> {
+    let value = row.get(key).filter(|v| !v.is_empty())?;
+    match value.parse::<T>() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            row_error(row_no, key, format!("[{}]格式错误", value));
+            None
+        }
+    }
+}
+
+/// 定时调价的价格必须大于0
+fn positive_prices(prices: &HashMap<i64, f64>) -> Result<(), ValidationError> {
+    if prices.values().all(|price| price.is_finite() && *price > 0.0) {
+        return Ok(());
+    }
+
+    let mut err = ValidationError::new("sku_prices");
+    err.message = Some("sku价格必须大于0".into());
+    Err(err)
+}
+
 /// 检测商品是否已存在
 fn unique_title(_title: &str) -> Result<(), ValidationError> {
     // 由于不能直接执行async函数，下边的代码使用方式也不正确，所有这里返回true
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use axum::extract::{Json, Path, Query};
+use axum::response::IntoResponse;
+use serde_json::json;
+use validator::Validate;
+
+use common::error::format_errors;
+use common::purchase::{ReqPurchaseOrder, ReqPurchaseReceive, ReqSupplier};
+use common::{ApiResponse, PagePer, Pagination};
+
+use crate::models::purchases::{PurchaseOrder, PurchaseStatus, Supplier};
+
+/// 补货建议默认参数: 统计天数, 采购周期, 备货天数
+const SALES_WINDOW_DAYS: i64 = 30;
+const LEAD_DAYS: u32 = 7;
+const COVER_DAYS: u32 = 30;
+
+/// 供应商及采购单管理
+pub struct PurchaseController;
+
+impl PurchaseController {
+    // 供应商列表
+    pub async fn suppliers() -> impl IntoResponse {
+        match Supplier::index().await {
+            Ok(suppliers) => ApiResponse::response(Some(suppliers)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 创建供应商
+    pub async fn create_supplier(Json(payload): Json<ReqSupplier>) -> impl IntoResponse {
+        Self::save_supplier(0, payload).await
+    }
+
+    // 修改供应商
+    pub async fn update_supplier(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqSupplier>,
+    ) -> impl IntoResponse {
+        Self::save_supplier(id, payload).await
+    }
+
+    async fn save_supplier(id: i64, payload: ReqSupplier) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Supplier::save(id, payload).await {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 采购单列表, 可按供应商、状态筛选
+    pub async fn index(
+        Query(page_per): Query<PagePer>,
+        Query(inner): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let supplier_id = inner
+            .get("supplier_id")
+            .and_then(|id| id.parse::<i64>().ok());
+        let status = inner
+            .get("status")
+            .and_then(|status| status.parse::<u8>().ok())
+            .and_then(|status| PurchaseStatus::try_from(status).ok());
+        let mut pagination = Pagination::new(vec![], page_per);
+        match PurchaseOrder::index(supplier_id, status, &mut pagination).await {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 创建采购单
+    pub async fn create(Json(payload): Json<ReqPurchaseOrder>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match PurchaseOrder::create(
+            payload.supplier_id.unwrap(),
+            payload.warehouse_id.unwrap(),
+            payload.lines.unwrap(),
+            payload.remark.unwrap_or_default(),
+        )
+        .await
+        {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 详情
+    pub async fn get(Path(id): Path<i64>) -> impl IntoResponse {
+        let result = match PurchaseOrder::get(id).await {
+            Ok(order) => order.detail().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(detail) => ApiResponse::response(Some(detail)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 确认下单
+    pub async fn submit(Path(id): Path<i64>) -> impl IntoResponse {
+        let result = match PurchaseOrder::get(id).await {
+            Ok(order) => order.submit().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 取消
+    pub async fn cancel(Path(id): Path<i64>) -> impl IntoResponse {
+        let result = match PurchaseOrder::get(id).await {
+            Ok(order) => order.cancel().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 收货, 可分批
+    pub async fn receive(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqPurchaseReceive>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match PurchaseOrder::get(id).await {
+            Ok(order) => {
+                order
+                    .receive(payload.lines.unwrap(), payload.remark.unwrap_or_default())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 补货建议, ?days=统计天数&lead_days=采购周期&cover_days=备货天数
+    pub async fn suggestions(Query(inner): Query<HashMap<String, String>>) -> impl IntoResponse {
+        let days = inner
+            .get("days")
+            .and_then(|days| days.parse::<i64>().ok())
+            .filter(|days| *days > 0)
+            .unwrap_or(SALES_WINDOW_DAYS);
+        let lead_days = inner
+            .get("lead_days")
+            .and_then(|days| days.parse::<u32>().ok())
+            .unwrap_or(LEAD_DAYS);
+        let cover_days = inner
+            .get("cover_days")
+            .and_then(|days| days.parse::<u32>().ok())
+            .unwrap_or(COVER_DAYS);
+        match PurchaseOrder::suggestions(days, lead_days, cover_days).await {
+            Ok(result) => ApiResponse::response(Some(json!({ "suggestions": result }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
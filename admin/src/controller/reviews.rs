@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use validator::Validate;
+
+use common::{
+    error::format_errors,
+    order::{ReqReviewReply, ReqReviewStatus},
+    ApiResponse, PagePer, Pagination,
+};
+
+use crate::models::reviews::{ProductReview, ReviewStatus};
+
+/// 商品评价审核及商家回复
+pub struct ReviewController;
+
+impl ReviewController {
+    /// 评价列表, 默认为待审核
+    pub async fn index(
+        Query(page_per): Query<PagePer>,
+        Query(inner): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let status = match ReviewStatus::try_from(
+            inner.get("status").map(|s| s.as_str()).unwrap_or("pending"),
+        ) {
+            Ok(status) => status,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        let mut pagination: Pagination<ProductReview> = Pagination::new(vec![], page_per);
+        if let Err(e) = ProductReview::moderation_list(status, &mut pagination).await {
+            return ApiResponse::fail_msg(e.to_string()).json();
+        }
+
+        ApiResponse::response(Some(pagination)).json()
+    }
+
+    /// 审核
+    pub async fn moderate(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqReviewStatus>,
+    ) -> impl IntoResponse {
+        let status = match ReviewStatus::try_from(payload.status.as_str()) {
+            Ok(status) => status,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        let review = match ProductReview::get(id).await {
+            Ok(review) => review,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        match review.moderate(status).await {
+            Ok(bool_val) => ApiResponse::response(Some(json!({ "status": bool_val }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 商家回复
+    pub async fn reply(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqReviewReply>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(
+                common::response::FAIL,
+                Some(json!(format_errors(e))),
+            )
+            .json();
+        }
+
+        let review = match ProductReview::get(id).await {
+            Ok(review) => review,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+
+        match review.reply(payload.content.unwrap()).await {
+            Ok(bool_val) => ApiResponse::response(Some(json!({ "status": bool_val }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
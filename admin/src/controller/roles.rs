@@ -0,0 +1,215 @@
+use axum::extract::{Json, Path, Query};
+use axum::response::IntoResponse;
+use serde_json::json;
+use validator::Validate;
+
+use common::auth::{ReqDomain, ReqPermission, ReqRole, ReqRoleGrant};
+use common::error::{format_errors, ApiResult};
+use common::route_catalog;
+use common::ApiResponse;
+
+use crate::models::permissions::Permission;
+use crate::models::roles::{EffectivePermissions, Role};
+
+/// 角色及权限目录管理
+pub struct RoleController;
+
+impl RoleController {
+    fn domain(query: ReqDomain) -> String {
+        query
+            .domain
+            .filter(|domain| !domain.is_empty())
+            .unwrap_or_else(|| common::casbin::DEFAULT_DOMAIN.to_string())
+    }
+
+    /// 角色列表
+    pub async fn index(Query(query): Query<ReqDomain>) -> impl IntoResponse {
+        match Self::roles(&Self::domain(query)).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn roles(domain: &str) -> ApiResult<Vec<serde_json::Value>> {
+        let mut result = vec![];
+        for role in Role::index().await? {
+            let mut item = role.to_json();
+            item["inherits"] = json!(role.inherits(domain).await?);
+            result.push(item);
+        }
+
+        Ok(result)
+    }
+
+    /// 创建角色
+    pub async fn create(
+        Query(query): Query<ReqDomain>,
+        Json(payload): Json<ReqRole>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Role::create(payload, &Self::domain(query)).await {
+            Ok(role) => ApiResponse::response(Some(role.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 修改角色
+    pub async fn update(
+        Path(id): Path<i64>,
+        Query(query): Query<ReqDomain>,
+        Json(payload): Json<ReqRole>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Role::update(id, payload, &Self::domain(query)).await {
+            Ok(role) => ApiResponse::response(Some(role.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 删除角色
+    pub async fn delete(Path(id): Path<i64>) -> impl IntoResponse {
+        match Role::delete(id).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 拥有该角色的用户
+    pub async fn users(Path(id): Path<i64>, Query(query): Query<ReqDomain>) -> impl IntoResponse {
+        let result = match Role::get(id).await {
+            Ok(role) => role.users(&Self::domain(query)).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(users) => ApiResponse::response(Some(users)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 角色的权限
+    pub async fn permissions(
+        Path(id): Path<i64>,
+        Query(query): Query<ReqDomain>,
+    ) -> impl IntoResponse {
+        let result = match Role::get(id).await {
+            Ok(role) => role.permissions(&Self::domain(query)).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(permissions) => ApiResponse::response(Some(
+                permissions
+                    .iter()
+                    .map(|permission| permission.to_json())
+                    .collect::<Vec<serde_json::Value>>(),
+            ))
+            .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 设置角色的权限
+    pub async fn set_permissions(
+        Path(id): Path<i64>,
+        Query(query): Query<ReqDomain>,
+        Json(payload): Json<ReqRoleGrant>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let permission_ids = payload.permission_ids.unwrap();
+        let result = match Role::get(id).await {
+            Ok(role) => {
+                role.set_permissions(&permission_ids, &Self::domain(query))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 用户最终拥有的角色及权限, 包括继承的角色
+    pub async fn user_permissions(
+        Path(user_id): Path<i64>,
+        Query(query): Query<ReqDomain>,
+    ) -> impl IntoResponse {
+        match EffectivePermissions::for_user(user_id, &Self::domain(query)).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 权限目录
+    pub async fn catalog() -> impl IntoResponse {
+        match Permission::index().await {
+            Ok(permissions) => ApiResponse::response(Some(
+                permissions
+                    .iter()
+                    .map(|permission| permission.to_json())
+                    .collect::<Vec<serde_json::Value>>(),
+            ))
+            .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 不再对应任何路由的权限及 casbin 规则
+    pub async fn stale_permissions() -> impl IntoResponse {
+        match Permission::stale(&route_catalog::routes()).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 添加权限
+    pub async fn create_permission(Json(payload): Json<ReqPermission>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Permission::create(payload).await {
+            Ok(permission) => ApiResponse::response(Some(permission.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 修改权限
+    pub async fn update_permission(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqPermission>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Permission::update(id, payload).await {
+            Ok(permission) => ApiResponse::response(Some(permission.to_json())).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 删除权限
+    pub async fn delete_permission(Path(id): Path<i64>) -> impl IntoResponse {
+        match Permission::delete(id).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use axum::extract::{Json, Path};
+use axum::response::IntoResponse;
+use serde_json::json;
+use validator::Validate;
+
+use common::error::format_errors;
+use common::freight::ChargeType;
+use common::shipping::{ReqShippingTemplate, ReqTemplateProducts};
+use common::ApiResponse;
+
+use crate::models::shipping_templates::ShippingTemplate;
+
+/// 运费模板
+pub struct ShippingTemplateController;
+
+impl ShippingTemplateController {
+    // 列表
+    pub async fn index() -> impl IntoResponse {
+        match ShippingTemplate::index().await {
+            Ok(templates) => ApiResponse::response(Some(templates)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 详情
+    pub async fn get(Path(id): Path<i64>) -> impl IntoResponse {
+        match ShippingTemplate::get(id).await {
+            Ok(template) => ApiResponse::response(Some(template)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 创建
+    pub async fn create(Json(payload): Json<ReqShippingTemplate>) -> impl IntoResponse {
+        Self::save(0, payload).await
+    }
+
+    // 修改
+    pub async fn update(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqShippingTemplate>,
+    ) -> impl IntoResponse {
+        Self::save(id, payload).await
+    }
+
+    async fn save(id: i64, payload: ReqShippingTemplate) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let charge_type = match ChargeType::try_from(payload.charge_type.unwrap()) {
+            Ok(charge_type) => charge_type,
+            Err(e) => return ApiResponse::fail_msg(e).json(),
+        };
+        match ShippingTemplate::save(
+            id,
+            payload.name.unwrap(),
+            charge_type,
+            payload.rules.unwrap(),
+            payload.is_default.unwrap_or_default(),
+        )
+        .await
+        {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 删除
+    pub async fn delete(Path(id): Path<i64>) -> impl IntoResponse {
+        match ShippingTemplate::delete(id).await {
+            Ok(bool_val) => ApiResponse::response(Some(json!({ "status": bool_val }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 设置商品的运费模板
+    pub async fn products(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqTemplateProducts>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let template = match ShippingTemplate::get(id).await {
+            Ok(template) => template,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+        match template.bind_products(payload.product_ids).await {
+            Ok(total) => ApiResponse::response(Some(json!({ "total": total }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
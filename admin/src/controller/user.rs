@@ -3,79 +3,414 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Path, Query},
+    http::{header, HeaderMap},
     response::IntoResponse,
     Extension, Json,
 };
 use serde_json::json;
+use tracing::{error, info};
 use validator::Validate;
 
-use common::error::format_errors;
+use common::cookie;
+use common::error::{format_errors, ApiError, ApiResult};
 use common::jwt::Claims;
 
 use common::{
-    jwt::{UserSource, UserType, JWT},
-    request::user::{ReqCrateUser, ReqGetUser, ReqLogin, ReqUpdateUser},
+    jwt::UserSource,
+    request::user::{
+        ReqCrateUser, ReqGetUser, ReqLogin, ReqRegister, ReqResendVerify, ReqSmsLogin, ReqSmsSend,
+        ReqUpdateUser, ReqUserStatus, ReqVerifyEmail,
+    },
     ApiResponse, PagePer, Pagination,
 };
 
 use crate::models::cart_items::CartItems;
-use crate::models::user::Admin;
+use crate::models::guest_cart::{GuestCart, GUEST_CART_COOKIE};
+use crate::models::login_events::LoginEvent;
+use crate::models::login_guard::LoginGuard;
+use crate::models::mfa::{MfaPending, UserMfa};
+use crate::models::notifications::UserNotification;
+use crate::models::sessions::Session;
+use crate::models::sms_codes::SmsCode;
+use crate::models::user::{Admin, Credential, UserStatus};
 use crate::AppState;
 
+/// 短信登录验证码场景, 注册时绑定手机号也使用该场景的验证码
+const SMS_LOGIN: &str = "login";
+
+/// 新设备登录提醒的通知类型
+const LOGIN_ALERT: &str = "login_alert";
+
+/// 登录记录返回条数
+const LOGIN_EVENTS_LIMIT: i64 = 50;
+
 pub struct AdminController;
 
 impl AdminController {
     /// 用户注册
-    pub async fn register(Json(_payload): Json<ReqGetUser>) {}
+    pub async fn register(Json(payload): Json<ReqRegister>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
 
-    /// 用户登录
-    pub async fn login(Json(payload): Json<ReqLogin>) -> impl IntoResponse {
-        match payload.validate() {
-            Ok(_) => {}
-            Err(e) => {
-                return ApiResponse::fail_msg(e.to_string()).json();
+        let email = payload.email.unwrap();
+        let result =
+            match Self::verify_phone(payload.phone.as_deref(), payload.code.as_deref()).await {
+                Ok(()) => {
+                    Admin::register(email.clone(), payload.phone, &payload.password.unwrap()).await
+                }
+                Err(e) => Err(e),
+            };
+        let result = match result {
+            Ok(id) => Self::send_verify_email(id, &email).await.map(|_| id),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 注册时填写了手机号必须先通过短信验证
+    async fn verify_phone(phone: Option<&str>, code: Option<&str>) -> ApiResult<()> {
+        let phone = match phone.filter(|phone| !phone.is_empty()) {
+            Some(phone) => phone,
+            None => return Ok(()),
+        };
+        match code {
+            Some(code) => SmsCode::verify(SMS_LOGIN, phone, code).await,
+            None => Err(ApiError::Error("请输入短信验证码".to_string())),
+        }
+    }
+
+    /// 邮箱验证
+    pub async fn verify_email(Json(payload): Json<ReqVerifyEmail>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Admin::verify_email(&payload.token.unwrap()).await {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 重新发送验证邮件, 不暴露邮箱是否已注册
+    pub async fn resend_verify(Json(payload): Json<ReqResendVerify>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let email = payload.email.unwrap();
+        let result = match Credential::by_email(&email).await {
+            Ok(Some(user)) if user.email_verified_at.is_none() => {
+                Self::send_verify_email(user.id, &email).await
             }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
         }
+    }
 
-        let email = payload.email.unwrap().clone();
-        let user = Admin::get(ReqGetUser {
-            id: None,
-            name: None,
-            age: None,
-            nickname: None,
-            phone: None,
-            email: Some(email.clone()),
-        })
-        .await;
+    // 生成验证链接, 邮件服务接入前只记录发送, 日志中不能出现token
+    async fn send_verify_email(user_id: i64, email: &str) -> ApiResult<()> {
+        Admin::email_verify_token(user_id).await?;
+        info!("邮箱验证链接已生成: {}", email);
 
-        let user = match user {
-            Ok(userinfo) => userinfo,
-            Err(e) => {
-                return ApiResponse::fail_msg(e.to_string()).json();
+        Ok(())
+    }
+
+    /// 用户登录, 失败次数过多时需要人机验证, 超过上限后锁定
+    pub async fn login(headers: HeaderMap, Json(payload): Json<ReqLogin>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::fail_msg(e.to_string()).json();
+        }
+
+        let email = payload.email.unwrap_or_default();
+        let ip = common::client_ip(&headers);
+        let captcha_required = match LoginGuard::check(&email, &ip).await {
+            Ok(required) => required,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+        if captcha_required {
+            let passed = match payload.captcha.filter(|token| !token.is_empty()) {
+                Some(token) => common::captcha().await.verify(&token, &ip).await,
+                None => Ok(false),
+            };
+            match passed {
+                Ok(true) => {}
+                Ok(false) => return Self::login_failed("请完成人机验证".to_string(), true).json(),
+                Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
             }
+        }
+
+        let user = match Credential::by_email(&email).await {
+            Ok(user) => user,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+        let result = match &user {
+            Some(user) => user.check(&payload.password.unwrap()).await,
+            None => Err(ApiError::Error("用户名或密码错误".to_string())),
+        };
+        if let Err(e) = result {
+            let user_id = user.map(|user| user.id);
+            let captcha_required =
+                Self::record_failure(user_id, &email, &headers, &e.to_string()).await;
+            return Self::login_failed(e.to_string(), captcha_required).json();
+        }
+        if let Err(e) = LoginGuard::success(&email).await {
+            error!("清除登录失败次数失败: {}", e);
+        }
+
+        match Self::complete_login(&user.unwrap(), UserSource::PC, &headers).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 登录失败, 需要人机验证时告知前端
+    fn login_failed(message: String, captcha_required: bool) -> ApiResponse<serde_json::Value> {
+        ApiResponse {
+            code: common::FAIL,
+            message: Some(ApiError::Error(message)),
+            data: Some(json!({ "captcha_required": captcha_required })),
+        }
+    }
+
+    /// 记录登录失败, 返回下次登录是否需要人机验证
+    async fn record_failure(
+        user_id: Option<i64>,
+        email: &str,
+        headers: &HeaderMap,
+        reason: &str,
+    ) -> bool {
+        let ip = common::client_ip(headers);
+        let source = format!("{:?}", UserSource::PC);
+        let user_agent = Self::user_agent(headers);
+        if let Err(e) =
+            LoginEvent::create(user_id, email, &source, &ip, &user_agent, Some(reason)).await
+        {
+            error!("记录登录失败: {}", e);
+        }
+
+        LoginGuard::fail(email, &ip).await.unwrap_or_else(|e| {
+            error!("记录登录失败次数失败: {}", e);
+            false
+        })
+    }
+
+    fn user_agent(headers: &HeaderMap) -> String {
+        headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// 发送登录验证码
+    pub async fn sms_code(
+        headers: HeaderMap,
+        Json(payload): Json<ReqSmsSend>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let phone = payload.phone.unwrap();
+        match SmsCode::send(SMS_LOGIN, &phone, &common::client_ip(&headers)).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 短信验证码登录, 未注册的手机号自动注册
+    pub async fn sms_login(
+        headers: HeaderMap,
+        Json(payload): Json<ReqSmsLogin>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let phone = payload.phone.unwrap();
+        let result = match SmsCode::verify(SMS_LOGIN, &phone, &payload.code.unwrap()).await {
+            Ok(()) => Self::phone_user(phone.trim_start_matches("+86")).await,
+            Err(e) => Err(e),
+        };
+        let user = match result {
+            Ok(user) => user,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
         };
+        if let Err(e) = user.check_status() {
+            return ApiResponse::fail_msg(e.to_string()).json();
+        }
+
+        match Self::complete_login(&user, UserSource::PC, &headers).await {
+            Ok(result) => ApiResponse::response(Some(result)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    async fn phone_user(phone: &str) -> ApiResult<Credential> {
+        if let Some(user) = Credential::by_phone(phone).await? {
+            return Ok(user);
+        }
+
+        let id = Admin::register_phone(phone).await?;
+        Credential::by_id(id)
+            .await?
+            .ok_or(ApiError::Error("注册失败, 请稍后重试".to_string()))
+    }
 
-        if user.id == 0 || user.email.clone() != email {
-            return ApiResponse::fail_msg("用户名或密码错误".to_string()).json();
+    /// 已开启两步验证或管理员必须开启时, 先返回第二步的token, 否则直接签发token
+    pub(crate) async fn complete_login(
+        user: &Credential,
+        from: UserSource,
+        headers: &HeaderMap,
+    ) -> ApiResult<serde_json::Value> {
+        let require_mfa = UserMfa::enabled(user.id).await?
+            || (user.is_admin() && common::application_config().await.mfa.require_admin);
+        if require_mfa {
+            return Ok(json!({
+                "mfa_required": true,
+                "mfa_token": MfaPending::create(user.id, from).await?,
+            }));
         }
 
-        let jwt = JWT::default();
-        let mut claims = jwt.new_claims(
-            user.id as i64,
+        Self::sign_in(user, from, headers).await.map_err(|e| {
+            error!("登录失败: {}", e);
+            ApiError::Error("登录失败, 请稍后重试".to_string())
+        })
+    }
+
+    /// 验证通过后创建会话并签发token
+    pub(crate) async fn sign_in(
+        user: &Credential,
+        from: UserSource,
+        headers: &HeaderMap,
+    ) -> ApiResult<serde_json::Value> {
+        let source = format!("{:?}", from);
+        let mut claims = common::jwt::jwt().await.new_claims(
+            user.id,
             user.email.clone(),
             user.name.clone(),
-            payload.password.unwrap().clone(),
-            UserSource::PC,
-            UserType::User,
+            "".to_string(),
+            from,
+            user.user_type(),
         );
+        let ip = common::client_ip(headers);
+        let user_agent = Self::user_agent(headers);
+        let (access_token, refresh_token) =
+            Session::create(&mut claims, ip.clone(), user_agent.clone()).await?;
+
+        if let Err(e) = Self::audit_login(user, &source, &ip, &user_agent).await {
+            error!("记录登录失败: {}", e);
+        }
+
+        // 合并游客购物车
+        if let Some(token) = cookie::get_signed(headers, GUEST_CART_COOKIE).await {
+            if let Err(e) = GuestCart::new(token).merge_into(user.id).await {
+                error!("合并游客购物车失败: {}", e);
+            }
+        }
+
+        Ok(json!({
+            "access_token": access_token,
+            "refresh_token":refresh_token,
+        }))
+    }
+
+    /// 记录登录, 新设备或新网段登录时发送提醒, 首次登录不提醒
+    async fn audit_login(
+        user: &Credential,
+        source: &str,
+        ip: &str,
+        user_agent: &str,
+    ) -> ApiResult<()> {
+        let history = LoginEvent::history(user.id, ip, user_agent).await?;
+        LoginEvent::create(Some(user.id), &user.email, source, ip, user_agent, None).await?;
+        if !history.is_unusual() {
+            return Ok(());
+        }
+
+        UserNotification::create_many(
+            &[user.id],
+            LOGIN_ALERT,
+            "新设备登录提醒",
+            json!({
+                "ip": ip,
+                "user_agent": user_agent,
+                "source": source,
+                "new_device": !history.known_device,
+                "new_location": !history.known_network,
+                "login_at": common::time_ymd_his(chrono::Local::now().naive_local()),
+            }),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// 退出登录
+    pub async fn logout(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match Session::revoke(user.id, &user.sid).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 退出全部设备
+    pub async fn logout_all(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match Session::revoke_all(user.id).await {
+            Ok(total) => ApiResponse::response(Some(json!({ "total": total }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
 
-        match jwt.token_info(&mut claims) {
-            Ok((access_token, refresh_token)) => ApiResponse::response(Some(json!({
-                "access_token": access_token,
-                "refresh_token":refresh_token,
-            })))
+    /// 登录设备列表
+    pub async fn sessions(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match Session::list(user.id).await {
+            Ok(sessions) => ApiResponse::response(Some(
+                sessions
+                    .iter()
+                    .map(|session| session.to_json(&user.sid))
+                    .collect::<Vec<serde_json::Value>>(),
+            ))
             .json(),
-            Err(_) => ApiResponse::fail_msg("登录失败, 请稍后重试".to_string()).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 最近的登录记录
+    pub async fn login_events(Extension(user): Extension<Claims>) -> impl IntoResponse {
+        match LoginEvent::recent(user.id, LOGIN_EVENTS_LIMIT).await {
+            Ok(events) => ApiResponse::response(Some(
+                events
+                    .iter()
+                    .map(|event| event.to_json())
+                    .collect::<Vec<serde_json::Value>>(),
+            ))
+            .json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    /// 下线指定设备
+    pub async fn revoke_session(
+        Extension(user): Extension<Claims>,
+        Path(sid): Path<String>,
+    ) -> impl IntoResponse {
+        match Session::revoke(user.id, &sid).await {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
         }
     }
 
@@ -132,6 +467,35 @@ impl AdminController {
         ApiResponse::response(Some(Admin::delete(userid).await)).json()
     }
 
+    /// 禁用、锁定或恢复账号
+    pub async fn set_status(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqUserStatus>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let status = match UserStatus::try_from(payload.status.unwrap()) {
+            Ok(status) => status,
+            Err(e) => return ApiResponse::fail_msg(e.to_string()).json(),
+        };
+        let locked_until = payload
+            .locked_minutes
+            .map(|minutes| chrono::Local::now().naive_local() + chrono::Duration::minutes(minutes));
+        let result = match Admin::set_status(id, status, locked_until).await {
+            // 禁用、锁定后强制下线
+            Ok(true) if status != UserStatus::Normal => Session::revoke_all(id).await.map(|_| true),
+            result => result,
+        };
+        match result {
+            Ok(true) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Ok(false) => ApiResponse::fail_msg("用户不存在".to_string()).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
     /// 用户列表
     pub async fn lists(
         Query(params): Query<serde_json::Value>,
@@ -189,4 +553,28 @@ impl AdminController {
             Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
         }
     }
+
+    /// 站内通知列表
+    pub async fn notifications(
+        Query(page_per): Query<PagePer>,
+        Extension(user): Extension<Claims>,
+    ) -> impl IntoResponse {
+        let mut pagination: Pagination<UserNotification> = Pagination::new(vec![], page_per);
+
+        match UserNotification::index(user.id, &mut pagination).await {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
+        }
+    }
+
+    /// 通知标记已读
+    pub async fn read_notifications(
+        Extension(user): Extension<Claims>,
+        Json(ids): Json<Vec<i64>>,
+    ) -> impl IntoResponse {
+        match UserNotification::read(user.id, ids).await {
+            Ok(rows) => ApiResponse::response(Some(json!({ "rows": rows }))).json(),
+            Err(err) => ApiResponse::fail_msg(err.to_string()).json(),
+        }
+    }
 }
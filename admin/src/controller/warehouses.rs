@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use axum::extract::{Json, Path, Query};
+use axum::response::IntoResponse;
+use serde_json::json;
+use validator::Validate;
+
+use common::error::format_errors;
+use common::warehouse::{ReqStockAdjust, ReqStockTransfer, ReqWarehouse};
+use common::{ApiResponse, PagePer, Pagination};
+
+use crate::models::warehouses::Warehouse;
+
+/// 低库存默认阈值
+const LOW_STOCK: i32 = 10;
+
+/// 仓库及库存管理
+pub struct WarehouseController;
+
+impl WarehouseController {
+    // 列表
+    pub async fn index() -> impl IntoResponse {
+        match Warehouse::index().await {
+            Ok(warehouses) => ApiResponse::response(Some(warehouses)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 创建
+    pub async fn create(Json(payload): Json<ReqWarehouse>) -> impl IntoResponse {
+        Self::save(0, payload).await
+    }
+
+    // 修改
+    pub async fn update(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqWarehouse>,
+    ) -> impl IntoResponse {
+        Self::save(id, payload).await
+    }
+
+    async fn save(id: i64, payload: ReqWarehouse) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Warehouse::save(id, payload).await {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 仓库库存明细, low: 只看低于该库存的sku
+    pub async fn stocks(
+        Path(id): Path<i64>,
+        Query(page_per): Query<PagePer>,
+        Query(inner): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let low = inner.get("low").and_then(|low| low.parse::<i32>().ok());
+        let mut pagination = Pagination::new(vec![], page_per);
+        let result = match Warehouse::get(id).await {
+            Ok(warehouse) => warehouse.stocks(low, &mut pagination).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(pagination)).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 盘点
+    pub async fn adjust(
+        Path(id): Path<i64>,
+        Json(payload): Json<ReqStockAdjust>,
+    ) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        let result = match Warehouse::get(id).await {
+            Ok(warehouse) => {
+                warehouse
+                    .adjust(payload.sku_id.unwrap(), payload.stock.unwrap())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => ApiResponse::response(Some(json!({ "status": true }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 调拨
+    pub async fn transfer(Json(payload): Json<ReqStockTransfer>) -> impl IntoResponse {
+        if let Err(e) = payload.validate() {
+            return ApiResponse::success_code_data(common::FAIL, Some(json!(format_errors(e))))
+                .json();
+        }
+
+        match Warehouse::transfer(
+            payload.sku_id.unwrap(),
+            payload.from_warehouse_id.unwrap(),
+            payload.to_warehouse_id.unwrap(),
+            payload.amount.unwrap(),
+            payload.remark.unwrap_or_default(),
+        )
+        .await
+        {
+            Ok(id) => ApiResponse::response(Some(json!({ "id": id }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+
+    // 库存报表
+    pub async fn report(Query(inner): Query<HashMap<String, String>>) -> impl IntoResponse {
+        let low = inner
+            .get("low")
+            .and_then(|low| low.parse::<i32>().ok())
+            .unwrap_or(LOW_STOCK);
+        match Warehouse::report(low).await {
+            Ok(report) => ApiResponse::response(Some(json!({ "warehouses": report }))).json(),
+            Err(e) => ApiResponse::fail_msg(e.to_string()).json(),
+        }
+    }
+}
@@ -2,15 +2,42 @@ extern crate cron_job;
 
 use chrono::FixedOffset;
 use cron_job::CronJob;
+use tokio::runtime::Handle;
 
 pub use crate::jobs::calculate_fine::calculate_installment_fine;
 use crate::jobs::calculate_fine::OverdueRate;
+use crate::jobs::product_schedule::PublishSchedule;
+use crate::jobs::purchases::SalesSnapshot;
+use crate::jobs::shipments::{AutoReceive, TrackShipments};
 
 pub mod calculate_fine;
+pub mod product_schedule;
+pub mod purchases;
+pub mod shipments;
 
-pub fn start_jobs() {
+/// 启动定时任务, 异步任务投递到 handle 所在的运行时中执行
+pub fn start_jobs(handle: Handle) {
     let mut cron = CronJob::new(FixedOffset::west_opt(-8), 50);
     cron.new_job("0 * * * * *", OverdueRate);
+    cron.new_job(
+        "0 * * * * *",
+        PublishSchedule {
+            handle: handle.clone(),
+        },
+    );
+    cron.new_job(
+        "0 */10 * * * *",
+        TrackShipments {
+            handle: handle.clone(),
+        },
+    );
+    cron.new_job(
+        "0 0 * * * *",
+        AutoReceive {
+            handle: handle.clone(),
+        },
+    );
+    cron.new_job("0 0 1 * * *", SalesSnapshot { handle });
 
     cron.start();
 }
@@ -0,0 +1,24 @@
+extern crate cron_job;
+
+use cron_job::Job;
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+use crate::models::product_schedules::ProductSchedule;
+
+/// 执行到期的商品定时上下架、调价任务
+pub struct PublishSchedule {
+    pub handle: Handle,
+}
+
+impl Job for PublishSchedule {
+    fn run(&mut self) {
+        self.handle.spawn(async {
+            match ProductSchedule::run_due().await {
+                Ok(0) => {}
+                Ok(total) => info!("商品定时任务执行完成: {} 条", total),
+                Err(e) => error!("商品定时任务执行失败: {}", e),
+            }
+        });
+    }
+}
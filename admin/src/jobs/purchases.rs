@@ -0,0 +1,23 @@
+extern crate cron_job;
+
+use cron_job::Job;
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+use crate::models::purchases::PurchaseOrder;
+
+/// 每日记录商品销量快照, 用于计算补货建议
+pub struct SalesSnapshot {
+    pub handle: Handle,
+}
+
+impl Job for SalesSnapshot {
+    fn run(&mut self) {
+        self.handle.spawn(async {
+            match PurchaseOrder::snapshot_sales().await {
+                Ok(total) => info!("商品销量快照完成: {} 个商品", total),
+                Err(e) => error!("商品销量快照失败: {}", e),
+            }
+        });
+    }
+}
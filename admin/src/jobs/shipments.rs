@@ -0,0 +1,46 @@
+extern crate cron_job;
+
+use cron_job::Job;
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+use crate::models::orders::Orders;
+use crate::models::shipments::Shipment;
+
+/// 查询未签收包裹的物流轨迹
+pub struct TrackShipments {
+    pub handle: Handle,
+}
+
+impl Job for TrackShipments {
+    fn run(&mut self) {
+        self.handle.spawn(async {
+            match Shipment::poll_due().await {
+                Ok(0) => {}
+                Ok(total) => info!("物流轨迹更新完成: {} 个包裹", total),
+                Err(e) => error!("物流轨迹更新失败: {}", e),
+            }
+        });
+    }
+}
+
+/// 签收后超过配置天数自动确认收货
+pub struct AutoReceive {
+    pub handle: Handle,
+}
+
+impl Job for AutoReceive {
+    fn run(&mut self) {
+        self.handle.spawn(async {
+            let days = common::application_config()
+                .await
+                .shipment
+                .auto_receive_days;
+            match Orders::auto_receive(days).await {
+                Ok(0) => {}
+                Ok(total) => info!("自动确认收货: {} 个订单", total),
+                Err(e) => error!("自动确认收货失败: {}", e),
+            }
+        });
+    }
+}
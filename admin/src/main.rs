@@ -2,10 +2,12 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use axum::middleware as AxumMiddleware;
 use axum::Extension;
-use tracing::info;
+use tracing::{error, info};
 
 use crate::controller::MQMANAGER;
+use crate::models::permissions::Permission;
 
 mod controller;
 mod jobs;
@@ -28,14 +30,27 @@ async fn main() {
 
     let app_state = Arc::new(AppState {});
 
-    let router = router::routers().await.layer(Extension(app_state));
+    let router = router::routers()
+        .await
+        .layer(AxumMiddleware::from_fn(middleware::real_ip))
+        .layer(Extension(app_state));
+    // 按路由表同步权限目录
+    match Permission::sync(&common::route_catalog::routes()).await {
+        Ok((created, updated)) => info!(
+            "permissions synced, created: {}, updated: {}",
+            created, updated
+        ),
+        Err(e) => error!("permissions sync failed: {}", e),
+    }
 
     MQMANAGER.get().await;
+    let handle = tokio::runtime::Handle::current();
+    std::thread::spawn(move || jobs::start_jobs(handle));
     common::elasticsearch::client().await;
 
     info!("admin-srv run at: {}", addr);
     axum::Server::bind(&addr)
-        .serve(router.into_make_service())
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
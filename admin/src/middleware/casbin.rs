@@ -11,7 +11,7 @@ use axum::{
 use futures::future::BoxFuture;
 use tower::{Layer, Service};
 
-use common::casbin::CasbinVals;
+use common::casbin::{self, CasbinVals};
 use common::jwt::Claims;
 
 #[derive(Clone)]
@@ -49,20 +49,14 @@ where
         let mut inner = std::mem::replace(&mut self.inner, not_ready_inner);
 
         let subject = match req.extensions().get::<Claims>() {
-            Some(user) => {
-                if user.id == 1 {
-                    "super_admin".to_string()
-                } else {
-                    format!("user:{}", user.id)
-                }
-            }
+            Some(user) => casbin::subject(user.id),
             None => String::from(""),
         };
 
         Box::pin(async move {
             req.extensions_mut().insert(CasbinVals {
                 subject: subject,
-                domain: Some("localhost".to_string()),
+                domain: Some(casbin::DEFAULT_DOMAIN.to_string()),
             });
             inner.call(req).await
         })
@@ -1,25 +1,46 @@
+use std::net::SocketAddr;
+
+use axum::extract::ConnectInfo;
 use axum::headers::authorization::Bearer;
 use axum::headers::Authorization;
-use axum::http::{Request, StatusCode};
+use axum::http::{HeaderValue, Request, StatusCode};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 use axum::TypedHeader;
 
 use common::error::{ApiError, ApiResult};
-use common::jwt::JWT;
 use common::ApiResponse;
 
+use crate::models::sessions::Session;
+
 pub(crate) mod casbin;
 
+/// 按连接地址重写客户端ip请求头, 不可信来源传入的 X-Forwarded-For / X-Real-IP 被忽略
+pub async fn real_ip<B>(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let cfg = common::application_config().await;
+    let ip = common::real_ip(req.headers(), peer.ip(), &cfg.trusted_proxies);
+
+    let headers = req.headers_mut();
+    headers.remove("x-forwarded-for");
+    if let Ok(value) = HeaderValue::from_str(&ip.to_string()) {
+        headers.insert("x-real-ip", value);
+    }
+
+    next.run(req).await
+}
+
 /// 登录守卫
 pub async fn auth_guard<B>(
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     mut req: Request<B>,
     next: Next<B>,
 ) -> ApiResult<Response> {
-    match JWT::default().verify(auth.token()) {
+    match common::jwt::jwt().await.verify(auth.token()) {
         Ok(claims) => {
-            // 验证redis 用户登录信息
             if claims.token_type() != "access_token" && req.uri().path() != "/refresh_token" {
                 return Ok(ApiResponse::<i32>::fail_msg_code(
                     StatusCode::UNAUTHORIZED.as_u16(),
@@ -28,6 +49,17 @@ pub async fn auth_guard<B>(
                 .into_response());
             }
 
+            // 验证redis 用户登录信息, refresh_token 在刷新时校验
+            if claims.token_type() == "access_token" {
+                if let Err(e) = Session::check(&claims).await {
+                    return Ok(ApiResponse::<i32>::fail_msg_code(
+                        StatusCode::UNAUTHORIZED.as_u16(),
+                        e.to_string(),
+                    )
+                    .into_response());
+                }
+            }
+
             // 携带用户信息到下游去
             req.extensions_mut().insert(claims);
             Ok(next.run(req).await)
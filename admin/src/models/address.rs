@@ -219,6 +219,16 @@ impl UserAddress {
                 "address".to_string(),
                 serde_json::to_value(&info.address).unwrap(),
             ),
+            // 地区id, 用于计算运费
+            (
+                "province_id".to_string(),
+                serde_json::to_value(info.province).unwrap(),
+            ),
+            ("city_id".to_string(), serde_json::to_value(info.city).unwrap()),
+            (
+                "district_id".to_string(),
+                serde_json::to_value(info.district).unwrap(),
+            ),
         ]))
     }
 }
@@ -0,0 +1,604 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::postgres::types::PgMoney;
+use sqlx::types::Json;
+use sqlx::{Postgres, Row, Transaction};
+
+use common::error::{ApiError, ApiResult};
+use common::Pagination;
+
+use crate::models::order_items::OrderItems;
+use crate::models::orders::Orders;
+use crate::models::shipments::Shipment;
+use crate::models::warehouses::Warehouse;
+use crate::models::LogisticStatus;
+
+/// 售后类型
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum AfterSaleType {
+    // 退货退款
+    Return = 1,
+    // 换货
+    Exchange = 2,
+}
+
+impl Default for AfterSaleType {
+    fn default() -> Self {
+        AfterSaleType::Return
+    }
+}
+
+impl TryFrom<u8> for AfterSaleType {
+    type Error = ApiError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(AfterSaleType::Return),
+            2 => Ok(AfterSaleType::Exchange),
+            _ => Err(ApiError::Error("售后类型错误".to_string())),
+        }
+    }
+}
+
+impl AsRef<str> for AfterSaleType {
+    fn as_ref(&self) -> &str {
+        match self {
+            AfterSaleType::Return => "退货退款",
+            AfterSaleType::Exchange => "换货",
+        }
+    }
+}
+
+/// 售后状态
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum AfterSaleStatus {
+    // 待审核
+    Pending = 1,
+    // 审核通过, 待寄回
+    Approved = 2,
+    // 已拒绝
+    Rejected = 3,
+    // 已寄回, 待验货
+    Returned = 4,
+    // 验货通过, 待退款/换货
+    Inspected = 5,
+    // 验货不通过
+    InspectFailed = 6,
+    // 已退款
+    Refunded = 7,
+    // 已换货发出
+    Reshipped = 8,
+    // 已取消
+    Cancelled = 9,
+}
+
+impl Default for AfterSaleStatus {
+    fn default() -> Self {
+        AfterSaleStatus::Pending
+    }
+}
+
+impl AsRef<str> for AfterSaleStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            AfterSaleStatus::Pending => "待审核",
+            AfterSaleStatus::Approved => "待寄回",
+            AfterSaleStatus::Rejected => "已拒绝",
+            AfterSaleStatus::Returned => "待验货",
+            AfterSaleStatus::Inspected => "验货通过",
+            AfterSaleStatus::InspectFailed => "验货不通过",
+            AfterSaleStatus::Refunded => "已退款",
+            AfterSaleStatus::Reshipped => "已换货",
+            AfterSaleStatus::Cancelled => "已取消",
+        }
+    }
+}
+
+impl AfterSaleStatus {
+    /// 售后是否已结束
+    pub fn finished(&self) -> bool {
+        matches!(
+            self,
+            AfterSaleStatus::Rejected
+                | AfterSaleStatus::InspectFailed
+                | AfterSaleStatus::Refunded
+                | AfterSaleStatus::Reshipped
+                | AfterSaleStatus::Cancelled
+        )
+    }
+}
+
+/// 售后单, 关联订单商品
+#[derive(Debug, sqlx::FromRow)]
+pub struct AfterSale {
+    pub id: i64,
+    pub no: String,
+    pub user_id: i64,
+    pub order_id: i64,
+    pub order_item_id: i64,
+    pub r#type: AfterSaleType,
+    pub status: AfterSaleStatus,
+    // 售后数量
+    pub amount: i16,
+    pub reason: String,
+    pub photos: Json<Vec<String>>,
+    pub refund_amount: PgMoney,
+    pub refund_no: Option<String>,
+    // 寄回的物流信息
+    pub return_company: Option<String>,
+    pub return_tracking_no: Option<String>,
+    // 换货发出的包裹
+    pub shipment_id: i64,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// 售后进度
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AfterSaleLog {
+    pub id: i64,
+    pub after_sale_id: i64,
+    pub status: AfterSaleStatus,
+    // user: 用户, admin: 商家
+    pub operator: String,
+    pub remark: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl AfterSale {
+    /// 申请售后, 订单确认收货后才能申请
+    pub async fn apply(
+        user_id: i64,
+        item: &OrderItems,
+        r#type: AfterSaleType,
+        amount: i16,
+        reason: String,
+        photos: Vec<String>,
+    ) -> ApiResult<i64> {
+        let order = Orders::get(item.order_id, user_id).await?;
+        if order.ship_status != LogisticStatus::Received {
+            return Err(ApiError::Error("确认收货后才能申请售后".to_string()));
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        sqlx::query("select id from order_items where id = $1 for update")
+            .bind(item.id)
+            .execute(&mut tx)
+            .await?;
+
+        // 可售后数量: 购买数量 - 处理中及已退换的数量
+        let used = sqlx::query(
+            "select coalesce(sum(amount), 0)::INT8 as total from after_sales \
+            where order_item_id = $1 and status != all($2)",
+        )
+        .bind(item.id)
+        .bind(vec![
+            AfterSaleStatus::Rejected as i16,
+            AfterSaleStatus::InspectFailed as i16,
+            AfterSaleStatus::Cancelled as i16,
+        ])
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("total");
+        let bought = item
+            .product_sku
+            .get("amount")
+            .and_then(|v| v.as_i64())
+            .unwrap_or_default();
+        if used + amount as i64 > bought {
+            return Err(ApiError::Error(format!(
+                "可申请售后数量为: {}",
+                (bought - used).max(0)
+            )));
+        }
+
+        // 默认退款金额: 按实付金额(已分摊优惠, 不含运费)计算, 不超过尚未退款的金额
+        let refund_amount = match r#type {
+            AfterSaleType::Return => {
+                let row = sqlx::query(
+                    "select coalesce(sum(amount), 0)::INT8 as amount, coalesce(sum(refund_amount), 0::money) as refunded \
+                    from after_sales where order_item_id = $1 and type = $2 and status != all($3)",
+                )
+                .bind(item.id)
+                .bind(AfterSaleType::Return)
+                .bind(vec![
+                    AfterSaleStatus::Rejected as i16,
+                    AfterSaleStatus::InspectFailed as i16,
+                    AfterSaleStatus::Cancelled as i16,
+                ])
+                .fetch_one(&mut tx)
+                .await?;
+                let items = OrderItems::get(item.order_id).await?;
+                let paid =
+                    OrderItems::paid_amounts(order.total_amount.0 - order.shipping_fee.0, &items)
+                        .get(&item.id)
+                        .cloned()
+                        .unwrap_or_default();
+
+                common::settlement::refundable(
+                    paid,
+                    bought,
+                    row.get::<i64, _>("amount"),
+                    amount as i64,
+                    row.get::<PgMoney, _>("refunded").0,
+                )
+            }
+            AfterSaleType::Exchange => 0,
+        };
+
+        let now = chrono::Local::now().naive_local();
+        let id = sqlx::query(
+            "insert into after_sales (no, user_id, order_id, order_item_id, type, status, amount, reason, photos, \
+            refund_amount, shipment_id, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 0, $11, $11) RETURNING id",
+        )
+        .bind(common::snow_id().await.to_string())
+        .bind(user_id)
+        .bind(item.order_id)
+        .bind(item.id)
+        .bind(r#type)
+        .bind(AfterSaleStatus::Pending)
+        .bind(amount)
+        .bind(&reason)
+        .bind(Json(photos))
+        .bind(PgMoney(refund_amount))
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+
+        Self::log(id, AfterSaleStatus::Pending, "user", &reason, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    pub async fn get(id: i64, user_id: Option<i64>) -> ApiResult<Self> {
+        let after_sale: Self = sqlx::query_as("select * from after_sales where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("售后单不存在".to_string()))?;
+        if let Some(user_id) = user_id {
+            if after_sale.user_id != user_id {
+                return Err(ApiError::Error("售后单不存在".to_string()));
+            }
+        }
+
+        Ok(after_sale)
+    }
+
+    async fn log(
+        id: i64,
+        status: AfterSaleStatus,
+        operator: &str,
+        remark: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        sqlx::query(
+            "insert into after_sale_logs (after_sale_id, status, operator, remark, created_at) \
+            values ($1, $2, $3, $4, $5)",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(operator)
+        .bind(remark)
+        .bind(chrono::Local::now().naive_local())
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 修改状态并记录进度, 当前状态不是 from 时返回错误
+    async fn transition(
+        &self,
+        from: &[AfterSaleStatus],
+        to: AfterSaleStatus,
+        operator: &str,
+        remark: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        if !from.contains(&self.status) {
+            return Err(ApiError::Error(format!(
+                "售后单当前状态为「{}」, 不能执行此操作",
+                self.status.as_ref()
+            )));
+        }
+
+        let rows = sqlx::query(
+            "update after_sales set status = $1, updated_at = $2 where id = $3 and status = $4",
+        )
+        .bind(to)
+        .bind(chrono::Local::now().naive_local())
+        .bind(self.id)
+        .bind(self.status)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error(
+                "售后单状态已变化, 请刷新后重试".to_string(),
+            ));
+        }
+
+        Self::log(self.id, to, operator, remark, tx).await
+    }
+
+    /// 用户取消
+    pub async fn cancel(&self) -> ApiResult<()> {
+        let mut tx = common::postgres().await.begin().await?;
+        self.transition(
+            &[AfterSaleStatus::Pending, AfterSaleStatus::Approved],
+            AfterSaleStatus::Cancelled,
+            "user",
+            "用户取消售后",
+            &mut tx,
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// 商家审核, 审核通过时可修改退款金额(部分退款)
+    pub async fn review(
+        &self,
+        approved: bool,
+        remark: String,
+        refund_amount: Option<f64>,
+    ) -> ApiResult<()> {
+        let mut tx = common::postgres().await.begin().await?;
+        if !approved {
+            self.transition(
+                &[AfterSaleStatus::Pending],
+                AfterSaleStatus::Rejected,
+                "admin",
+                &remark,
+                &mut tx,
+            )
+            .await?;
+            tx.commit().await?;
+
+            return Ok(());
+        }
+
+        self.transition(
+            &[AfterSaleStatus::Pending],
+            AfterSaleStatus::Approved,
+            "admin",
+            &remark,
+            &mut tx,
+        )
+        .await?;
+        if let (AfterSaleType::Return, Some(amount)) = (self.r#type, refund_amount) {
+            let amount = (amount * 100.0).round() as i64;
+            if amount > self.refund_amount.0 {
+                return Err(ApiError::Error(format!(
+                    "退款金额不能超过: {:.2}",
+                    self.refund_amount.0 as f64 / 100.0
+                )));
+            }
+            sqlx::query("update after_sales set refund_amount = $1 where id = $2")
+                .bind(PgMoney(amount))
+                .bind(self.id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// 用户寄回商品
+    pub async fn ship_back(&self, company: String, tracking_no: String) -> ApiResult<()> {
+        let mut tx = common::postgres().await.begin().await?;
+        self.transition(
+            &[AfterSaleStatus::Approved],
+            AfterSaleStatus::Returned,
+            "user",
+            &format!("{} {}", company, tracking_no),
+            &mut tx,
+        )
+        .await?;
+        sqlx::query(
+            "update after_sales set return_company = $1, return_tracking_no = $2 where id = $3",
+        )
+        .bind(company)
+        .bind(tracking_no)
+        .bind(self.id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // 售后商品的sku
+    async fn sku_id(&self, tx: &mut Transaction<'_, Postgres>) -> ApiResult<i64> {
+        Ok(
+            sqlx::query("select product_sku from order_items where id = $1")
+                .bind(self.order_item_id)
+                .fetch_one(&mut *tx)
+                .await?
+                .get::<Json<HashMap<String, serde_json::Value>>, _>("product_sku")
+                .get("sku_id")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// 仓库验货, 验货通过后退回库存
+    pub async fn inspect(&self, passed: bool, remark: String) -> ApiResult<()> {
+        let mut tx = common::postgres().await.begin().await?;
+        let status = if passed {
+            AfterSaleStatus::Inspected
+        } else {
+            AfterSaleStatus::InspectFailed
+        };
+        self.transition(
+            &[AfterSaleStatus::Returned],
+            status,
+            "admin",
+            &remark,
+            &mut tx,
+        )
+        .await?;
+
+        if passed {
+            let sku_id = self.sku_id(&mut tx).await?;
+            Warehouse::restock(self.order_item_id, sku_id, self.amount as i32, &mut tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// 退款, 仅退货退款类型
+    pub async fn refund(&self) -> ApiResult<String> {
+        if self.r#type != AfterSaleType::Return {
+            return Err(ApiError::Error("换货售后不能退款".to_string()));
+        }
+
+        let refund_no = common::snow_id().await.to_string();
+        let mut tx = common::postgres().await.begin().await?;
+        self.transition(
+            &[AfterSaleStatus::Inspected],
+            AfterSaleStatus::Refunded,
+            "admin",
+            &format!(
+                "退款金额: {:.2}, 退款单号: {}",
+                self.refund_amount.0 as f64 / 100.0,
+                refund_no
+            ),
+            &mut tx,
+        )
+        .await?;
+        sqlx::query("update after_sales set refund_no = $1 where id = $2")
+            .bind(&refund_no)
+            .bind(self.id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(refund_no)
+    }
+
+    /// 换货发出, 扣减换出商品的库存, 创建不关联订单商品的包裹以便跟踪物流
+    pub async fn reship(
+        &self,
+        company: String,
+        carrier: String,
+        tracking_no: String,
+    ) -> ApiResult<i64> {
+        if self.r#type != AfterSaleType::Exchange {
+            return Err(ApiError::Error("退货售后不能换货".to_string()));
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        self.transition(
+            &[AfterSaleStatus::Inspected],
+            AfterSaleStatus::Reshipped,
+            "admin",
+            &format!("{} {}", company, tracking_no),
+            &mut tx,
+        )
+        .await?;
+        let sku_id = self.sku_id(&mut tx).await?;
+        Warehouse::deduct(self.order_item_id, sku_id, self.amount as i32, &mut tx).await?;
+        let shipment_id =
+            Shipment::create_replacement(self.order_id, company, carrier, tracking_no, &mut tx)
+                .await?;
+        sqlx::query("update after_sales set shipment_id = $1 where id = $2")
+            .bind(shipment_id)
+            .bind(self.id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(shipment_id)
+    }
+
+    /// 售后详情及进度
+    pub async fn detail(&self) -> ApiResult<serde_json::Value> {
+        let logs: Vec<AfterSaleLog> = sqlx::query_as(
+            "select * from after_sale_logs where after_sale_id = $1 order by id asc",
+        )
+        .bind(self.id)
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        Ok(json!({
+            "after_sale": self.to_json(),
+            "timeline": logs
+                .iter()
+                .map(|log| {
+                    json!({
+                        "status": log.status.as_ref(),
+                        "operator": log.operator,
+                        "remark": log.remark,
+                        "created_at": common::time_ymd_his(log.created_at),
+                    })
+                })
+                .collect::<Vec<serde_json::Value>>(),
+        }))
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "no": self.no,
+            "order_id": self.order_id,
+            "order_item_id": self.order_item_id,
+            "type": self.r#type.as_ref(),
+            "status": self.status.as_ref(),
+            "finished": self.status.finished(),
+            "amount": self.amount,
+            "reason": self.reason,
+            "photos": self.photos,
+            "refund_amount": self.refund_amount.0 as f64 / 100.0,
+            "refund_no": self.refund_no,
+            "return_company": self.return_company,
+            "return_tracking_no": self.return_tracking_no,
+            "shipment_id": self.shipment_id,
+            "created_at": common::time_ymd_his(self.created_at),
+        })
+    }
+
+    /// 售后列表, user_id 为空时为后台列表
+    pub async fn index(
+        user_id: Option<i64>,
+        status: Option<AfterSaleStatus>,
+        pagination: &mut Pagination<serde_json::Value>,
+    ) -> ApiResult<()> {
+        let result: Vec<Self> = sqlx::query_as(
+            "select * from after_sales where ($1::INT8 is null or user_id = $1) \
+            and ($2::INT2 is null or status = $2) order by id desc limit $3 offset $4",
+        )
+        .bind(user_id)
+        .bind(status)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query(
+            "select count(*) as total from after_sales where ($1::INT8 is null or user_id = $1) \
+            and ($2::INT2 is null or status = $2)",
+        )
+        .bind(user_id)
+        .bind(status)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result.iter().map(|item| item.to_json()).collect());
+
+        Ok(())
+    }
+}
@@ -1,10 +1,16 @@
 use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::Row;
 
 use common::error::{ApiError, ApiResult};
 
+use crate::models::coupons::Coupons;
+use crate::models::order_items::ItemProductSku;
+use crate::models::orders::Orders;
+use crate::models::shipping_templates::{FreightItem, ShippingTemplate};
+
 #[derive(Debug, sqlx::FromRow, Deserialize, Serialize)]
 pub struct CartItems {
     pub id: i64,
@@ -12,6 +18,8 @@ pub struct CartItems {
     pub product_id: i64,
     pub product_sku_id: i64,
     pub amount: i16,
+    // 加入购物车时的sku价格
+    pub price: f64,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
 }
@@ -21,6 +29,14 @@ pub enum IncrType {
     Reduce,
 }
 
+/// 购物车结算结果
+pub enum Checkout {
+    // 订单ID
+    Created(i64),
+    // 价格有变动的商品, 需要用户确认
+    PriceChanged(Vec<serde_json::Value>),
+}
+
 impl CartItems {
     // 加入购物车
     pub async fn add(userid: i64, product_id: i64, sku_id: i64, amount: u16) -> ApiResult<u64> {
@@ -64,7 +80,7 @@ SELECT EXISTS (SELECT id FROM product_skus WHERE id = $2)",
             }
         }
 
-        Ok(sqlx::query("insert into cart_items (user_id,product_id,product_sku_id,amount) values ($1,$2,$3,$4) RETURNING id")
+        Ok(sqlx::query("insert into cart_items (user_id,product_id,product_sku_id,amount,price) values ($1,$2,$3,$4,(select price from product_skus where id = $3)) RETURNING id")
             .bind(userid)
             .bind(product_id)
             .bind(sku_id)
@@ -170,4 +186,158 @@ SELECT EXISTS (SELECT id FROM product_skus WHERE id = $2)",
         .map(|row| row.get::<i64, _>("product_id"))
         .collect::<HashSet<i64>>())
     }
+
+    /// 结算购物车中选中的商品
+    ///
+    /// 重新校验sku价格、库存及上架状态, 价格有变动且未确认时返回变动的商品;
+    /// 创建订单与删除已购买的购物车商品在同一事务中完成
+    pub async fn checkout(
+        user_id: i64,
+        mut ids: Vec<i64>,
+        address: sqlx::types::Json<HashMap<String, serde_json::Value>>,
+        remark: String,
+        coupon_code: Option<String>,
+        confirm_price: bool,
+    ) -> ApiResult<Checkout> {
+        ids.sort();
+        ids.dedup();
+        let mut tx = common::postgres().await.begin().await?;
+
+        let items: Vec<CartItems> = sqlx::query_as(
+            "select * from cart_items where user_id = $1 and id = any($2) order by id asc for update",
+        )
+        .bind(user_id)
+        .bind(&ids)
+        .fetch_all(&mut tx)
+        .await?;
+        if items.is_empty() || items.len() != ids.len() {
+            return Err(ApiError::Error("购物车商品不存在".to_string()));
+        }
+
+        let sku_ids = items
+            .iter()
+            .map(|item| item.product_sku_id)
+            .collect::<Vec<i64>>();
+        let skus = sqlx::query(
+            "select s.id,s.product_id,s.title,s.description,s.price,s.stock,s.weight,p.on_sale,p.image \
+            from product_skus as s inner join products as p on s.product_id = p.id \
+            where s.id = any($1) for update of s",
+        )
+        .bind(&sku_ids)
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<i64, _>("id"), row))
+        .collect::<HashMap<i64, sqlx::postgres::PgRow>>();
+
+        let mut changed = Vec::new();
+        let mut order_items: Vec<(i64, ItemProductSku)> = Vec::new();
+        for item in items.iter() {
+            let sku = match skus.get(&item.product_sku_id) {
+                Some(sku) if sku.get::<i64, _>("product_id") == item.product_id => sku,
+                _ => return Err(ApiError::Error("购物车中的商品已不存在".to_string())),
+            };
+
+            let title = sku.get::<String, _>("title");
+            if false == sku.get::<bool, _>("on_sale") {
+                return Err(ApiError::Error(format!("商品「{}」已下架", title)));
+            }
+            if sku.get::<i32, _>("stock") < item.amount as i32 {
+                return Err(ApiError::Error(format!("商品「{}」库存不足", title)));
+            }
+
+            let price = sku.get::<f64, _>("price");
+            if (price - item.price).abs() >= 0.01 {
+                changed.push(json!({
+                    "id": item.id,
+                    "product_id": item.product_id,
+                    "product_sku_id": item.product_sku_id,
+                    "title": title,
+                    "before": item.price,
+                    "after": price,
+                }));
+            }
+
+            // 订单金额以分为单位
+            let money = common::cart::line_money(price, item.amount as i64);
+            let pictures = sku.get::<sqlx::types::Json<Vec<String>>, _>("image");
+            order_items.push((
+                item.product_id,
+                ItemProductSku {
+                    sku_id: item.product_sku_id,
+                    title,
+                    descr: sku.get("description"),
+                    amount: item.amount,
+                    price: price as i64,
+                    picture: pictures.0.get(0).cloned().unwrap_or_default(),
+                    money,
+                },
+            ));
+        }
+        // 订单按sku分行, 同一商品的多个sku各占一行
+        let total_money = common::cart::order_total(
+            &order_items
+                .iter()
+                .map(|(_, item)| (item.sku_id, item.money))
+                .collect::<Vec<(i64, i64)>>(),
+        )?;
+
+        if !changed.is_empty() && !confirm_price {
+            // 更新为最新价格, 用户确认后再次结算
+            sqlx::query(
+                "update cart_items set price = s.price from product_skus as s \
+                where cart_items.product_sku_id = s.id and cart_items.id = any($1)",
+            )
+            .bind(&ids)
+            .execute(&mut tx)
+            .await?;
+            tx.commit().await?;
+
+            return Ok(Checkout::PriceChanged(changed));
+        }
+
+        if let Some(code) = &coupon_code {
+            if false == Coupons::is_in_effect(code.clone(), Some(total_money)).await? {
+                return Err(ApiError::Error("此优惠券不能使用".to_string()));
+            }
+        }
+
+        let freight_items = items
+            .iter()
+            .map(|item| {
+                let sku = skus.get(&item.product_sku_id).unwrap();
+                FreightItem {
+                    product_id: item.product_id,
+                    weight: sku.get::<i32, _>("weight"),
+                    amount: item.amount as i32,
+                    money: common::cart::line_money(
+                        sku.get::<f64, _>("price"),
+                        item.amount as i64,
+                    ),
+                }
+            })
+            .collect::<Vec<FreightItem>>();
+        let shipping_fee = ShippingTemplate::freight(&address.0, &freight_items).await?;
+
+        let order_id = Orders::create_with_tx(
+            user_id,
+            total_money,
+            shipping_fee,
+            address,
+            remark,
+            coupon_code,
+            order_items,
+            &mut tx,
+        )
+        .await?;
+
+        sqlx::query("delete from cart_items where id = any($1) and user_id = $2")
+            .bind(&ids)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(Checkout::Created(order_id))
+    }
 }
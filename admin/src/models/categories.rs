@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::Row;
@@ -13,6 +15,9 @@ pub struct Categories {
     pub is_directory: bool,
     pub level: i16,
     pub path: String,
+    pub sort: i32,
+    #[sqlx(default)]
+    pub product_count: i64,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
     pub deleted_at: Option<chrono::NaiveDateTime>,
@@ -20,13 +25,13 @@ pub struct Categories {
 
 impl Categories {
     pub async fn index(category_id: i64) -> ApiResult<Vec<Value>> {
-        let mut sql = "select id,name,parent_id,is_directory,level,path from categories \
+        let mut sql = "select id,name,parent_id,is_directory,level,path,sort from categories \
         where 1=1 "
             .to_string();
         if category_id > 0 {
-            sql.push_str(&format!(" and path like '%{}%'", category_id));
+            sql.push_str(&format!(" and {}", Self::descendant_sql(category_id)));
         }
-        sql.push_str(" and deleted_at is null order by id asc");
+        sql.push_str(" and deleted_at is null order by level asc, sort asc, id asc");
 
         let mut result: Vec<Categories> = sqlx::query(&*sql)
             .fetch_all(&*common::postgres().await)
@@ -41,6 +46,8 @@ impl Categories {
                     is_directory: row.get::<bool, _>("is_directory"),
                     level: row.get::<i16, _>("level"),
                     path: row.get::<String, _>("path"),
+                    sort: row.get::<i32, _>("sort"),
+                    product_count: 0,
                     created_at: time_only,
                     updated_at: time_only,
                     deleted_at: None,
@@ -51,6 +58,208 @@ impl Categories {
         Ok(Self::build_tree(&mut result, category_id))
     }
 
+    /// 按路径段精确匹配子孙类目, 避免 id=1 时匹配到 11、21 等类目
+    pub fn descendant_sql(category_id: i64) -> String {
+        format!("position('_{}_' in '_' || path) > 0", category_id)
+    }
+
+    /// 完整类目树, 每个节点包含该类目及其子孙类目下的商品数量
+    pub async fn tree() -> ApiResult<Vec<Value>> {
+        let mut result: Vec<Categories> = sqlx::query_as(
+            "select * from categories where deleted_at is null order by level asc, sort asc, id asc",
+        )
+        .fetch_all(&*common::postgres().await)
+        .await?;
+
+        let counts = sqlx::query(
+            "select category_id, count(*) as total from products group by category_id",
+        )
+        .fetch_all(&*common::postgres().await)
+        .await?
+        .iter()
+        .map(|row| (row.get::<i64, _>("category_id"), row.get::<i64, _>("total")))
+        .collect::<HashMap<i64, i64>>();
+
+        let mut totals: HashMap<i64, i64> = HashMap::new();
+        for category in result.iter() {
+            let count = counts.get(&category.id).cloned().unwrap_or_default();
+            for id in category.ancestor_ids().into_iter().chain([category.id]) {
+                *totals.entry(id).or_insert(0) += count;
+            }
+        }
+        for category in result.iter_mut() {
+            category.product_count = totals.get(&category.id).cloned().unwrap_or_default();
+        }
+
+        Ok(Self::build_tree(&mut result, 0))
+    }
+
+    /// 批量获取类目
+    pub async fn batch(ids: Vec<i64>) -> ApiResult<Vec<Categories>> {
+        Ok(sqlx::query_as(
+            "select * from categories where id = any($1) and deleted_at is null order by level asc, sort asc, id asc",
+        )
+        .bind(ids)
+        .fetch_all(&*common::postgres().await)
+        .await?)
+    }
+
+    /// 路径中的祖先类目id
+    pub fn ancestor_ids(&self) -> Vec<i64> {
+        self.path
+            .split('_')
+            .filter_map(|id| id.parse::<i64>().ok())
+            .collect()
+    }
+
+    /// 移动类目(包含子类目)到新的父级下, 同时重写子孙类目的 path 和 level
+    pub async fn move_to(&self, parent_id: i64, sort: Option<i32>) -> ApiResult<bool> {
+        let parent = Self::get(parent_id).await?;
+        if parent_id > 0 && parent.is_none() {
+            return Err(ApiError::Error("父级类目不存在".to_string()));
+        }
+        if let Some(parent) = &parent {
+            if parent.id == self.id || parent.ancestor_ids().contains(&self.id) {
+                return Err(ApiError::Error("不能移动到自身或子类目下".to_string()));
+            }
+        }
+
+        let mut node = Categories {
+            id: self.id,
+            parent_id,
+            ..Categories::default()
+        };
+        node.creating(parent);
+
+        let old_prefix = format!("{}{}_", self.path, self.id);
+        let new_prefix = format!("{}{}_", node.path, self.id);
+        let level_offset = node.level - self.level;
+
+        let mut tx = common::postgres().await.begin().await?;
+        let sort = match sort {
+            Some(sort) => sort,
+            None => sqlx::query(
+                "select coalesce(max(sort), 0) + 1 as sort from categories where parent_id = $1",
+            )
+            .bind(parent_id)
+            .fetch_one(&mut tx)
+            .await?
+            .get::<i32, _>("sort"),
+        };
+
+        sqlx::query(
+            "update categories set parent_id = $1, path = $2, level = $3, sort = $4, updated_at = $5 where id = $6",
+        )
+        .bind(parent_id)
+        .bind(&node.path)
+        .bind(node.level)
+        .bind(sort)
+        .bind(chrono::Local::now().naive_local())
+        .bind(self.id)
+        .execute(&mut tx)
+        .await?;
+
+        // path 中的 '_' 是 like 的通配符, 这里使用 left() 做前缀匹配
+        sqlx::query(
+            "update categories set path = $1 || substr(path, length($2) + 1), level = level + $3, \
+            updated_at = $4 where left(path, length($2)) = $2",
+        )
+        .bind(&new_prefix)
+        .bind(&old_prefix)
+        .bind(level_offset)
+        .bind(chrono::Local::now().naive_local())
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "update categories set is_directory = exists (select 1 from categories as c \
+            where c.parent_id = categories.id and c.deleted_at is null) where id = any($1)",
+        )
+        .bind(vec![self.parent_id, parent_id])
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// 设置同级类目的排序, ids 的顺序即为排序
+    pub async fn sort(parent_id: i64, ids: Vec<i64>) -> ApiResult<u64> {
+        let mut tx = common::postgres().await.begin().await?;
+        let mut rows = 0u64;
+        for (idx, id) in ids.iter().enumerate() {
+            rows += sqlx::query(
+                "update categories set sort = $1, updated_at = $2 where id = $3 and parent_id = $4",
+            )
+            .bind(idx as i32 + 1)
+            .bind(chrono::Local::now().naive_local())
+            .bind(id)
+            .bind(parent_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        }
+
+        if rows as usize != ids.len() {
+            tx.rollback().await?;
+            return Err(ApiError::Error("排序失败, 类目不属于同一父级".to_string()));
+        }
+        tx.commit().await?;
+
+        Ok(rows)
+    }
+
+    /// 根据 parent_id 重建全部类目的 path 和 level, 返回修复的类目数量
+    pub async fn rebuild_path() -> ApiResult<u64> {
+        let categories: Vec<Categories> = sqlx::query_as("select * from categories")
+            .fetch_all(&*common::postgres().await)
+            .await?;
+
+        let mut children: HashMap<i64, Vec<&Categories>> = HashMap::new();
+        for category in categories.iter() {
+            children.entry(category.parent_id).or_insert(Vec::new()).push(category);
+        }
+
+        // 从根类目开始逐层计算, 父级不存在的类目视为根类目
+        let ids = categories.iter().map(|c| c.id).collect::<Vec<i64>>();
+        let mut stack: Vec<(&Categories, String, i16)> = categories
+            .iter()
+            .filter(|c| c.parent_id == 0 || !ids.contains(&c.parent_id))
+            .map(|c| (c, String::new(), 0))
+            .collect();
+        let mut changed: Vec<(i64, i64, String, i16)> = Vec::new();
+        let mut visited = 0usize;
+        while let Some((category, path, level)) = stack.pop() {
+            visited += 1;
+            let parent_id = if path.is_empty() { 0 } else { category.parent_id };
+            if category.path != path || category.level != level || category.parent_id != parent_id {
+                changed.push((category.id, parent_id, path.clone(), level));
+            }
+
+            for child in children.get(&category.id).cloned().unwrap_or_default() {
+                stack.push((child, format!("{}{}_", path, category.id), level + 1));
+            }
+        }
+        if visited != categories.len() {
+            return Err(ApiError::Error("类目存在循环引用, 请检查 parent_id".to_string()));
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        for (id, parent_id, path, level) in changed.iter() {
+            sqlx::query("update categories set parent_id = $1, path = $2, level = $3 where id = $4")
+                .bind(parent_id)
+                .bind(path)
+                .bind(level)
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(changed.len() as u64)
+    }
+
     pub async fn get(id: i64) -> ApiResult<Option<Categories>> {
         if id <= 0 {
             return Ok(None);
@@ -87,7 +296,8 @@ impl Categories {
 
         self.creating(Self::get(self.parent_id).await?);
 
-        Ok(sqlx::query("insert into categories (name,parent_id,is_directory,level,path) values ($1, $2, $3, $4, $5) RETURNING id")
+        Ok(sqlx::query("insert into categories (name,parent_id,is_directory,level,path,sort) values ($1, $2, $3, $4, $5, \
+        (select coalesce(max(sort), 0) + 1 from categories where parent_id = $2)) RETURNING id")
             .bind(&self.name)
             .bind(self.parent_id)
             .bind(self.is_directory)
@@ -152,6 +362,42 @@ impl Categories {
         )
     }
 
+    // 全部类目的名称路径, 如: 数码/手机/智能手机
+    pub async fn path_names() -> ApiResult<HashMap<i64, String>> {
+        let categories: Vec<Categories> =
+            sqlx::query_as("select * from categories where deleted_at is null")
+                .fetch_all(&*common::postgres().await)
+                .await?;
+        let names = categories
+            .iter()
+            .map(|c| (c.id, c.name.clone()))
+            .collect::<HashMap<i64, String>>();
+
+        Ok(categories
+            .iter()
+            .map(|c| {
+                let mut path = c
+                    .path
+                    .split('_')
+                    .filter_map(|id| id.parse::<i64>().ok())
+                    .filter_map(|id| names.get(&id).cloned())
+                    .collect::<Vec<String>>();
+                path.push(c.name.clone());
+
+                (c.id, path.join("/"))
+            })
+            .collect())
+    }
+
+    // 根据名称路径查找类目id, 如: 数码/手机/智能手机
+    pub async fn id_by_path_names() -> ApiResult<HashMap<String, i64>> {
+        Ok(Self::path_names()
+            .await?
+            .into_iter()
+            .map(|(id, path)| (path, id))
+            .collect())
+    }
+
     // 类目是否商品使用
     pub async fn is_use_product(category_id: i64) -> ApiResult<bool> {
         Ok(
@@ -198,6 +444,8 @@ impl Node for Categories {
             "is_directory":json!( self.is_directory),
             "level":  json!( self.level),
             "path":  json!(self.path.clone()),
+            "sort": json!(self.sort),
+            "product_count": json!(self.product_count),
         })
     }
 
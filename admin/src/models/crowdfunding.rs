@@ -138,6 +138,30 @@ impl CrowdfundingProduct {
             .ok_or(ApiError::Error("NotFound".to_string()))
     }
 
+    /// 商品的众筹信息
+    pub async fn by_product_ids(product_ids: Vec<i64>) -> ApiResult<HashMap<i64, Self>> {
+        Ok(sqlx::query(
+            "select * from crowdfunding_products where product_id = any($1) and deleted_at is null",
+        )
+        .bind(product_ids)
+        .fetch_all(&*common::postgres().await)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let crowdfunding = CrowdfundingProduct {
+                id: row.get::<i64, _>("id"),
+                product_id: row.get::<i64, _>("product_id"),
+                target_amount: row.get::<PgMoney, _>("target_amount"),
+                total_amount: row.get::<PgMoney, _>("total_amount"),
+                user_count: row.get::<i32, _>("user_count"),
+                end_at: row.get::<chrono::NaiveDateTime, _>("end_at"),
+                status: row.get::<Status, _>("status"),
+            };
+            (crowdfunding.product_id, crowdfunding)
+        })
+        .collect())
+    }
+
     // 创建
     pub async fn store(
         product_id: i64,
@@ -147,7 +171,7 @@ impl CrowdfundingProduct {
     ) -> ApiResult<i64> {
         Ok(sqlx::query(
             "insert into crowdfunding_products (product_id,target_amount,end_at,\
-        created_at,updated_at,status) values ($1, $2, $3, $4, $5, $6)",
+        created_at,updated_at,status) values ($1, $2, $3, $4, $5, $6) RETURNING id",
         )
         .bind(product_id)
         .bind(target_amount)
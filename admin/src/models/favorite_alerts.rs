@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Postgres, Row, Transaction};
+use tracing::{error, info};
+
+use common::error::ApiResult;
+use common::rabbitmq::RabbitMQQueue;
+
+use crate::models::notifications::UserNotification;
+
+/// 收藏商品提醒类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AlertType {
+    // 降价
+    PriceDrop,
+    // 到货
+    Restock,
+}
+
+impl Default for AlertType {
+    fn default() -> Self {
+        AlertType::PriceDrop
+    }
+}
+
+impl AsRef<str> for AlertType {
+    fn as_ref(&self) -> &str {
+        match self {
+            AlertType::PriceDrop => "price_drop",
+            AlertType::Restock => "restock",
+        }
+    }
+}
+
+/// 修改前的商品sku状态: 最低价及各sku库存
+///
+/// 商品修改时sku会重建, 因此以sku名称区分
+#[derive(Debug, Default)]
+pub struct SkuWatcher {
+    states: HashMap<i64, (Option<f64>, HashMap<String, i32>)>,
+}
+
+impl SkuWatcher {
+    /// 记录修改前的状态
+    pub async fn watch(product_ids: &[i64], tx: &mut Transaction<'_, Postgres>) -> ApiResult<Self> {
+        Ok(SkuWatcher {
+            states: Self::states(product_ids, tx).await?,
+        })
+    }
+
+    async fn states(
+        product_ids: &[i64],
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<HashMap<i64, (Option<f64>, HashMap<String, i32>)>> {
+        let mut states: HashMap<i64, (Option<f64>, HashMap<String, i32>)> = product_ids
+            .iter()
+            .map(|id| (*id, (None, HashMap::new())))
+            .collect();
+
+        let rows = sqlx::query(
+            "select product_id,title,price,stock from product_skus where product_id = any($1)",
+        )
+        .bind(product_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in rows.iter() {
+            let state = states
+                .entry(row.get::<i64, _>("product_id"))
+                .or_insert((None, HashMap::new()));
+            let price = row.get::<f64, _>("price");
+            state.0 = Some(state.0.map_or(price, |min: f64| min.min(price)));
+            state.1.insert(row.get("title"), row.get::<i32, _>("stock"));
+        }
+
+        Ok(states)
+    }
+
+    /// 与修改后的状态对比, 返回需要发送的提醒
+    pub async fn changes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<Vec<FavoriteAlert>> {
+        let product_ids = self.states.keys().cloned().collect::<Vec<i64>>();
+        let after = Self::states(&product_ids, tx).await?;
+
+        let mut alerts = Vec::new();
+        for (product_id, (before_price, before_stock)) in self.states.iter() {
+            let (after_price, after_stock) = match after.get(product_id) {
+                Some(state) => state,
+                None => continue,
+            };
+
+            if let (Some(before_price), Some(after_price)) = (before_price, after_price) {
+                if after_price + 0.005 < *before_price {
+                    alerts.push(FavoriteAlert {
+                        r#type: AlertType::PriceDrop,
+                        product_id: *product_id,
+                        before_price: *before_price,
+                        after_price: *after_price,
+                        skus: vec![],
+                        created_at: Some(chrono::Local::now().naive_local()),
+                    });
+                }
+            }
+
+            let mut restocked = after_stock
+                .iter()
+                .filter(|(title, &stock)| {
+                    stock > 0
+                        && before_stock
+                            .get(*title)
+                            .map_or(false, |&before| before <= 0)
+                })
+                .map(|(title, _)| title.clone())
+                .collect::<Vec<String>>();
+            if !restocked.is_empty() {
+                restocked.sort();
+                alerts.push(FavoriteAlert {
+                    r#type: AlertType::Restock,
+                    product_id: *product_id,
+                    before_price: before_price.unwrap_or_default(),
+                    after_price: after_price.unwrap_or_default(),
+                    skus: restocked,
+                    created_at: Some(chrono::Local::now().naive_local()),
+                });
+            }
+        }
+
+        Ok(alerts)
+    }
+}
+
+/// 收藏商品提醒, 由队列消费者分发给收藏了该商品的用户
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FavoriteAlert {
+    pub r#type: AlertType,
+    pub product_id: i64,
+    pub before_price: f64,
+    pub after_price: f64,
+    // 到货的sku
+    pub skus: Vec<String>,
+    pub created_at: Option<chrono::NaiveDateTime>,
+}
+
+impl FavoriteAlert {
+    /// 事务提交后发送提醒
+    pub async fn publish(alerts: Vec<Self>) {
+        for alert in alerts {
+            if let Err(e) = alert.produce(24 * 60 * 60 * 1000).await {
+                error!("收藏商品提醒加入队列失败: {}", e);
+            }
+        }
+    }
+
+    /// 按用户的提醒设置分发
+    async fn dispatch(&self) -> ApiResult<u64> {
+        let sql = match self.r#type {
+            AlertType::PriceDrop => {
+                "select user_id,target_price from favorite_products where product_id = $1 and notify_price_drop = true"
+            }
+            AlertType::Restock => {
+                "select user_id,target_price from favorite_products where product_id = $1 and notify_restock = true"
+            }
+        };
+        let user_ids = sqlx::query(sql)
+            .bind(self.product_id)
+            .fetch_all(common::postgres().await)
+            .await?
+            .iter()
+            .filter(|row| {
+                // 设置了期望价格时, 降到期望价格以下才提醒
+                self.r#type != AlertType::PriceDrop
+                    || row
+                        .get::<Option<f64>, _>("target_price")
+                        .map_or(true, |target| self.after_price <= target)
+            })
+            .map(|row| row.get::<i64, _>("user_id"))
+            .collect::<Vec<i64>>();
+        if user_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let product_title = sqlx::query("select title from products where id = $1")
+            .bind(self.product_id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .map(|row| row.get::<String, _>("title"))
+            .unwrap_or_default();
+        let title = match self.r#type {
+            AlertType::PriceDrop => format!(
+                "您收藏的商品「{}」降价了: {:.2} → {:.2}",
+                product_title, self.before_price, self.after_price
+            ),
+            AlertType::Restock => format!(
+                "您收藏的商品「{}」到货了: {}",
+                product_title,
+                self.skus.join(", ")
+            ),
+        };
+
+        UserNotification::create_many(
+            &user_ids,
+            self.r#type.as_ref(),
+            &title,
+            json!({
+                "product_id": self.product_id,
+                "before_price": self.before_price,
+                "after_price": self.after_price,
+                "skus": self.skus,
+            }),
+        )
+        .await
+    }
+}
+
+#[axum::async_trait]
+impl RabbitMQQueue for FavoriteAlert {
+    async fn callback(&self, data: Vec<u8>) {
+        match serde_json::from_slice::<Self>(data.as_slice()) {
+            Ok(alert) => match alert.dispatch().await {
+                Ok(total) => info!(
+                    "收藏商品提醒已发送: product_id={}, type={}, users={}",
+                    alert.product_id,
+                    alert.r#type.as_ref(),
+                    total
+                ),
+                Err(e) => error!("收藏商品提醒发送失败: {}", e),
+            },
+            Err(e) => {
+                error!("FavoriteAlert callback 数据解析错误: {}", e);
+            }
+        }
+    }
+
+    fn to_string(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn queue_name(&self) -> &'static str {
+        "favorite-alert-queue"
+    }
+
+    fn exchange_name(&self) -> &'static str {
+        "favorite-alert-exchange"
+    }
+
+    fn router_key(&self) -> &'static str {
+        "favorite-alert-router-key"
+    }
+}
@@ -9,6 +9,12 @@ pub struct FavoriteProducts {
     pub id: i64,
     pub user_id: i64,
     pub product_id: i64,
+    // 降价提醒
+    pub notify_price_drop: bool,
+    // 到货提醒
+    pub notify_restock: bool,
+    // 期望价格, 降到此价格以下才提醒
+    pub target_price: Option<f64>,
     pub created_at: NaiveDateTime,
 }
 
@@ -65,6 +71,30 @@ select exists (select id from favorite_products where user_id = $2 and product_i
             .get::<i64, _>("id") as u64)
     }
 
+    /// 设置收藏商品的提醒
+    pub async fn update_alerts(
+        user_id: i64,
+        product_id: i64,
+        notify_price_drop: Option<bool>,
+        notify_restock: Option<bool>,
+        target_price: Option<f64>,
+    ) -> ApiResult<bool> {
+        Ok(sqlx::query(
+            "update favorite_products set notify_price_drop = coalesce($1, notify_price_drop), \
+            notify_restock = coalesce($2, notify_restock), target_price = $3 \
+            where user_id = $4 and product_id = $5",
+        )
+        .bind(notify_price_drop)
+        .bind(notify_restock)
+        .bind(target_price)
+        .bind(user_id)
+        .bind(product_id)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
     /// 取消收藏
     pub async fn un_favorite(user_id: i64, product_id: i64) -> ApiResult<u64> {
         Ok(
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use common::error::{ApiError, ApiResult};
+
+use crate::models::cart_items::IncrType;
+
+/// 游客购物车有效期: 7天
+pub const GUEST_CART_TTL: usize = 7 * 24 * 3600;
+
+/// 游客购物车cookie名称
+pub const GUEST_CART_COOKIE: &str = "guest_cart";
+
+/// 游客购物车, 以redis hash保存: guest_cart:{token} => {product_sku_id: GuestCartItem}
+pub struct GuestCart {
+    token: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GuestCartItem {
+    pub product_id: i64,
+    pub product_sku_id: i64,
+    pub amount: i16,
+    // 加入购物车时的sku价格
+    pub price: f64,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl GuestCart {
+    pub fn new(token: String) -> Self {
+        GuestCart { token }
+    }
+
+    /// 生成新的游客购物车
+    pub fn generate() -> Self {
+        GuestCart {
+            token: common::get_random_str(32),
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn key(&self) -> String {
+        format!("guest_cart:{}", self.token)
+    }
+
+    async fn save(&self, item: &GuestCartItem) -> ApiResult<()> {
+        common::redis::hset_ex(
+            &self.key(),
+            &item.product_sku_id.to_string(),
+            &serde_json::to_string(item)?,
+            GUEST_CART_TTL,
+        )
+        .await
+    }
+
+    async fn item(&self, sku_id: i64) -> ApiResult<Option<GuestCartItem>> {
+        match common::redis::hget(&self.key(), &sku_id.to_string()).await? {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    // 购物车商品
+    pub async fn items(&self) -> ApiResult<Vec<GuestCartItem>> {
+        let mut items = common::redis::hget_all(&self.key())
+            .await?
+            .values()
+            .filter_map(|value| serde_json::from_str::<GuestCartItem>(value).ok())
+            .collect::<Vec<GuestCartItem>>();
+        items.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+        Ok(items)
+    }
+
+    // sku当前的价格及库存
+    async fn sku(product_id: i64, sku_id: i64) -> ApiResult<(f64, i32)> {
+        let row = sqlx::query(
+            "select s.price,s.stock,p.on_sale from product_skus as s inner join products as p \
+            on s.product_id = p.id where s.id = $1 and s.product_id = $2",
+        )
+        .bind(sku_id)
+        .bind(product_id)
+        .fetch_optional(common::postgres().await)
+        .await?
+        .ok_or(ApiError::Error("商品sku不存在".to_string()))?;
+        if false == row.get::<bool, _>("on_sale") {
+            return Err(ApiError::Error("商品已下架或商品不存在".to_string()));
+        }
+
+        Ok((row.get::<f64, _>("price"), row.get::<i32, _>("stock")))
+    }
+
+    // 加入购物车, 同一sku数量累加, 超出库存时拒绝
+    pub async fn add(&self, product_id: i64, sku_id: i64, amount: u16) -> ApiResult<()> {
+        let (price, stock) = Self::sku(product_id, sku_id).await?;
+        if let Some(mut item) = self.item(sku_id).await? {
+            item.amount = common::cart::change_amount(item.amount, amount as i32, stock)?;
+            return self.save(&item).await;
+        }
+
+        self.save(&GuestCartItem {
+            product_id,
+            product_sku_id: sku_id,
+            amount: common::cart::change_amount(0, amount as i32, stock)?,
+            price,
+            created_at: chrono::Local::now().naive_local(),
+        })
+        .await
+    }
+
+    // 购物车数量增减
+    pub async fn update_amount(&self, sku_id: i64, up_type: IncrType, val: u16) -> ApiResult<bool> {
+        let mut item = match self.item(sku_id).await? {
+            Some(item) => item,
+            None => return Ok(false),
+        };
+
+        item.amount = match up_type {
+            IncrType::Add => {
+                let (_, stock) = Self::sku(item.product_id, sku_id).await?;
+                common::cart::change_amount(item.amount, val as i32, stock)?
+            }
+            IncrType::Reduce => common::cart::change_amount(item.amount, -(val as i32), 0)?,
+        };
+        if item.amount <= 0 {
+            return Ok(self.delete(vec![sku_id]).await? > 0);
+        }
+
+        self.save(&item).await?;
+        Ok(true)
+    }
+
+    // 删除
+    pub async fn delete(&self, sku_ids: Vec<i64>) -> ApiResult<u64> {
+        let fields = sku_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>();
+
+        common::redis::hdel(&self.key(), &fields).await
+    }
+
+    // 清空购物车
+    pub async fn clear(&self) -> ApiResult<()> {
+        common::redis::del(&self.key()).await
+    }
+
+    /// 按sku合并到用户购物车, 数量累加且不超过库存, 合并后清空游客购物车
+    pub async fn merge_into(&self, user_id: i64) -> ApiResult<u64> {
+        let items = self.items().await?;
+        if items.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        let mut counter = 0u64;
+        for item in items.iter() {
+            let sku = sqlx::query(
+                "select s.stock,s.price,p.on_sale from product_skus as s inner join products as p \
+                on s.product_id = p.id where s.id = $1 and s.product_id = $2",
+            )
+            .bind(item.product_sku_id)
+            .bind(item.product_id)
+            .fetch_optional(&mut tx)
+            .await?;
+            let (stock, price) = match sku {
+                Some(row) if row.get::<bool, _>("on_sale") && row.get::<i32, _>("stock") > 0 => {
+                    (row.get::<i32, _>("stock"), row.get::<f64, _>("price"))
+                }
+                _ => continue,
+            };
+
+            // 按sku合并, 同一商品的不同sku分别保存
+            let exists = sqlx::query(
+                "select id,amount from cart_items where user_id = $1 and product_id = $2 \
+                and product_sku_id = $3 for update",
+            )
+            .bind(user_id)
+            .bind(item.product_id)
+            .bind(item.product_sku_id)
+            .fetch_optional(&mut tx)
+            .await?;
+            match exists {
+                Some(row) => {
+                    sqlx::query("update cart_items set amount = $1, updated_at = $2 where id = $3")
+                        .bind(common::cart::merge_amount(
+                            row.get::<i16, _>("amount"),
+                            item.amount,
+                            stock,
+                        ))
+                        .bind(chrono::Local::now().naive_local())
+                        .bind(row.get::<i64, _>("id"))
+                        .execute(&mut tx)
+                        .await?;
+                }
+                None => {
+                    sqlx::query(
+                        "insert into cart_items (user_id,product_id,product_sku_id,amount,price) values ($1,$2,$3,$4,$5)",
+                    )
+                    .bind(user_id)
+                    .bind(item.product_id)
+                    .bind(item.product_sku_id)
+                    .bind(common::cart::merge_amount(0, item.amount, stock))
+                    .bind(price)
+                    .execute(&mut tx)
+                    .await?;
+                }
+            }
+            counter += 1;
+        }
+        tx.commit().await?;
+
+        self.clear().await?;
+
+        Ok(counter)
+    }
+}
@@ -0,0 +1,180 @@
+use serde_json::json;
+use sqlx::{Postgres, Row, Transaction};
+
+use common::error::{ApiError, ApiResult};
+use common::oauth::OAuthUser;
+
+use crate::models::user::Admin;
+
+/// 第三方账号, 一个用户可绑定多个平台的账号
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    // 平台: wechat, wxapp, alipay
+    pub provider: String,
+    pub openid: String,
+    pub unionid: Option<String>,
+    pub nickname: Option<String>,
+    pub avatar: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_login_at: Option<chrono::NaiveDateTime>,
+}
+
+impl UserIdentity {
+    pub async fn find(provider: &str, openid: &str) -> ApiResult<Option<Self>> {
+        Ok(
+            sqlx::query_as("select * from user_identities where provider = $1 and openid = $2")
+                .bind(provider)
+                .bind(openid)
+                .fetch_optional(common::postgres().await)
+                .await?,
+        )
+    }
+
+    /// 同一微信开放平台下的其他应用已登录过时, 使用同一用户
+    pub async fn find_by_unionid(unionid: &str) -> ApiResult<Option<Self>> {
+        Ok(
+            sqlx::query_as("select * from user_identities where unionid = $1 order by id limit 1")
+                .bind(unionid)
+                .fetch_optional(common::postgres().await)
+                .await?,
+        )
+    }
+
+    pub async fn list(user_id: i64) -> ApiResult<Vec<Self>> {
+        Ok(
+            sqlx::query_as("select * from user_identities where user_id = $1 order by id")
+                .bind(user_id)
+                .fetch_all(common::postgres().await)
+                .await?,
+        )
+    }
+
+    pub async fn create(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i64,
+        info: &OAuthUser,
+    ) -> ApiResult<Self> {
+        Ok(sqlx::query_as(
+            "insert into user_identities (user_id, provider, openid, unionid, nickname, avatar, created_at) \
+            values ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+        )
+        .bind(user_id)
+        .bind(&info.provider)
+        .bind(&info.openid)
+        .bind(&info.unionid)
+        .bind(&info.nickname)
+        .bind(&info.avatar)
+        .bind(chrono::Local::now().naive_local())
+        .fetch_one(tx)
+        .await?)
+    }
+
+    /// 第三方登录对应的用户, 未绑定时按 unionid 关联或自动注册
+    pub async fn login_user(info: &OAuthUser) -> ApiResult<i64> {
+        if let Some(identity) = Self::find(&info.provider, &info.openid).await? {
+            identity.touch(info).await?;
+            return Ok(identity.user_id);
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        let linked = match &info.unionid {
+            Some(unionid) => Self::find_by_unionid(unionid).await?,
+            None => None,
+        };
+        let user_id = match linked {
+            Some(identity) => identity.user_id,
+            None => Admin::register_oauth(&mut tx, info.nickname.as_deref()).await?,
+        };
+        let identity = Self::create(&mut tx, user_id, info).await?;
+        tx.commit().await?;
+        identity.touch(info).await?;
+
+        Ok(user_id)
+    }
+
+    /// 绑定到已登录的用户, 同一平台只能绑定一个账号
+    pub async fn link(user_id: i64, info: &OAuthUser) -> ApiResult<Self> {
+        if let Some(identity) = Self::find(&info.provider, &info.openid).await? {
+            return match identity.user_id == user_id {
+                true => Err(ApiError::Error("已绑定该账号".to_string())),
+                false => Err(ApiError::Error("该账号已绑定其他用户".to_string())),
+            };
+        }
+        let exists = Self::list(user_id)
+            .await?
+            .iter()
+            .any(|identity| identity.provider == info.provider);
+        if exists {
+            return Err(ApiError::Error(
+                "已绑定该平台的其他账号, 请先解绑".to_string(),
+            ));
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        let identity = Self::create(&mut tx, user_id, info).await?;
+        tx.commit().await?;
+
+        Ok(identity)
+    }
+
+    /// 解绑, 至少保留一种登录方式
+    pub async fn unlink(user_id: i64, provider: &str) -> ApiResult<()> {
+        let identities = Self::list(user_id).await?;
+        if !identities
+            .iter()
+            .any(|identity| identity.provider == provider)
+        {
+            return Err(ApiError::Error("未绑定该平台账号".to_string()));
+        }
+
+        let row = sqlx::query("select password, phone from users where id = $1")
+            .bind(user_id)
+            .fetch_one(common::postgres().await)
+            .await?;
+        let has_password = !row.get::<String, _>("password").is_empty();
+        let has_phone = !row.get::<String, _>("phone").is_empty();
+        if !has_password && !has_phone && identities.len() <= 1 {
+            return Err(ApiError::Error(
+                "解绑后将无法登录, 请先设置密码或绑定手机号".to_string(),
+            ));
+        }
+
+        sqlx::query("delete from user_identities where user_id = $1 and provider = $2")
+            .bind(user_id)
+            .bind(provider)
+            .execute(common::postgres().await)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 登录时更新昵称头像及登录时间
+    pub async fn touch(&self, info: &OAuthUser) -> ApiResult<()> {
+        sqlx::query(
+            "update user_identities set unionid = coalesce($1, unionid), nickname = coalesce($2, nickname), \
+            avatar = coalesce($3, avatar), last_login_at = $4 where id = $5",
+        )
+        .bind(&info.unionid)
+        .bind(&info.nickname)
+        .bind(&info.avatar)
+        .bind(chrono::Local::now().naive_local())
+        .bind(self.id)
+        .execute(common::postgres().await)
+        .await?;
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "provider": self.provider,
+            "nickname": self.nickname,
+            "avatar": self.avatar,
+            "created_at": common::time_ymd_his(self.created_at),
+            "last_login_at": self.last_login_at.map(common::time_ymd_his),
+        })
+    }
+}
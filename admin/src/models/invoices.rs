@@ -0,0 +1,487 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::postgres::types::PgMoney;
+use sqlx::types::Json;
+use sqlx::Row;
+
+use common::error::{ApiError, ApiResult};
+use common::invoice::ReqInvoiceTitle;
+use common::invoice_pdf::{self, InvoiceDocument, InvoiceLine};
+use common::Pagination;
+
+use crate::models::after_sales::AfterSaleStatus;
+use crate::models::order_items::OrderItems;
+use crate::models::orders::Orders;
+use crate::models::RefundStatus;
+
+/// 发票抬头类型
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum InvoiceTitleType {
+    // 个人
+    Personal = 1,
+    // 企业
+    Company = 2,
+}
+
+impl Default for InvoiceTitleType {
+    fn default() -> Self {
+        InvoiceTitleType::Personal
+    }
+}
+
+impl TryFrom<u8> for InvoiceTitleType {
+    type Error = ApiError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(InvoiceTitleType::Personal),
+            2 => Ok(InvoiceTitleType::Company),
+            _ => Err(ApiError::Error("抬头类型错误".to_string())),
+        }
+    }
+}
+
+impl AsRef<str> for InvoiceTitleType {
+    fn as_ref(&self) -> &str {
+        match self {
+            InvoiceTitleType::Personal => "个人",
+            InvoiceTitleType::Company => "企业",
+        }
+    }
+}
+
+/// 发票状态
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum InvoiceStatus {
+    // 待开票
+    Pending = 1,
+    // 已开票
+    Issued = 2,
+    // 已拒绝
+    Rejected = 3,
+}
+
+impl Default for InvoiceStatus {
+    fn default() -> Self {
+        InvoiceStatus::Pending
+    }
+}
+
+impl TryFrom<u8> for InvoiceStatus {
+    type Error = ApiError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(InvoiceStatus::Pending),
+            2 => Ok(InvoiceStatus::Issued),
+            3 => Ok(InvoiceStatus::Rejected),
+            _ => Err(ApiError::Error("发票状态错误".to_string())),
+        }
+    }
+}
+
+impl AsRef<str> for InvoiceStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            InvoiceStatus::Pending => "待开票",
+            InvoiceStatus::Issued => "已开票",
+            InvoiceStatus::Rejected => "已拒绝",
+        }
+    }
+}
+
+/// 用户发票抬头
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct InvoiceTitle {
+    pub id: i64,
+    pub user_id: i64,
+    pub r#type: InvoiceTitleType,
+    pub title: String,
+    // 纳税人识别号, 个人抬头为空
+    pub tax_no: String,
+    pub address: String,
+    pub phone: String,
+    pub bank_name: String,
+    pub bank_account: String,
+    pub email: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl InvoiceTitle {
+    /// 企业抬头必须填写税号
+    fn check(info: &ReqInvoiceTitle) -> ApiResult<InvoiceTitleType> {
+        let r#type = InvoiceTitleType::try_from(info.r#type.unwrap_or_default())?;
+        let tax_no = info.tax_no.clone().unwrap_or_default();
+        if r#type == InvoiceTitleType::Company && tax_no.is_empty() {
+            return Err(ApiError::Error("企业抬头请填写税号".to_string()));
+        }
+        if !tax_no.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ApiError::Error("税号格式错误".to_string()));
+        }
+
+        Ok(r#type)
+    }
+
+    pub async fn list(user_id: i64) -> ApiResult<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "select * from user_invoice_titles where user_id = $1 order by updated_at desc",
+        )
+        .bind(user_id)
+        .fetch_all(common::postgres().await)
+        .await?)
+    }
+
+    pub async fn get(id: i64, user_id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from user_invoice_titles where id = $1 and user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("发票抬头不存在".to_string()))
+    }
+
+    pub async fn create(user_id: i64, info: ReqInvoiceTitle) -> ApiResult<i64> {
+        let r#type = Self::check(&info)?;
+        let count =
+            sqlx::query("select count(*) as total from user_invoice_titles where user_id = $1")
+                .bind(user_id)
+                .fetch_one(common::postgres().await)
+                .await?
+                .get::<i64, _>("total");
+        if count >= 10 {
+            return Err(ApiError::Error("最多保存10个发票抬头".to_string()));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        Ok(sqlx::query(
+            "insert into user_invoice_titles (user_id, type, title, tax_no, address, phone, bank_name, \
+            bank_account, email, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10) RETURNING id",
+        )
+        .bind(user_id)
+        .bind(r#type)
+        .bind(info.title.unwrap_or_default())
+        .bind(info.tax_no.unwrap_or_default())
+        .bind(info.address.unwrap_or_default())
+        .bind(info.phone.unwrap_or_default())
+        .bind(info.bank_name.unwrap_or_default())
+        .bind(info.bank_account.unwrap_or_default())
+        .bind(info.email.unwrap_or_default())
+        .bind(now)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("id"))
+    }
+
+    pub async fn update(id: i64, user_id: i64, info: ReqInvoiceTitle) -> ApiResult<bool> {
+        let r#type = Self::check(&info)?;
+        let rows = sqlx::query(
+            "update user_invoice_titles set type = $1, title = $2, tax_no = $3, address = $4, phone = $5, \
+            bank_name = $6, bank_account = $7, email = $8, updated_at = $9 where id = $10 and user_id = $11",
+        )
+        .bind(r#type)
+        .bind(info.title.unwrap_or_default())
+        .bind(info.tax_no.unwrap_or_default())
+        .bind(info.address.unwrap_or_default())
+        .bind(info.phone.unwrap_or_default())
+        .bind(info.bank_name.unwrap_or_default())
+        .bind(info.bank_account.unwrap_or_default())
+        .bind(info.email.unwrap_or_default())
+        .bind(chrono::Local::now().naive_local())
+        .bind(id)
+        .bind(user_id)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    pub async fn delete(id: i64, user_id: i64) -> ApiResult<bool> {
+        let rows = sqlx::query("delete from user_invoice_titles where id = $1 and user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(common::postgres().await)
+            .await?
+            .rows_affected();
+
+        Ok(rows > 0)
+    }
+}
+
+/// 订单发票, 申请时保存抬头快照
+#[derive(Debug, sqlx::FromRow)]
+pub struct Invoice {
+    pub id: i64,
+    pub no: String,
+    pub user_id: i64,
+    pub order_id: i64,
+    pub title: Json<InvoiceTitle>,
+    pub status: InvoiceStatus,
+    // 含税金额, 扣除已退款的售后金额
+    pub amount: PgMoney,
+    pub tax_rate: f64,
+    pub tax_amount: PgMoney,
+    pub reason: Option<String>,
+    // 存储中的PDF文件路径
+    pub file: Option<String>,
+    pub issued_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Invoice {
+    /// 申请开票, 订单支付后才可申请, 被拒绝后可重新申请
+    pub async fn apply(order: &Orders, title: InvoiceTitle) -> ApiResult<i64> {
+        if order.paid_at.is_none() || order.closed {
+            return Err(ApiError::Error("订单未支付, 不能申请发票".to_string()));
+        }
+        if order.refund_status == RefundStatus::SUCCESS {
+            return Err(ApiError::Error("订单已退款, 不能申请发票".to_string()));
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        sqlx::query("select id from orders where id = $1 for update")
+            .bind(order.id)
+            .execute(&mut tx)
+            .await?;
+        let exists = sqlx::query("select id from invoices where order_id = $1 and status != $2")
+            .bind(order.id)
+            .bind(InvoiceStatus::Rejected)
+            .fetch_optional(&mut tx)
+            .await?;
+        if exists.is_some() {
+            return Err(ApiError::Error("订单已申请发票".to_string()));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let id = sqlx::query(
+            "insert into invoices (no, user_id, order_id, title, status, amount, tax_rate, tax_amount, \
+            created_at, updated_at) values ($1, $2, $3, $4, $5, $6, 0, $7, $8, $8) RETURNING id",
+        )
+        .bind(common::snow_id().await.to_string())
+        .bind(order.user_id)
+        .bind(order.id)
+        .bind(Json(title))
+        .bind(InvoiceStatus::Pending)
+        .bind(order.total_amount)
+        .bind(PgMoney(0))
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from invoices where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("发票不存在".to_string()))
+    }
+
+    /// 订单最近一次申请的发票
+    pub async fn by_order(order_id: i64) -> ApiResult<Option<Self>> {
+        Ok(
+            sqlx::query_as("select * from invoices where order_id = $1 order by id desc limit 1")
+                .bind(order_id)
+                .fetch_optional(common::postgres().await)
+                .await?,
+        )
+    }
+
+    /// 开票: 生成PDF并保存到文件存储
+    pub async fn issue(&self) -> ApiResult<()> {
+        if self.status != InvoiceStatus::Pending {
+            return Err(ApiError::Error(format!(
+                "发票{}, 不能开票",
+                self.status.as_ref()
+            )));
+        }
+
+        let order = Orders::get(self.order_id, self.user_id).await?;
+        let items = OrderItems::get(order.id).await?;
+        let refunded = sqlx::query(
+            "select coalesce(sum(refund_amount), 0::money) as total from after_sales \
+            where order_id = $1 and status = $2",
+        )
+        .bind(order.id)
+        .bind(AfterSaleStatus::Refunded)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<PgMoney, _>("total")
+        .0;
+
+        let total = order.total_amount.0 - refunded;
+        if total <= 0 {
+            return Err(ApiError::Error("订单已全部退款, 不能开票".to_string()));
+        }
+
+        // 商品明细按标价列出, 优惠及分摊尾差以优惠明细冲减, 明细合计与实付金额一致
+        let mut lines = items
+            .iter()
+            .map(|item| {
+                let amount = item
+                    .product_sku
+                    .get("amount")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or_default();
+                InvoiceLine {
+                    title: item
+                        .product_sku
+                        .get("title")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    amount,
+                    price: if amount > 0 { item.money() / amount } else { 0 },
+                }
+            })
+            .collect::<Vec<InvoiceLine>>();
+        if order.shipping_fee.0 > 0 {
+            lines.push(InvoiceLine {
+                title: "运费".to_string(),
+                amount: 1,
+                price: order.shipping_fee.0,
+            });
+        }
+        if refunded > 0 {
+            lines.push(InvoiceLine {
+                title: "售后退款".to_string(),
+                amount: 1,
+                price: -refunded,
+            });
+        }
+        invoice_pdf::balance(&mut lines, total);
+
+        let cfg = &common::application_config().await.invoice;
+        let now = chrono::Local::now().naive_local();
+        let content = invoice_pdf::render(
+            cfg,
+            &InvoiceDocument {
+                no: self.no.clone(),
+                order_no: order.no.clone(),
+                buyer_title: self.title.title.clone(),
+                buyer_tax_no: self.title.tax_no.clone(),
+                lines,
+                total,
+                issued_at: now,
+            },
+        )?;
+        let (_, tax_amount) = invoice_pdf::tax_breakdown(total, cfg.tax_rate);
+
+        let path = format!("invoices/{}/{}.pdf", now.format("%Y%m"), self.no);
+        common::storage()
+            .await
+            .put(&path, content, "application/pdf")
+            .await?;
+
+        let rows = sqlx::query(
+            "update invoices set status = $1, amount = $2, tax_rate = $3, tax_amount = $4, file = $5, \
+            issued_at = $6, updated_at = $6 where id = $7 and status = $8",
+        )
+        .bind(InvoiceStatus::Issued)
+        .bind(PgMoney(total))
+        .bind(cfg.tax_rate)
+        .bind(PgMoney(tax_amount))
+        .bind(&path)
+        .bind(now)
+        .bind(self.id)
+        .bind(InvoiceStatus::Pending)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            common::storage().await.delete(&path).await?;
+            return Err(ApiError::Error("发票状态已变更, 请刷新后重试".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// 拒绝开票
+    pub async fn reject(&self, reason: String) -> ApiResult<()> {
+        let rows = sqlx::query(
+            "update invoices set status = $1, reason = $2, updated_at = $3 where id = $4 and status = $5",
+        )
+        .bind(InvoiceStatus::Rejected)
+        .bind(reason)
+        .bind(chrono::Local::now().naive_local())
+        .bind(self.id)
+        .bind(InvoiceStatus::Pending)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error(format!(
+                "发票{}, 不能拒绝",
+                self.status.as_ref()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// 读取PDF文件内容
+    pub async fn content(&self) -> ApiResult<Vec<u8>> {
+        match (&self.status, &self.file) {
+            (InvoiceStatus::Issued, Some(path)) => common::storage().await.get(path).await,
+            _ => Err(ApiError::Error("发票尚未开具".to_string())),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "no": self.no,
+            "user_id": self.user_id,
+            "order_id": self.order_id,
+            "title_type": self.title.r#type.as_ref(),
+            "title": self.title.title,
+            "tax_no": self.title.tax_no,
+            "email": self.title.email,
+            "status": self.status.as_ref(),
+            "amount": self.amount.0 as f64 / 100.0,
+            "tax_rate": self.tax_rate,
+            "tax_amount": self.tax_amount.0 as f64 / 100.0,
+            "reason": self.reason,
+            "issued_at": self.issued_at.map(common::time_ymd_his),
+            "created_at": common::time_ymd_his(self.created_at),
+        })
+    }
+
+    /// 发票列表
+    pub async fn index(
+        status: Option<InvoiceStatus>,
+        pagination: &mut Pagination<serde_json::Value>,
+    ) -> ApiResult<()> {
+        let result: Vec<Self> = sqlx::query_as(
+            "select * from invoices where ($1::INT2 is null or status = $1) \
+            order by id desc limit $2 offset $3",
+        )
+        .bind(status)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query(
+            "select count(*) as total from invoices where ($1::INT2 is null or status = $1)",
+        )
+        .bind(status)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result.iter().map(|item| item.to_json()).collect());
+
+        Ok(())
+    }
+}
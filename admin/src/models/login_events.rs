@@ -0,0 +1,112 @@
+use serde_json::json;
+use sqlx::Row;
+
+use common::error::ApiResult;
+use common::login_guard;
+
+/// 登录记录
+#[derive(Debug, sqlx::FromRow)]
+pub struct LoginEvent {
+    pub id: i64,
+    // 账号不存在时为空
+    pub user_id: Option<i64>,
+    pub account: String,
+    // 登录来源
+    pub source: String,
+    pub success: bool,
+    // 失败原因
+    pub reason: Option<String>,
+    pub ip: String,
+    // ip所在网段, 用于判断登录地点是否变化
+    pub network: String,
+    pub user_agent: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// 与历史登录记录比较的结果
+#[derive(Debug, Default)]
+pub struct LoginHistory {
+    // 成功登录次数
+    pub total: i64,
+    pub known_device: bool,
+    pub known_network: bool,
+}
+
+impl LoginHistory {
+    /// 首次登录不提醒
+    pub fn is_unusual(&self) -> bool {
+        self.total > 0 && (!self.known_device || !self.known_network)
+    }
+}
+
+impl LoginEvent {
+    /// 记录登录, reason 为空表示登录成功
+    pub async fn create(
+        user_id: Option<i64>,
+        account: &str,
+        source: &str,
+        ip: &str,
+        user_agent: &str,
+        reason: Option<&str>,
+    ) -> ApiResult<()> {
+        sqlx::query(
+            "insert into login_events (user_id, account, source, success, reason, ip, network, user_agent, created_at) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(user_id)
+        .bind(account)
+        .bind(source)
+        .bind(reason.is_none())
+        .bind(reason)
+        .bind(ip)
+        .bind(login_guard::ip_network(ip))
+        .bind(user_agent)
+        .bind(chrono::Local::now().naive_local())
+        .execute(common::postgres().await)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 用户成功登录过的设备及网段
+    pub async fn history(user_id: i64, ip: &str, user_agent: &str) -> ApiResult<LoginHistory> {
+        let row = sqlx::query(
+            "select count(*) as total, count(*) filter (where user_agent = $2) as devices, \
+            count(*) filter (where network = $3) as networks from login_events where user_id = $1 and success",
+        )
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(login_guard::ip_network(ip))
+        .fetch_one(common::postgres().await)
+        .await?;
+
+        Ok(LoginHistory {
+            total: row.get::<i64, _>("total"),
+            known_device: row.get::<i64, _>("devices") > 0,
+            known_network: row.get::<i64, _>("networks") > 0,
+        })
+    }
+
+    /// 用户最近的登录记录
+    pub async fn recent(user_id: i64, limit: i64) -> ApiResult<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "select * from login_events where user_id = $1 order by id desc limit $2",
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(common::postgres().await)
+        .await?)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "source": self.source,
+            "success": self.success,
+            "reason": self.reason,
+            "ip": self.ip,
+            "user_agent": self.user_agent,
+            "created_at": common::time_ymd_his(self.created_at),
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use common::error::{ApiError, ApiResult};
+use common::login_guard;
+
+/// 登录失败计数及锁定, 按账号和ip分别统计
+pub struct LoginGuard;
+
+impl LoginGuard {
+    fn fail_key(scope: &str, value: &str) -> String {
+        format!("login_fail:{}:{}", scope, value)
+    }
+
+    fn lock_key(scope: &str, value: &str) -> String {
+        format!("login_lock:{}:{}", scope, value)
+    }
+
+    /// 统计维度及对应的失败次数上限, 获取不到ip时只按账号统计
+    ///
+    /// ip 由 real_ip 中间件按连接地址确定, 只有可信反向代理转发的请求才使用请求头中的地址
+    async fn targets(account: &str, ip: &str) -> Vec<(&'static str, String, i64)> {
+        let cfg = &common::application_config().await.login_guard;
+        let mut targets = vec![(
+            "account",
+            account.trim().to_lowercase(),
+            cfg.account_max_failures,
+        )];
+        if !ip.is_empty() {
+            targets.push(("ip", ip.to_string(), cfg.ip_max_failures));
+        }
+
+        targets
+    }
+
+    /// 锁定中返回错误, 否则返回是否需要人机验证
+    pub async fn check(account: &str, ip: &str) -> ApiResult<bool> {
+        let cfg = &common::application_config().await.login_guard;
+        let mut failures = 0;
+        for (scope, value, _) in Self::targets(account, ip).await {
+            if let Some(until) = common::redis::get(&Self::lock_key(scope, &value)).await? {
+                return Err(ApiError::Error(format!(
+                    "登录失败次数过多, 请于 {} 后重试",
+                    until
+                )));
+            }
+            let total = common::redis::get(&Self::fail_key(scope, &value))
+                .await?
+                .and_then(|total| total.parse::<i64>().ok())
+                .unwrap_or(0);
+            failures = failures.max(total);
+        }
+
+        Ok(cfg.captcha_after > 0 && failures >= cfg.captcha_after)
+    }
+
+    /// 记录失败, 达到次数后锁定, 之后每失败一次锁定时长翻倍; 返回下次是否需要人机验证
+    pub async fn fail(account: &str, ip: &str) -> ApiResult<bool> {
+        let cfg = &common::application_config().await.login_guard;
+        let mut failures = 0;
+        for (scope, value, max_failures) in Self::targets(account, ip).await {
+            let total = common::redis::incr_ex(&Self::fail_key(scope, &value), cfg.window).await?;
+            let seconds = login_guard::lock_seconds(
+                total,
+                max_failures,
+                cfg.lock_seconds,
+                cfg.max_lock_seconds,
+            );
+            if seconds > 0 {
+                let until =
+                    chrono::Local::now().naive_local() + chrono::Duration::seconds(seconds as i64);
+                common::redis::set_ex(
+                    &Self::lock_key(scope, &value),
+                    &common::time_ymd_his(until),
+                    seconds,
+                )
+                .await?;
+            }
+            failures = failures.max(total);
+        }
+
+        Ok(cfg.captcha_after > 0 && failures >= cfg.captcha_after)
+    }
+
+    /// 登录成功后清除账号的失败次数, ip的失败次数保留到统计周期结束
+    pub async fn success(account: &str) -> ApiResult<()> {
+        common::redis::del(&Self::fail_key("account", &account.trim().to_lowercase())).await
+    }
+}
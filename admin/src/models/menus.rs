@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+use sqlx::Row;
+
+use common::auth::ReqMenu;
+use common::error::{ApiError, ApiResult};
+use common::tree::{self, Node, NodeTrait};
+
+/// 后台菜单, 关联权限时只有拥有该权限的用户可见
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Menu {
+    pub id: i64,
+    pub parent_id: i64,
+    pub name: String,
+    // 前端路由
+    pub path: String,
+    pub icon: String,
+    pub sort: i32,
+    pub permission_id: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Menu {
+    /// 全部菜单, 子菜单排在父菜单之后
+    async fn all() -> ApiResult<Vec<Self>> {
+        let menus: Vec<Self> = sqlx::query_as("select * from menus order by sort, id")
+            .fetch_all(common::postgres().await)
+            .await?;
+
+        Ok(tree::preorder(&menus, 0))
+    }
+
+    /// 菜单树
+    pub async fn tree() -> ApiResult<Vec<Value>> {
+        Ok(Self::build_tree(&mut Self::all().await?, 0))
+    }
+
+    /// 用户可见的菜单树, permission_ids 为 None 时全部可见; 父菜单不可见时子菜单也不可见
+    pub async fn visible(permission_ids: Option<HashSet<i64>>) -> ApiResult<Vec<Value>> {
+        let mut visible_ids = HashSet::from([0]);
+        let mut menus = Self::all()
+            .await?
+            .into_iter()
+            .filter(|menu| {
+                let allowed = match (&permission_ids, menu.permission_id) {
+                    (Some(ids), Some(permission_id)) => ids.contains(&permission_id),
+                    _ => true,
+                };
+                if allowed && visible_ids.contains(&menu.parent_id) {
+                    visible_ids.insert(menu.id);
+                    return true;
+                }
+
+                false
+            })
+            .collect::<Vec<Self>>();
+
+        Ok(Self::build_tree(&mut menus, 0))
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from menus where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("菜单不存在".to_string()))
+    }
+
+    /// 上级菜单必须存在, 且不能是自身或自身的子菜单
+    async fn check_parent(id: Option<i64>, parent_id: i64) -> ApiResult<()> {
+        if parent_id == 0 {
+            return Ok(());
+        }
+
+        let menus = Self::all().await?;
+        if !menus.iter().any(|menu| menu.id == parent_id) {
+            return Err(ApiError::Error("上级菜单不存在".to_string()));
+        }
+        if let Some(id) = id {
+            let descendants = tree::preorder(&menus, id);
+            if parent_id == id || descendants.iter().any(|menu| menu.id == parent_id) {
+                return Err(ApiError::Error(
+                    "上级菜单不能是自身或自身的子菜单".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn create(payload: ReqMenu) -> ApiResult<Self> {
+        let parent_id = payload.parent_id.unwrap_or(0);
+        Self::check_parent(None, parent_id).await?;
+
+        let now = chrono::Local::now().naive_local();
+        Ok(sqlx::query_as(
+            "insert into menus (parent_id, name, path, icon, sort, permission_id, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+        )
+        .bind(parent_id)
+        .bind(payload.name.unwrap())
+        .bind(payload.path.unwrap_or_default())
+        .bind(payload.icon.unwrap_or_default())
+        .bind(payload.sort.unwrap_or(0))
+        .bind(payload.permission_id)
+        .bind(now)
+        .bind(now)
+        .fetch_one(common::postgres().await)
+        .await?)
+    }
+
+    pub async fn update(id: i64, payload: ReqMenu) -> ApiResult<Self> {
+        Self::get(id).await?;
+        let parent_id = payload.parent_id.unwrap_or(0);
+        Self::check_parent(Some(id), parent_id).await?;
+
+        Ok(sqlx::query_as(
+            "update menus set parent_id = $1, name = $2, path = $3, icon = $4, sort = $5, permission_id = $6, \
+            updated_at = $7 where id = $8 RETURNING *",
+        )
+        .bind(parent_id)
+        .bind(payload.name.unwrap())
+        .bind(payload.path.unwrap_or_default())
+        .bind(payload.icon.unwrap_or_default())
+        .bind(payload.sort.unwrap_or(0))
+        .bind(payload.permission_id)
+        .bind(chrono::Local::now().naive_local())
+        .bind(id)
+        .fetch_one(common::postgres().await)
+        .await?)
+    }
+
+    /// 有子菜单时不能删除
+    pub async fn delete(id: i64) -> ApiResult<()> {
+        let children = sqlx::query("select count(*) as total from menus where parent_id = $1")
+            .bind(id)
+            .fetch_one(common::postgres().await)
+            .await?
+            .get::<i64, _>("total");
+        if children > 0 {
+            return Err(ApiError::Error("请先删除子菜单".to_string()));
+        }
+
+        let rows = sqlx::query("delete from menus where id = $1")
+            .bind(id)
+            .execute(common::postgres().await)
+            .await?
+            .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error("菜单不存在".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Value {
+        self.get_data()
+    }
+}
+
+impl Node for Menu {
+    fn is_root(&self, pid: i64) -> bool {
+        self.parent_id == pid
+    }
+
+    fn get_pid(&self) -> i64 {
+        self.parent_id
+    }
+
+    fn get_id(&self) -> i64 {
+        self.id
+    }
+
+    fn get_data(&self) -> Value {
+        json!({
+            "id": self.id,
+            "parent_id": self.parent_id,
+            "name": self.name,
+            "path": self.path,
+            "icon": self.icon,
+            "sort": self.sort,
+            "permission_id": self.permission_id,
+        })
+    }
+}
+
+impl NodeTrait<Menu> for Menu {}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+
+use common::error::{ApiError, ApiResult};
+use common::jwt::UserSource;
+use common::totp;
+
+/// 登录第二步token有效期(秒)
+const MFA_PENDING_TTL: usize = 5 * 60;
+
+/// 第二步验证最多尝试次数
+const MFA_MAX_ATTEMPTS: i64 = 5;
+
+/// 恢复码数量
+const RECOVERY_CODES: usize = 10;
+
+/// 两步验证(TOTP)
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserMfa {
+    pub user_id: i64,
+    pub secret: String,
+    // 恢复码hash, 使用后删除
+    pub recovery_codes: Json<Vec<String>>,
+    // 最后使用的时间步, 防止验证码重放
+    pub last_step: Option<i64>,
+    pub enabled_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl UserMfa {
+    pub async fn get(user_id: i64) -> ApiResult<Option<Self>> {
+        Ok(sqlx::query_as("select * from user_mfa where user_id = $1")
+            .bind(user_id)
+            .fetch_optional(common::postgres().await)
+            .await?)
+    }
+
+    /// 是否已开启
+    pub async fn enabled(user_id: i64) -> ApiResult<bool> {
+        Ok(Self::get(user_id)
+            .await?
+            .map_or(false, |mfa| mfa.enabled_at.is_some()))
+    }
+
+    /// 生成新的密钥, 验证通过后才会开启
+    pub async fn setup(user_id: i64) -> ApiResult<Self> {
+        if Self::enabled(user_id).await? {
+            return Err(ApiError::Error("已开启两步验证".to_string()));
+        }
+
+        Ok(sqlx::query_as(
+            "insert into user_mfa (user_id, secret, recovery_codes, created_at) values ($1, $2, $3, $4) \
+            on conflict (user_id) do update set secret = excluded.secret, recovery_codes = excluded.recovery_codes, \
+            last_step = null, created_at = excluded.created_at RETURNING *",
+        )
+        .bind(user_id)
+        .bind(totp::generate_secret())
+        .bind(Json(Vec::<String>::new()))
+        .bind(chrono::Local::now().naive_local())
+        .fetch_one(common::postgres().await)
+        .await?)
+    }
+
+    /// 扫码地址
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        totp::provisioning_uri(issuer, account, &self.secret)
+    }
+
+    /// 校验验证码或恢复码, 恢复码只能使用一次
+    pub async fn verify(&self, code: &str) -> ApiResult<bool> {
+        let now = chrono::Local::now().timestamp();
+        if let Some(step) = totp::verify(&self.secret, code, now, self.last_step) {
+            // 并发请求同一验证码只有一个能成功
+            let rows = sqlx::query(
+                "update user_mfa set last_step = $1 where user_id = $2 and (last_step is null or last_step < $1)",
+            )
+            .bind(step)
+            .bind(self.user_id)
+            .execute(common::postgres().await)
+            .await?
+            .rows_affected();
+
+            return Ok(rows > 0);
+        }
+
+        let hash = totp::hash_recovery_code(code);
+        if self.enabled_at.is_none() || !self.recovery_codes.contains(&hash) {
+            return Ok(false);
+        }
+
+        let rows = sqlx::query(
+            "update user_mfa set recovery_codes = recovery_codes - $1 where user_id = $2 and recovery_codes ? $1",
+        )
+        .bind(hash)
+        .bind(self.user_id)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    /// 开启, 返回恢复码
+    pub async fn enable(&self, code: &str) -> ApiResult<Vec<String>> {
+        if self.enabled_at.is_some() {
+            return Err(ApiError::Error("已开启两步验证".to_string()));
+        }
+        if !self.verify(code).await? {
+            return Err(ApiError::Error("验证码错误".to_string()));
+        }
+
+        let codes = totp::recovery_codes(RECOVERY_CODES);
+        sqlx::query("update user_mfa set enabled_at = $1, recovery_codes = $2 where user_id = $3")
+            .bind(chrono::Local::now().naive_local())
+            .bind(Json(Self::hash_codes(&codes)))
+            .bind(self.user_id)
+            .execute(common::postgres().await)
+            .await?;
+
+        Ok(codes)
+    }
+
+    /// 重新生成恢复码, 旧的恢复码失效
+    pub async fn regenerate_codes(&self, code: &str) -> ApiResult<Vec<String>> {
+        if self.enabled_at.is_none() {
+            return Err(ApiError::Error("未开启两步验证".to_string()));
+        }
+        if !self.verify(code).await? {
+            return Err(ApiError::Error("验证码错误".to_string()));
+        }
+
+        let codes = totp::recovery_codes(RECOVERY_CODES);
+        sqlx::query("update user_mfa set recovery_codes = $1 where user_id = $2")
+            .bind(Json(Self::hash_codes(&codes)))
+            .bind(self.user_id)
+            .execute(common::postgres().await)
+            .await?;
+
+        Ok(codes)
+    }
+
+    /// 关闭
+    pub async fn disable(&self, code: &str) -> ApiResult<()> {
+        if !self.verify(code).await? {
+            return Err(ApiError::Error("验证码错误".to_string()));
+        }
+
+        sqlx::query("delete from user_mfa where user_id = $1")
+            .bind(self.user_id)
+            .execute(common::postgres().await)
+            .await?;
+
+        Ok(())
+    }
+
+    fn hash_codes(codes: &[String]) -> Vec<String> {
+        codes
+            .iter()
+            .map(|code| totp::hash_recovery_code(code))
+            .collect()
+    }
+}
+
+/// 密码校验通过, 等待两步验证的登录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaPending {
+    pub user_id: i64,
+    // 登录来源, 验证通过后写入token
+    #[serde(default)]
+    pub from: UserSource,
+}
+
+impl MfaPending {
+    fn key(token: &str) -> String {
+        format!("mfa_pending:{}", token)
+    }
+
+    fn attempts_key(token: &str) -> String {
+        format!("mfa_pending_attempts:{}", token)
+    }
+
+    pub async fn create(user_id: i64, from: UserSource) -> ApiResult<String> {
+        let token = common::get_random_str(32);
+        let pending = MfaPending { user_id, from };
+        common::redis::set_ex(
+            &Self::key(&token),
+            &serde_json::to_string(&pending)?,
+            MFA_PENDING_TTL,
+        )
+        .await?;
+
+        Ok(token)
+    }
+
+    pub async fn get(token: &str) -> ApiResult<Self> {
+        match common::redis::get(&Self::key(token)).await? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Err(ApiError::Error("登录已过期, 请重新登录".to_string())),
+        }
+    }
+
+    /// 记录失败次数, 超过次数后需重新登录
+    ///
+    /// 失败次数单独计数, 不改写登录信息, 避免每次失败都延长有效期
+    pub async fn fail(&self, token: &str) -> ApiResult<()> {
+        let attempts = common::redis::incr_ex(&Self::attempts_key(token), MFA_PENDING_TTL).await?;
+        if attempts >= MFA_MAX_ATTEMPTS {
+            return Self::consume(token).await;
+        }
+
+        Ok(())
+    }
+
+    /// 验证通过后删除
+    pub async fn consume(token: &str) -> ApiResult<()> {
+        common::redis::del(&Self::attempts_key(token)).await?;
+        common::redis::del(&Self::key(token)).await
+    }
+}
@@ -1,20 +1,41 @@
 use serde::Serialize;
 
 pub mod address;
+pub mod after_sales;
 pub mod auth;
 pub mod cart_items;
 pub mod categories;
 pub mod coupons;
 pub mod crowdfunding;
+pub mod favorite_alerts;
 pub mod favorite_products;
+pub mod guest_cart;
+pub mod identities;
 pub mod installment_items;
 pub mod installments;
+pub mod invoices;
+pub mod login_events;
+pub mod login_guard;
+pub mod menus;
+pub mod mfa;
+pub mod notifications;
 pub mod order_items;
 pub mod orders;
+pub mod permissions;
 pub mod product_property;
+pub mod product_revisions;
+pub mod product_schedules;
 pub mod product_skus;
 pub mod products;
+pub mod purchases;
+pub mod reviews;
+pub mod roles;
+pub mod sessions;
+pub mod shipments;
+pub mod shipping_templates;
+pub mod sms_codes;
 pub mod user;
+pub mod warehouses;
 
 // 支付方式
 #[derive(Debug, PartialEq, sqlx::Type, Serialize)]
@@ -108,6 +129,10 @@ pub enum LogisticStatus {
     Processing,
     ToBeReceived,
     Received,
+    // 部分发货
+    PartShipped,
+    // 已签收, 待确认收货
+    Delivered,
 }
 
 impl Default for LogisticStatus {
@@ -122,6 +147,8 @@ impl AsRef<str> for LogisticStatus {
             LogisticStatus::Processing => "处理中",
             LogisticStatus::ToBeReceived => "待收货",
             LogisticStatus::Received => "已收货",
+            LogisticStatus::PartShipped => "部分发货",
+            LogisticStatus::Delivered => "已签收",
         }
     }
 }
@@ -132,6 +159,8 @@ impl From<LogisticStatus> for i8 {
             LogisticStatus::Processing => 0,
             LogisticStatus::ToBeReceived => 1,
             LogisticStatus::Received => 2,
+            LogisticStatus::PartShipped => 3,
+            LogisticStatus::Delivered => 4,
         }
     }
 }
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder, Row};
+
+use common::error::ApiResult;
+use common::Pagination;
+
+/// 站内通知
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UserNotification {
+    pub id: i64,
+    pub user_id: i64,
+    pub r#type: String,
+    pub title: String,
+    pub content: Json<serde_json::Value>,
+    pub read_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl UserNotification {
+    /// 批量发送
+    pub async fn create_many(
+        user_ids: &[i64],
+        r#type: &str,
+        title: &str,
+        content: serde_json::Value,
+    ) -> ApiResult<u64> {
+        let mut total = 0u64;
+        let now = chrono::Local::now().naive_local();
+        for chunk in user_ids.chunks(500) {
+            let mut query_build: QueryBuilder<Postgres> = QueryBuilder::new(
+                "insert into user_notifications (user_id, type, title, content, created_at) ",
+            );
+            query_build.push_values(chunk.iter(), |mut b, user_id| {
+                b.push_bind(*user_id)
+                    .push_bind(r#type)
+                    .push_bind(title)
+                    .push_bind(Json(content.clone()))
+                    .push_bind(now);
+            });
+
+            total += query_build
+                .build()
+                .execute(common::postgres().await)
+                .await?
+                .rows_affected();
+        }
+
+        Ok(total)
+    }
+
+    /// 通知列表
+    pub async fn index(user_id: i64, pagination: &mut Pagination<Self>) -> ApiResult<()> {
+        let result: Vec<Self> = sqlx::query_as(
+            "select * from user_notifications where user_id = $1 order by id desc limit $2 offset $3",
+        )
+        .bind(user_id)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total =
+            sqlx::query("select count(*) as total from user_notifications where user_id = $1")
+                .bind(user_id)
+                .fetch_one(common::postgres().await)
+                .await?
+                .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result);
+
+        Ok(())
+    }
+
+    /// 标记已读
+    pub async fn read(user_id: i64, ids: Vec<i64>) -> ApiResult<u64> {
+        Ok(sqlx::query(
+            "update user_notifications set read_at = $1 where user_id = $2 and id = any($3) and read_at is null",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .bind(user_id)
+        .bind(ids)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected())
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use serde_json::json;
-use sqlx::Row;
+use sqlx::{Postgres, Row, Transaction};
 
 use common::error::{ApiError, ApiResult};
 use common::Pagination;
@@ -9,6 +9,8 @@ use common::Pagination;
 use crate::models::coupons::Coupons;
 use crate::models::order_items::{ItemProductSku, OrderItems};
 use crate::models::product_skus::ProductSku;
+use crate::models::shipments::Shipment;
+use crate::models::warehouses::Warehouse;
 use crate::models::{LogisticStatus, PayMethod, RefundStatus};
 
 #[derive(Debug, sqlx::FromRow)]
@@ -32,6 +34,8 @@ pub struct Orders {
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
     pub coupon_id: i64,
+    // 运费, 已包含在订单金额中
+    pub shipping_fee: sqlx::postgres::types::PgMoney,
 }
 
 impl Orders {
@@ -39,42 +43,75 @@ impl Orders {
     pub async fn create(
         user_id: i64,
         total_money: i64,
+        shipping_fee: i64,
         address: sqlx::types::Json<HashMap<String, serde_json::Value>>,
         remark: String,
         coupon_code: Option<String>,
-        order_items: HashMap<i64, ItemProductSku>,
+        order_items: Vec<(i64, ItemProductSku)>,
     ) -> ApiResult<i64> {
         let mut tx = common::postgres().await.begin().await?;
+        let order_id = Self::create_with_tx(
+            user_id,
+            total_money,
+            shipping_fee,
+            address,
+            remark,
+            coupon_code,
+            order_items,
+            &mut tx,
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(order_id)
+    }
+
+    // 在已有事务中创建订单, 金额以分为单位, 订单金额 = 商品金额 + 运费
+    pub async fn create_with_tx(
+        user_id: i64,
+        total_money: i64,
+        shipping_fee: i64,
+        address: sqlx::types::Json<HashMap<String, serde_json::Value>>,
+        remark: String,
+        coupon_code: Option<String>,
+        order_items: Vec<(i64, ItemProductSku)>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<i64> {
         if let Some(code) = coupon_code {
-            if false == Coupons::use_coupon(code, &mut tx).await? {
+            if false == Coupons::use_coupon(code, &mut *tx).await? {
                 return Err(ApiError::Error("此优惠券不符合使用条件".to_string()));
             }
         }
 
         let ship_data: Vec<HashMap<String, serde_json::Value>> = Vec::new();
         let order_id = sqlx::query(
-            "INSERT INTO orders (no,user_id,address,total_amount,remark,ship_status,ship_data) VALUES ($1,$2,$3,$4,$5,$6,$7) RETURNING id"
+            "INSERT INTO orders (no,user_id,address,total_amount,remark,ship_status,ship_data,shipping_fee) VALUES ($1,$2,$3,$4,$5,$6,$7,$8) RETURNING id"
         )
             .bind(Self::get_order_no().await?)
             .bind(user_id)
             .bind(json!(address))
-            .bind(total_money)
+            .bind(common::settlement::order_amount(total_money, shipping_fee))
             .bind(remark)
             .bind::<i8>(LogisticStatus::Processing.into())
             .bind(json!(ship_data))
-            .fetch_one(&mut tx)
+            .bind(sqlx::postgres::types::PgMoney(shipping_fee))
+            .fetch_one(&mut *tx)
             .await?.get::<i64, _>("id");
 
         let (bool_val, item_ids): (bool, Vec<HashMap<i64, i64>>) =
-            OrderItems::create(order_id, order_items, &mut tx).await?;
+            OrderItems::create(order_id, order_items, &mut *tx).await?;
         if false == bool_val {
-            tx.rollback().await?;
             return Err(ApiError::Error("创建商品订单失败".to_string()));
         }
 
-        ProductSku::buckle_inventory(item_ids, -1, &mut tx).await?;
-
-        tx.commit().await?;
+        // 启用仓库时按收货地址分仓扣减库存
+        let province_id = address
+            .get("province_id")
+            .and_then(|v| v.as_i64())
+            .unwrap_or_default() as i32;
+        if !Warehouse::allocate(order_id, province_id, &mut *tx).await? {
+            ProductSku::buckle_inventory(item_ids, -1, &mut *tx).await?;
+        }
 
         Ok(order_id)
     }
@@ -196,32 +233,45 @@ impl Orders {
         }
     }
 
-    // 发货
-    pub async fn ship(userid: i64, id: i64, no: String, company: String) -> ApiResult<bool> {
+    // 发货, 每次发货创建一个包裹
+    pub async fn ship(
+        userid: i64,
+        id: i64,
+        company: String,
+        carrier: String,
+        no: String,
+        item_ids: Vec<i64>,
+        warehouse_id: Option<i64>,
+    ) -> ApiResult<i64> {
         let order = Orders::get(id, userid).await?;
-        if order.ship_status != LogisticStatus::Processing || order.pay_method == PayMethod::Unknown
+        if (order.ship_status != LogisticStatus::Processing
+            && order.ship_status != LogisticStatus::PartShipped)
+            || order.pay_method == PayMethod::Unknown
         {
-            return Ok(false);
+            return Err(ApiError::Error("订单当前状态不能发货".to_string()));
         }
 
-        Ok(sqlx::query("update orders set ship_status = $1,ship_data=$2, updated_at = $3 where id = $4 and user_id = $5")
-            .bind::<i8>(LogisticStatus::ToBeReceived.into())
-            .bind(json!(vec![
-            HashMap::from([
-            ("express_no", no),
-            ("company", company),
-        ])]))
-            .bind(chrono::Local::now())
-            .bind(id)
-            .bind(userid)
-            .execute(common::postgres().await)
-            .await?.rows_affected() > 0)
+        // 按仓库发货, 包裹包含该仓库分配的待发货商品
+        let item_ids = match warehouse_id {
+            Some(warehouse_id) if item_ids.is_empty() => {
+                let ids = Warehouse::pending_items(order.id, warehouse_id).await?;
+                if ids.is_empty() {
+                    return Err(ApiError::Error("该仓库没有待发货的商品".to_string()));
+                }
+                ids
+            }
+            _ => item_ids,
+        };
+
+        Shipment::create(&order, company, carrier, no, item_ids).await
     }
 
     // 确认收获
     pub async fn received(id: i64, userid: i64) -> ApiResult<bool> {
         let order = Orders::get(id, userid).await?;
-        if order.ship_status != LogisticStatus::ToBeReceived {
+        if order.ship_status != LogisticStatus::ToBeReceived
+            && order.ship_status != LogisticStatus::Delivered
+        {
             return Ok(false);
         }
 
@@ -238,6 +288,23 @@ impl Orders {
             > 0)
     }
 
+    // 签收超过指定天数的订单自动确认收货
+    pub async fn auto_receive(days: u16) -> ApiResult<u64> {
+        let deadline = chrono::Local::now().naive_local() - chrono::Duration::days(days as i64);
+
+        Ok(sqlx::query(
+            "update orders set ship_status = $1, updated_at = $2 where ship_status = $3 and id in \
+            (select order_id from shipments group by order_id having max(delivered_at) <= $4)",
+        )
+        .bind::<i8>(LogisticStatus::Received.into())
+        .bind(chrono::Local::now().naive_local())
+        .bind::<i8>(LogisticStatus::Delivered.into())
+        .bind(deadline)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected())
+    }
+
     // 订单关联的优惠券
     pub async fn coupon(&self) -> ApiResult<Option<i64>> {
         if self.coupon_id <= 0 {
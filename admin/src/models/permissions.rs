@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use casbin::prelude::*;
+use serde_json::json;
+
+use common::auth::ReqPermission;
+use common::error::{ApiError, ApiResult};
+use common::route_catalog::{self, RoutePermission};
+
+/// 权限目录, 给角色分配权限时使用, 实际校验仍由 casbin 按路由及请求方式完成
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Permission {
+    pub id: i64,
+    // 权限标识, 如 orders.index
+    pub name: String,
+    pub display_name: String,
+    pub group_name: String,
+    pub path: String,
+    pub method: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl Permission {
+    pub async fn index() -> ApiResult<Vec<Self>> {
+        Ok(
+            sqlx::query_as("select * from permissions order by group_name, id")
+                .fetch_all(common::postgres().await)
+                .await?,
+        )
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Option<Self>> {
+        Ok(sqlx::query_as("select * from permissions where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?)
+    }
+
+    pub async fn get_many(ids: &[i64]) -> ApiResult<Vec<Self>> {
+        Ok(
+            sqlx::query_as("select * from permissions where id = any($1) order by id")
+                .bind(ids)
+                .fetch_all(common::postgres().await)
+                .await?,
+        )
+    }
+
+    /// casbin 规则对应的权限, 规则格式: [角色, 域名, 路由, 请求方式]
+    pub fn matched<'a>(catalog: &'a [Self], rules: &[Vec<String>]) -> Vec<&'a Self> {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.len() >= 4)
+            .map(|rule| (rule[2].as_str(), rule[3].as_str()))
+            .collect::<HashSet<(&str, &str)>>();
+
+        catalog
+            .iter()
+            .filter(|permission| {
+                rules.contains(&(permission.path.as_str(), permission.method.as_str()))
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "display_name": self.display_name,
+            "group_name": self.group_name,
+            "path": self.path,
+            "method": self.method,
+            "created_at": common::time_ymd_his(self.created_at),
+        })
+    }
+
+    pub async fn create(payload: ReqPermission) -> ApiResult<Self> {
+        Ok(sqlx::query_as(
+            "insert into permissions (name, display_name, group_name, path, method, created_at) \
+            values ($1, $2, $3, $4, $5, $6) RETURNING *",
+        )
+        .bind(payload.name.unwrap())
+        .bind(payload.display_name.unwrap())
+        .bind(payload.group_name.unwrap_or_default())
+        .bind(payload.path.unwrap())
+        .bind(payload.method.unwrap().to_uppercase())
+        .bind(chrono::Local::now().naive_local())
+        .fetch_one(common::postgres().await)
+        .await?)
+    }
+
+    /// 修改路由或请求方式时, 同步修改已分配给角色的 casbin 规则
+    pub async fn update(id: i64, payload: ReqPermission) -> ApiResult<Self> {
+        let before = Self::get(id)
+            .await?
+            .ok_or(ApiError::Error("权限不存在".to_string()))?;
+        let permission: Self = sqlx::query_as(
+            "update permissions set name = $1, display_name = $2, group_name = $3, path = $4, method = $5 \
+            where id = $6 RETURNING *",
+        )
+        .bind(payload.name.unwrap())
+        .bind(payload.display_name.unwrap())
+        .bind(payload.group_name.unwrap_or_default())
+        .bind(payload.path.unwrap())
+        .bind(payload.method.unwrap().to_uppercase())
+        .bind(id)
+        .fetch_one(common::postgres().await)
+        .await?;
+
+        Self::move_rules(&before, &permission).await?;
+
+        Ok(permission)
+    }
+
+    /// 路由或请求方式变化时, 迁移已分配给角色的 casbin 规则
+    async fn move_rules(before: &Self, after: &Self) -> ApiResult<()> {
+        if before.path == after.path && before.method == after.method {
+            return Ok(());
+        }
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        let rules =
+            enforcer.get_filtered_policy(2, vec![before.path.clone(), before.method.clone()]);
+        if !rules.is_empty() {
+            let updated = rules
+                .iter()
+                .map(|rule| {
+                    vec![
+                        rule[0].clone(),
+                        rule[1].clone(),
+                        after.path.clone(),
+                        after.method.clone(),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>();
+            enforcer.remove_policies(rules).await?;
+            enforcer.add_policies(updated).await?;
+        }
+
+        Ok(())
+    }
+
+    /// 按路由表同步权限目录, 优先按权限标识匹配, 其次按路由及请求方式匹配, 返回新增及更新的数量
+    pub async fn sync(routes: &[RoutePermission]) -> ApiResult<(u64, u64)> {
+        let catalog = Self::index().await?;
+        let (mut created, mut updated) = (0, 0);
+        for route in routes.iter() {
+            let before = catalog
+                .iter()
+                .find(|permission| permission.name == route.name)
+                .or_else(|| {
+                    catalog.iter().find(|permission| {
+                        permission.path == route.path && permission.method == route.method
+                    })
+                });
+
+            match before {
+                Some(before) => {
+                    if before.name == route.name
+                        && before.display_name == route.display_name
+                        && before.group_name == route.group_name
+                        && before.path == route.path
+                        && before.method == route.method
+                    {
+                        continue;
+                    }
+
+                    let permission: Self = sqlx::query_as(
+                        "update permissions set name = $1, display_name = $2, group_name = $3, path = $4, method = $5 \
+                        where id = $6 RETURNING *",
+                    )
+                    .bind(&route.name)
+                    .bind(&route.display_name)
+                    .bind(&route.group_name)
+                    .bind(&route.path)
+                    .bind(&route.method)
+                    .bind(before.id)
+                    .fetch_one(common::postgres().await)
+                    .await?;
+                    Self::move_rules(before, &permission).await?;
+                    updated += 1;
+                }
+                None => {
+                    sqlx::query(
+                        "insert into permissions (name, display_name, group_name, path, method, created_at) \
+                        values ($1, $2, $3, $4, $5, $6)",
+                    )
+                    .bind(&route.name)
+                    .bind(&route.display_name)
+                    .bind(&route.group_name)
+                    .bind(&route.path)
+                    .bind(&route.method)
+                    .bind(chrono::Local::now().naive_local())
+                    .execute(common::postgres().await)
+                    .await?;
+                    created += 1;
+                }
+            }
+        }
+
+        Ok((created, updated))
+    }
+
+    /// 已失效的权限目录及 casbin 规则, 即不再对应任何路由的条目
+    pub async fn stale(routes: &[RoutePermission]) -> ApiResult<serde_json::Value> {
+        let permissions = Self::index()
+            .await?
+            .into_iter()
+            .filter(|permission| {
+                !routes
+                    .iter()
+                    .any(|route| route.path == permission.path && route.method == permission.method)
+            })
+            .map(|permission| permission.to_json())
+            .collect::<Vec<serde_json::Value>>();
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let rules = enforcer
+            .write()
+            .await
+            .get_policy()
+            .into_iter()
+            .filter(|rule| !route_catalog::rule_matched(rule, routes))
+            .map(|rule| {
+                json!({
+                    "role": rule.get(0),
+                    "domain": rule.get(1),
+                    "path": rule.get(2),
+                    "method": rule.get(3),
+                })
+            })
+            .collect::<Vec<serde_json::Value>>();
+
+        Ok(json!({
+            "permissions": permissions,
+            "rules": rules,
+        }))
+    }
+
+    /// 删除权限及已分配给角色的 casbin 规则
+    pub async fn delete(id: i64) -> ApiResult<()> {
+        let permission = Self::get(id)
+            .await?
+            .ok_or(ApiError::Error("权限不存在".to_string()))?;
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        enforcer
+            .remove_filtered_policy(2, vec![permission.path, permission.method])
+            .await?;
+
+        let mut tx = common::postgres().await.begin().await?;
+        sqlx::query("update menus set permission_id = null where permission_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("delete from permissions where id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
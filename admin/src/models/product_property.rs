@@ -48,6 +48,36 @@ impl ProductProperty {
         Ok(())
     }
 
+    // 删除商品的全部属性
+    pub async fn delete_product_property(
+        product_id: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<u64> {
+        Ok(
+            sqlx::query("DELETE FROM product_properties WHERE product_id = $1")
+                .bind(product_id)
+                .execute(tx)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    // 多个商品的属性
+    pub async fn product_propertys(product_ids: Vec<i64>) -> ApiResult<HashMap<i64, Vec<Self>>> {
+        let result: Vec<Self> =
+            sqlx::query_as("SELECT * FROM product_properties WHERE product_id = ANY($1) ORDER BY id ASC")
+                .bind(product_ids)
+                .fetch_all(common::postgres().await)
+                .await?;
+
+        let mut data_map: HashMap<i64, Vec<Self>> = HashMap::new();
+        for item in result {
+            data_map.entry(item.product_id).or_insert(Vec::new()).push(item);
+        }
+
+        Ok(data_map)
+    }
+
     // 商品的所有属性
     pub async fn propertys(product_id: i64) -> ApiResult<Vec<HashMap<String, serde_json::Value>>> {
         let mut result = HashMap::new();
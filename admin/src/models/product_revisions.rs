@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::types::Json;
+use sqlx::{Postgres, Row, Transaction};
+
+use common::error::{ApiError, ApiResult};
+use common::Pagination;
+
+use crate::models::favorite_alerts::{FavoriteAlert, SkuWatcher};
+use crate::models::warehouses::Warehouse;
+
+/// 商品修改记录, 每次变更后保存商品及sku的完整快照
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProductRevision {
+    pub id: i64,
+    pub product_id: i64,
+    pub operator_id: i64,
+    pub snapshot: Json<Value>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl ProductRevision {
+    /// 保存商品当前状态的快照
+    pub async fn record(
+        product_id: i64,
+        operator_id: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<i64> {
+        let snapshot = Self::snapshot(product_id, &mut *tx).await?;
+
+        Ok(sqlx::query(
+            "insert into product_revisions (product_id, operator_id, snapshot, created_at) \
+            values ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(product_id)
+        .bind(operator_id)
+        .bind(Json(snapshot))
+        .bind(chrono::Local::now().naive_local())
+        .fetch_one(&mut *tx)
+        .await?
+        .get::<i64, _>("id"))
+    }
+
+    /// 读取商品当前的数据
+    async fn snapshot(product_id: i64, tx: &mut Transaction<'_, Postgres>) -> ApiResult<Value> {
+        let product = sqlx::query(
+            "select title,long_title,description,image,on_sale,sku_price,category_id \
+            from products where id = $1",
+        )
+        .bind(product_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ApiError::Error("商品不存在".to_string()))?;
+
+        let skus = sqlx::query(
+            "select id,title,description,price,stock,weight from product_skus where product_id = $1 order by id asc",
+        )
+        .bind(product_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| {
+            json!({
+                "id": row.get::<i64, _>("id"),
+                "title": row.get::<String, _>("title"),
+                "description": row.get::<String, _>("description"),
+                "price": row.get::<f64, _>("price"),
+                "stock": row.get::<i32, _>("stock"),
+                "weight": row.get::<i32, _>("weight"),
+            })
+        })
+        .collect::<Vec<Value>>();
+
+        let property = sqlx::query(
+            "select name,value from product_properties where product_id = $1 order by id asc",
+        )
+        .bind(product_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| {
+            json!({
+                "name": row.get::<String, _>("name"),
+                "value": row.get::<String, _>("value"),
+            })
+        })
+        .collect::<Vec<Value>>();
+
+        Ok(json!({
+            "title": product.get::<String, _>("title"),
+            "long_title": product.get::<String, _>("long_title"),
+            "description": product.get::<String, _>("description"),
+            "image": product.get::<Json<Vec<String>>, _>("image"),
+            "on_sale": product.get::<bool, _>("on_sale"),
+            "price": product.get::<f64, _>("sku_price"),
+            "category_id": product.get::<i64, _>("category_id"),
+            "skus": skus,
+            "property": property,
+        }))
+    }
+
+    /// 修改记录列表
+    pub async fn index(product_id: i64, pagination: &mut Pagination<Self>) -> ApiResult<()> {
+        let result: Vec<Self> = sqlx::query_as(
+            "select * from product_revisions where product_id = $1 order by id desc limit $2 offset $3",
+        )
+        .bind(product_id)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query("select count(*) as total from product_revisions where product_id = $1")
+            .bind(product_id)
+            .fetch_one(common::postgres().await)
+            .await?
+            .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result);
+
+        Ok(())
+    }
+
+    pub async fn get(id: i64, product_id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from product_revisions where id = $1 and product_id = $2")
+            .bind(id)
+            .bind(product_id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("修改记录不存在".to_string()))
+    }
+
+    /// 最新的修改记录
+    pub async fn latest(product_id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from product_revisions where product_id = $1 order by id desc limit 1")
+            .bind(product_id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("修改记录不存在".to_string()))
+    }
+
+    /// 对比两个版本, 返回有变化的字段
+    pub fn diff(&self, other: &Self) -> Vec<Value> {
+        let empty = serde_json::Map::new();
+        let before = self.snapshot.0.as_object().unwrap_or(&empty);
+        let after = other.snapshot.0.as_object().unwrap_or(&empty);
+
+        let mut fields = before.keys().chain(after.keys()).collect::<Vec<&String>>();
+        fields.sort();
+        fields.dedup();
+
+        fields
+            .into_iter()
+            .filter(|field| before.get(*field) != after.get(*field))
+            .map(|field| {
+                json!({
+                    "field": field,
+                    "before": before.get(field),
+                    "after": after.get(field),
+                })
+            })
+            .collect()
+    }
+
+    /// 恢复到当前版本, 恢复后会生成一条新的修改记录
+    pub async fn restore(&self, operator_id: i64) -> ApiResult<i64> {
+        let snapshot = &self.snapshot.0;
+        let mut tx = common::postgres().await.begin().await?;
+
+        let title = snapshot["title"].as_str().unwrap_or_default();
+        let exists = sqlx::query(
+            "select exists (select id from products where title = $1 and id != $2)",
+        )
+        .bind(title)
+        .bind(self.product_id)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<bool, _>("exists");
+        if exists {
+            return Err(ApiError::Error("恢复失败，商品名称重复".to_string()));
+        }
+
+        let rows = sqlx::query(
+            "update products set title = $1, long_title = $2, description = $3, image = $4, \
+            on_sale = $5, sku_price = $6, category_id = $7 where id = $8",
+        )
+        .bind(title)
+        .bind(snapshot["long_title"].as_str().unwrap_or_default())
+        .bind(snapshot["description"].as_str().unwrap_or_default())
+        .bind(snapshot["image"].clone())
+        .bind(snapshot["on_sale"].as_bool().unwrap_or_default())
+        .bind(snapshot["price"].as_f64().unwrap_or_default())
+        .bind(snapshot["category_id"].as_i64().unwrap_or_default())
+        .bind(self.product_id)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error("商品不存在".to_string()));
+        }
+
+        let watcher = SkuWatcher::watch(&[self.product_id], &mut tx).await?;
+        // sku按快照中的id恢复, 保证购物车、订单中的sku引用不变
+        let skus = snapshot["skus"].as_array().cloned().unwrap_or_default();
+        let sku_ids = skus
+            .iter()
+            .filter_map(|sku| sku["id"].as_i64())
+            .collect::<Vec<i64>>();
+        sqlx::query("delete from product_skus where product_id = $1 and id != all($2)")
+            .bind(self.product_id)
+            .bind(&sku_ids)
+            .execute(&mut tx)
+            .await?;
+        // 只恢复名称、描述、价格等信息, 库存保持当前值; 已删除的sku重新创建时库存为0
+        // 旧快照中没有重量, 恢复时保持当前重量
+        for sku in skus.iter() {
+            sqlx::query(
+                "insert into product_skus (id, title, description, price, stock, product_id, weight) \
+                values ($1, $2, $3, $4, 0, $5, coalesce($6, 0)) on conflict (id) do update set title = excluded.title, \
+                description = excluded.description, price = excluded.price, \
+                weight = coalesce($6, product_skus.weight)",
+            )
+            .bind(sku["id"].as_i64())
+            .bind(sku["title"].as_str().unwrap_or_default())
+            .bind(sku["description"].as_str().unwrap_or_default())
+            .bind(sku["price"].as_f64().unwrap_or_default())
+            .bind(self.product_id)
+            .bind(sku["weight"].as_i64().map(|weight| weight as i32))
+            .execute(&mut tx)
+            .await?;
+        }
+        Warehouse::sync_skus(self.product_id, &mut tx).await?;
+
+        sqlx::query("delete from product_properties where product_id = $1")
+            .bind(self.product_id)
+            .execute(&mut tx)
+            .await?;
+        for item in snapshot["property"].as_array().cloned().unwrap_or_default() {
+            sqlx::query(
+                "insert into product_properties (product_id, name, value) values ($1, $2, $3)",
+            )
+            .bind(self.product_id)
+            .bind(item["name"].as_str().unwrap_or_default())
+            .bind(item["value"].as_str().unwrap_or_default())
+            .execute(&mut tx)
+            .await?;
+        }
+
+        let revision_id = Self::record(self.product_id, operator_id, &mut tx).await?;
+        let alerts = watcher.changes(&mut tx).await?;
+        tx.commit().await?;
+        FavoriteAlert::publish(alerts).await;
+
+        Ok(revision_id)
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::Row;
+use tracing::error;
+
+use common::error::{ApiError, ApiResult};
+
+use crate::models::favorite_alerts::{FavoriteAlert, SkuWatcher};
+use crate::models::product_revisions::ProductRevision;
+
+/// 商品定时任务: 定时上下架、定时调价
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProductSchedule {
+    pub id: i64,
+    pub product_id: i64,
+    pub operator_id: i64,
+    // 上架状态, 为空时不修改
+    pub on_sale: Option<bool>,
+    // sku价格: {"sku_id": price}
+    pub sku_prices: Json<HashMap<i64, f64>>,
+    pub run_at: chrono::NaiveDateTime,
+    pub executed_at: Option<chrono::NaiveDateTime>,
+    // 执行失败的原因, 失败的任务不再重试
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl ProductSchedule {
+    /// 创建定时任务
+    pub async fn create(
+        product_id: i64,
+        operator_id: i64,
+        on_sale: Option<bool>,
+        sku_prices: HashMap<i64, f64>,
+        run_at: chrono::NaiveDateTime,
+    ) -> ApiResult<i64> {
+        if run_at <= chrono::Local::now().naive_local() {
+            return Err(ApiError::Error("执行时间必须晚于当前时间".to_string()));
+        }
+        if on_sale.is_none() && sku_prices.is_empty() {
+            return Err(ApiError::Error("没有需要执行的修改".to_string()));
+        }
+
+        let sku_ids = sku_prices.keys().cloned().collect::<Vec<i64>>();
+        let total = sqlx::query(
+            "select count(*) as total from product_skus where product_id = $1 and id = any($2)",
+        )
+        .bind(product_id)
+        .bind(&sku_ids)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("total");
+        if total as usize != sku_ids.len() {
+            return Err(ApiError::Error("商品sku不存在".to_string()));
+        }
+
+        Ok(sqlx::query(
+            "insert into product_schedules (product_id, operator_id, on_sale, sku_prices, run_at, created_at) \
+            values ($1, $2, $3, $4, $5, $6) RETURNING id",
+        )
+        .bind(product_id)
+        .bind(operator_id)
+        .bind(on_sale)
+        .bind(Json(sku_prices))
+        .bind(run_at)
+        .bind(chrono::Local::now().naive_local())
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("id"))
+    }
+
+    /// 商品待执行及执行失败的任务
+    pub async fn index(product_id: i64) -> ApiResult<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "select * from product_schedules where product_id = $1 and (executed_at is null or error is not null) \
+            order by run_at asc",
+        )
+        .bind(product_id)
+        .fetch_all(common::postgres().await)
+        .await?)
+    }
+
+    /// 取消未执行的任务
+    pub async fn cancel(id: i64, product_id: i64) -> ApiResult<bool> {
+        Ok(sqlx::query(
+            "delete from product_schedules where id = $1 and product_id = $2 and executed_at is null",
+        )
+        .bind(id)
+        .bind(product_id)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// 执行已到期的任务
+    pub async fn run_due() -> ApiResult<u64> {
+        let schedules: Vec<Self> = sqlx::query_as(
+            "select * from product_schedules where executed_at is null and run_at <= $1 order by run_at asc",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        // 单个任务失败时记录原因并继续执行后续任务, 避免阻塞后续任务
+        let mut counter = 0u64;
+        for schedule in schedules {
+            match schedule.execute().await {
+                Ok(()) => counter += 1,
+                Err(e) => {
+                    error!("商品定时任务[{}]执行失败: {}", schedule.id, e);
+                    if let Err(e) = schedule.fail(&e.to_string()).await {
+                        error!("商品定时任务[{}]状态保存失败: {}", schedule.id, e);
+                    }
+                }
+            }
+        }
+
+        Ok(counter)
+    }
+
+    /// 标记任务执行失败
+    async fn fail(&self, reason: &str) -> ApiResult<()> {
+        sqlx::query(
+            "update product_schedules set executed_at = $1, error = $2 where id = $3 and executed_at is null",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .bind(reason)
+        .bind(self.id)
+        .execute(common::postgres().await)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> ApiResult<()> {
+        let mut tx = common::postgres().await.begin().await?;
+
+        // 防止多个实例重复执行
+        let rows = sqlx::query(
+            "update product_schedules set executed_at = $1 where id = $2 and executed_at is null",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .bind(self.id)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Ok(());
+        }
+        let watcher = SkuWatcher::watch(&[self.product_id], &mut tx).await?;
+
+        if let Some(on_sale) = self.on_sale {
+            sqlx::query("update products set on_sale = $1 where id = $2")
+                .bind(on_sale)
+                .bind(self.product_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        if !self.sku_prices.0.is_empty() {
+            for (sku_id, price) in self.sku_prices.0.iter() {
+                sqlx::query("update product_skus set price = $1 where id = $2 and product_id = $3")
+                    .bind(price)
+                    .bind(sku_id)
+                    .bind(self.product_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+
+            sqlx::query(
+                "update products set sku_price = (select min(price) from product_skus where product_id = $1) where id = $1",
+            )
+            .bind(self.product_id)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        ProductRevision::record(self.product_id, self.operator_id, &mut tx).await?;
+        let alerts = watcher.changes(&mut tx).await?;
+        tx.commit().await?;
+        FavoriteAlert::publish(alerts).await;
+
+        Ok(())
+    }
+}
@@ -5,6 +5,8 @@ use sqlx::{Arguments, Postgres, QueryBuilder, Row, Transaction};
 
 use common::error::ApiResult;
 
+use crate::models::warehouses::Warehouse;
+
 #[derive(Debug, Deserialize, Serialize, Default, sqlx::FromRow)]
 pub struct ProductSku {
     pub id: i64,
@@ -13,6 +15,9 @@ pub struct ProductSku {
     pub price: f64,
     pub stock: i32,
     pub product_id: i64,
+    // 重量(克), 用于计算运费
+    #[serde(default)]
+    pub weight: i32,
 }
 
 /// 验证订单信息
@@ -24,7 +29,9 @@ pub struct CustomProductSku {
     pub picture: String,
     pub stock: i32,
     pub on_sale: bool,
-    pub price: i64,
+    // 单价(元), 计算金额时使用 common::cart::line_money 换算为分
+    pub price: f64,
+    pub weight: i32,
 }
 
 impl ProductSku {
@@ -54,25 +61,77 @@ impl ProductSku {
         Ok(data_map)
     }
 
-    /// 添加商品的sku
-    pub async fn add_product_sku(
+    /// 保存商品的sku: 已有的sku原地修改, 保证sku id不变; 新增的sku插入, 未提交的sku删除
+    pub async fn save_product_skus(
         product_id: i64,
         skus: &Vec<ProductSku>,
         tx: &mut Transaction<'_, Postgres>,
     ) -> ApiResult<bool> {
-        let mut query_build: QueryBuilder<Postgres> = sqlx::QueryBuilder::new(
-            "insert into product_skus (title, description, price, stock, product_id) ",
+        let existing =
+            sqlx::query("select id,title from product_skus where product_id = $1 for update")
+                .bind(product_id)
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|row| (row.get::<i64, _>("id"), row.get::<String, _>("title")))
+                .collect::<Vec<(i64, String)>>();
+        let matched = common::sku::match_ids(
+            &existing
+                .iter()
+                .map(|(id, title)| (*id, title.as_str()))
+                .collect::<Vec<(i64, &str)>>(),
+            &skus
+                .iter()
+                .map(|sku| (sku.id, sku.title.as_str()))
+                .collect::<Vec<(i64, &str)>>(),
         );
 
-        query_build.push_values(skus.as_slice().iter().take(skus.len()), |mut b, sku| {
-            b.push_bind(sku.title.clone())
-                .push_bind(sku.description.clone())
-                .push_bind(sku.price)
-                .push_bind(sku.stock)
-                .push_bind(product_id);
-        });
+        let kept = matched.iter().flatten().cloned().collect::<Vec<i64>>();
+        sqlx::query("delete from product_skus where product_id = $1 and id != all($2)")
+            .bind(product_id)
+            .bind(&kept)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut rows_num = 0u64;
+        let mut added = vec![];
+        for (sku, id) in skus.iter().zip(matched) {
+            match id {
+                Some(id) => {
+                    rows_num += sqlx::query(
+                        "update product_skus set title = $1, description = $2, price = $3, stock = $4, weight = $5 \
+                        where id = $6",
+                    )
+                    .bind(&sku.title)
+                    .bind(&sku.description)
+                    .bind(sku.price)
+                    .bind(sku.stock)
+                    .bind(sku.weight)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                }
+                None => added.push(sku),
+            }
+        }
+
+        if !added.is_empty() {
+            let mut query_build: QueryBuilder<Postgres> = sqlx::QueryBuilder::new(
+                "insert into product_skus (title, description, price, stock, product_id, weight) ",
+            );
+            query_build.push_values(added.iter(), |mut b, sku| {
+                b.push_bind(sku.title.clone())
+                    .push_bind(sku.description.clone())
+                    .push_bind(sku.price)
+                    .push_bind(sku.stock)
+                    .push_bind(product_id)
+                    .push_bind(sku.weight);
+            });
+            rows_num += query_build.build().execute(&mut *tx).await?.rows_affected();
+        }
+        Warehouse::sync_skus(product_id, tx).await?;
 
-        let rows_num = query_build.build().execute(tx).await?.rows_affected();
         Ok(rows_num as usize == skus.len())
     }
 
@@ -104,7 +163,7 @@ impl ProductSku {
 
         let query_builder = format!(
             "select sku.*,p.on_sale,p.image from ( \
-        SELECT id,product_id,stock,title,description,price FROM product_skus WHERE {} ) as sku \
+        SELECT id,product_id,stock,title,description,price,weight FROM product_skus WHERE {} ) as sku \
         left join  products as p ON sku.product_id = p.id",
             &rows[..(rows.len() - 3)]
         );
@@ -124,7 +183,8 @@ impl ProductSku {
                     picture: picture,
                     stock: row.get::<i32, _>("stock"),
                     on_sale: true,
-                    price: row.get::<f64, _>("price") as i64,
+                    price: row.get::<f64, _>("price"),
+                    weight: row.get::<i32, _>("weight"),
                 }
             })
             .map(|sku| (sku.product_id, sku))
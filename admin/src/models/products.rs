@@ -4,14 +4,17 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::postgres::types::PgMoney;
 use sqlx::types::Json;
-use sqlx::Row;
+use sqlx::{Postgres, Row, Transaction};
 
 use common::error::{ApiError, ApiResult};
 use common::Pagination;
 
+use crate::models::categories::Categories;
 use crate::models::crowdfunding::CrowdfundingProduct;
+use crate::models::favorite_alerts::{FavoriteAlert, SkuWatcher};
 use crate::models::favorite_products::FavoriteProducts;
 use crate::models::product_property::ProductProperty;
+use crate::models::product_revisions::ProductRevision;
 use crate::models::product_skus::ProductSku;
 use crate::models::products::PType::Crowdfunding;
 
@@ -52,18 +55,32 @@ impl Product {
         product: Product,
         target_amount: PgMoney,
         end_at: Option<chrono::NaiveDateTime>,
+        operator_id: i64,
     ) -> ApiResult<u64> {
         let mut tx = common::postgres().await.begin().await?;
+        let id = Self::insert(&product, target_amount, end_at, &mut tx).await?;
+        ProductRevision::record(id, operator_id, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(id as u64)
+    }
 
+    /// 在事务中创建商品及其sku、属性
+    pub async fn insert(
+        product: &Product,
+        target_amount: PgMoney,
+        end_at: Option<chrono::NaiveDateTime>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<i64> {
         let product_sku = product
             .skus
             .iter()
-            .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap())
-            .unwrap();
+            .min_by(|a, b| a.price.total_cmp(&b.price))
+            .ok_or(ApiError::Error("商品至少需要一个sku".to_string()))?;
 
         let id = sqlx::query(
-            "insert into products (title,description,image,on_sale,sku_price,category_id,long_title) \
-            values ($1, $2, $3, $4, $5, $6) RETURNING id",
+            "insert into products (title,description,image,on_sale,sku_price,category_id,long_title,type) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
         )
             .bind(&product.title.clone())
             .bind(&product.description.clone())
@@ -72,30 +89,25 @@ impl Product {
             .bind(product_sku.price)
             .bind(product.category_id)
             .bind(product.long_title.clone())
-            .fetch_one(&mut tx)
+            .bind(&product.r#type)
+            .fetch_one(&mut *tx)
             .await?
             .get::<i64, _>("id");
 
-        ProductSku::delete_product_sku(id, &mut tx).await?;
-
-        if false == ProductSku::add_product_sku(id, &product.skus, &mut tx).await? {
-            tx.rollback().await?;
+        if false == ProductSku::save_product_skus(id, &product.skus, &mut *tx).await? {
             return Err(ApiError::Error("添加商品sku失败, 请稍后重试".to_string()));
         }
 
-        if product.r#type == Crowdfunding {
-            CrowdfundingProduct::store(id, target_amount, end_at.unwrap(), &mut tx).await?;
-        }
+        Self::save_crowdfunding(id, product, target_amount, end_at, &mut *tx).await?;
 
         let property = product
             .property
             .iter()
             .map(|i| (i.name.as_str(), i.value.as_str()))
             .collect::<Vec<(&str, &str)>>();
-        ProductProperty::create(id, property, &mut tx).await?;
-        //添加sku
-        tx.commit().await?;
-        Ok(id as u64)
+        ProductProperty::create(id, property, &mut *tx).await?;
+
+        Ok(id)
     }
 
     /// 商品详情
@@ -153,8 +165,8 @@ impl Product {
         if let Some(cid) = payload.get("category_id") {
             let category_id = cid.parse::<i64>().unwrap_or(0);
             if category_id > 0 {
-                let sql = format!(" and category_id in (SELECT id FROM categories  WHERE id = {} or path like '%{}%' and deleted_at is NULL)",
-                                  category_id, category_id);
+                let sql = format!(" and category_id in (SELECT id FROM categories  WHERE (id = {} or {}) and deleted_at is NULL)",
+                                  category_id, Categories::descendant_sql(category_id));
                 sql_str.push_str(&sql);
                 count_str.push_str(&sql);
             }
@@ -202,7 +214,7 @@ impl Product {
     }
 
     /// 更新
-    pub async fn update(product: Self) -> ApiResult<bool> {
+    pub async fn update(product: Self, operator_id: i64) -> ApiResult<bool> {
         let count =
             sqlx::query("select count(*) as count from products where title = $1 and id != $2")
                 .bind(product.title.clone())
@@ -217,10 +229,10 @@ impl Product {
         let product_sku = product
             .skus
             .iter()
-            .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap())
-            .unwrap();
+            .min_by(|a, b| a.price.total_cmp(&b.price))
+            .ok_or(ApiError::Error("商品至少需要一个sku".to_string()))?;
         let mut tx = common::postgres().await.begin().await?;
-        ProductSku::delete_product_sku(product.id, &mut tx).await?;
+        let watcher = SkuWatcher::watch(&[product.id], &mut tx).await?;
         let row_bool = sqlx::query("update products set title = $1, description = $2, image = $3, on_sale = $4, sku_price = $5, long_title = $6 where id = $7")
             .bind(product.title.clone())
             .bind(product.description.clone())
@@ -238,17 +250,184 @@ impl Product {
             return Err(ApiError::Error("商品信息修改失败, 请稍后重试".to_string()));
         }
 
-        if false == ProductSku::add_product_sku(product.id, &product.skus, &mut tx).await? {
+        if false == ProductSku::save_product_skus(product.id, &product.skus, &mut tx).await? {
             tx.rollback().await?;
 
             return Err(ApiError::Error("修改商品sku失败, 请稍后重试".to_string()));
         }
 
+        ProductRevision::record(product.id, operator_id, &mut tx).await?;
+        let alerts = watcher.changes(&mut tx).await?;
         tx.commit().await?;
+        FavoriteAlert::publish(alerts).await;
 
         Ok(row_bool)
     }
 
+    /// 批量导入, 商品名称已存在时更新, 否则创建, 全部数据在同一事务中完成
+    pub async fn import(
+        products: Vec<(Product, PgMoney, Option<chrono::NaiveDateTime>)>,
+        operator_id: i64,
+    ) -> ApiResult<(u64, u64)> {
+        let (mut created, mut updated) = (0u64, 0u64);
+        let mut alerts = Vec::new();
+        let mut tx = common::postgres().await.begin().await?;
+
+        for (product, target_amount, end_at) in products.iter() {
+            let product_id = sqlx::query("select id from products where title = $1")
+                .bind(&product.title)
+                .fetch_optional(&mut tx)
+                .await?
+                .map(|row| row.get::<i64, _>("id"));
+
+            let id = match product_id {
+                Some(id) => {
+                    let watcher = SkuWatcher::watch(&[id], &mut tx).await?;
+                    Self::modify(id, product, *target_amount, *end_at, &mut tx).await?;
+                    alerts.extend(watcher.changes(&mut tx).await?);
+                    updated += 1;
+                    id
+                }
+                None => {
+                    created += 1;
+                    Self::insert(product, *target_amount, *end_at, &mut tx).await?
+                }
+            };
+            ProductRevision::record(id, operator_id, &mut tx).await?;
+        }
+
+        tx.commit().await?;
+        FavoriteAlert::publish(alerts).await;
+
+        Ok((created, updated))
+    }
+
+    /// 在事务中覆盖商品信息、类型、众筹信息、sku及属性
+    async fn modify(
+        id: i64,
+        product: &Product,
+        target_amount: PgMoney,
+        end_at: Option<chrono::NaiveDateTime>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        let product_sku = product
+            .skus
+            .iter()
+            .min_by(|a, b| a.price.total_cmp(&b.price))
+            .ok_or(ApiError::Error("商品至少需要一个sku".to_string()))?;
+
+        sqlx::query("update products set long_title = $1, description = $2, image = $3, on_sale = $4, \
+        sku_price = $5, category_id = $6, type = $7 where id = $8")
+            .bind(&product.long_title)
+            .bind(&product.description)
+            .bind(product.image.clone())
+            .bind(product.on_sale)
+            .bind(product_sku.price)
+            .bind(product.category_id)
+            .bind(&product.r#type)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        Self::save_crowdfunding(id, product, target_amount, end_at, &mut *tx).await?;
+
+        if false == ProductSku::save_product_skus(id, &product.skus, &mut *tx).await? {
+            return Err(ApiError::Error(format!("商品[{}]sku保存失败", product.title)));
+        }
+
+        ProductProperty::delete_product_property(id, &mut *tx).await?;
+        let property = product
+            .property
+            .iter()
+            .map(|i| (i.name.as_str(), i.value.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        ProductProperty::create(id, property, &mut *tx).await?;
+
+        Ok(())
+    }
+
+    /// 在事务中保存众筹信息, 商品改为普通商品时删除原有的众筹信息
+    async fn save_crowdfunding(
+        id: i64,
+        product: &Product,
+        target_amount: PgMoney,
+        end_at: Option<chrono::NaiveDateTime>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        let crowdfunding_id = sqlx::query(
+            "select id from crowdfunding_products where product_id = $1 and deleted_at is null",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.get::<i64, _>("id"));
+
+        if product.r#type == Crowdfunding {
+            let end_at = end_at.ok_or(ApiError::Error(format!(
+                "众筹商品[{}]需要填写截止时间",
+                product.title
+            )))?;
+            match crowdfunding_id {
+                Some(crowdfunding_id) => {
+                    CrowdfundingProduct::update(crowdfunding_id, target_amount, end_at, &mut *tx)
+                        .await?;
+                }
+                None => {
+                    CrowdfundingProduct::store(id, target_amount, end_at, &mut *tx).await?;
+                }
+            }
+        } else if let Some(crowdfunding_id) = crowdfunding_id {
+            sqlx::query("update crowdfunding_products set deleted_at = $1 where id = $2")
+                .bind(chrono::Local::now().naive_local())
+                .bind(crowdfunding_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// 导出全部商品(含sku、属性及众筹信息)
+    pub async fn export() -> ApiResult<Vec<(Self, Option<CrowdfundingProduct>)>> {
+        let mut result: Vec<Self> = sqlx::query("select * from products order by id asc")
+            .fetch_all(common::postgres().await)
+            .await?
+            .into_iter()
+            .map(|row| Product {
+                id: row.get::<i64, _>("id"),
+                title: row.get("title"),
+                long_title: row.get("long_title"),
+                description: row.get("description"),
+                image: row.get::<Json<Vec<String>>, _>("image"),
+                on_sale: row.get::<bool, _>("on_sale"),
+                rating: row.get::<i64, _>("rating"),
+                sold_count: row.get::<i64, _>("sold_count"),
+                review_count: row.get::<i32, _>("review_count"),
+                price: row.get::<f64, _>("sku_price"),
+                category_id: row.get::<i64, _>("category_id"),
+                skus: Vec::default(),
+                property: Vec::default(),
+                r#type: row.get::<PType, _>("type"),
+            })
+            .collect::<Vec<Self>>();
+
+        let ids = result.iter().map(|p| p.id).collect::<Vec<i64>>();
+        let mut skus = ProductSku::skus(ids.clone()).await?;
+        let mut property = ProductProperty::product_propertys(ids.clone()).await?;
+        let mut crowdfunding = CrowdfundingProduct::by_product_ids(ids).await?;
+        for product in result.iter_mut() {
+            product.skus = skus.remove(&product.id).unwrap_or_default();
+            product.property = property.remove(&product.id).unwrap_or_default();
+        }
+
+        Ok(result
+            .into_iter()
+            .map(|product| {
+                let crowdfunding = crowdfunding.remove(&product.id);
+                (product, crowdfunding)
+            })
+            .collect())
+    }
+
     /// 删除
     pub async fn delete(product_id: u64) -> ApiResult<bool> {
         FavoriteProducts::un_favorite_product(product_id as i64).await?;
@@ -277,6 +456,7 @@ impl Product {
                     price: item.price,
                     stock: item.stock,
                     product_id: item.product_id,
+                    weight: item.weight,
                 })
             }
 
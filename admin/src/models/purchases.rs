@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::postgres::types::PgMoney;
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder, Row, Transaction};
+
+use common::error::{ApiError, ApiResult};
+use common::purchase::{PurchaseLine, ReceiveLine, ReqSupplier};
+use common::reorder;
+use common::Pagination;
+
+use crate::models::favorite_alerts::{FavoriteAlert, SkuWatcher};
+use crate::models::warehouses::Warehouse;
+
+/// 供应商
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Supplier {
+    pub id: i64,
+    pub name: String,
+    pub contact_name: String,
+    pub contact_phone: String,
+    pub email: String,
+    pub address: String,
+    // 采购周期(天), 用于计算补货建议
+    pub lead_days: i32,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Supplier {
+    pub async fn index() -> ApiResult<Vec<Self>> {
+        Ok(sqlx::query_as("select * from suppliers order by id desc")
+            .fetch_all(common::postgres().await)
+            .await?)
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from suppliers where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("供应商不存在".to_string()))
+    }
+
+    /// 创建或修改, id 为0时创建
+    pub async fn save(id: i64, info: ReqSupplier) -> ApiResult<i64> {
+        let now = chrono::Local::now().naive_local();
+        if id == 0 {
+            return Ok(sqlx::query(
+                "insert into suppliers (name, contact_name, contact_phone, email, address, lead_days, enabled, \
+                created_at, updated_at) values ($1, $2, $3, $4, $5, $6, $7, $8, $8) RETURNING id",
+            )
+            .bind(info.name.unwrap_or_default())
+            .bind(info.contact_name.unwrap_or_default())
+            .bind(info.contact_phone.unwrap_or_default())
+            .bind(info.email.unwrap_or_default())
+            .bind(info.address.unwrap_or_default())
+            .bind(info.lead_days.unwrap_or(7))
+            .bind(info.enabled.unwrap_or(true))
+            .bind(now)
+            .fetch_one(common::postgres().await)
+            .await?
+            .get::<i64, _>("id"));
+        }
+
+        let rows = sqlx::query(
+            "update suppliers set name = $1, contact_name = $2, contact_phone = $3, email = $4, address = $5, \
+            lead_days = $6, enabled = $7, updated_at = $8 where id = $9",
+        )
+        .bind(info.name.unwrap_or_default())
+        .bind(info.contact_name.unwrap_or_default())
+        .bind(info.contact_phone.unwrap_or_default())
+        .bind(info.email.unwrap_or_default())
+        .bind(info.address.unwrap_or_default())
+        .bind(info.lead_days.unwrap_or(7))
+        .bind(info.enabled.unwrap_or(true))
+        .bind(now)
+        .bind(id)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error("供应商不存在".to_string()));
+        }
+
+        Ok(id)
+    }
+}
+
+/// 采购单状态
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum PurchaseStatus {
+    // 草稿
+    Draft = 1,
+    // 已下单, 待收货
+    Ordered = 2,
+    // 部分收货
+    PartReceived = 3,
+    // 已完成
+    Received = 4,
+    // 已取消
+    Cancelled = 5,
+}
+
+impl Default for PurchaseStatus {
+    fn default() -> Self {
+        PurchaseStatus::Draft
+    }
+}
+
+impl TryFrom<u8> for PurchaseStatus {
+    type Error = ApiError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(PurchaseStatus::Draft),
+            2 => Ok(PurchaseStatus::Ordered),
+            3 => Ok(PurchaseStatus::PartReceived),
+            4 => Ok(PurchaseStatus::Received),
+            5 => Ok(PurchaseStatus::Cancelled),
+            _ => Err(ApiError::Error("采购单状态错误".to_string())),
+        }
+    }
+}
+
+impl AsRef<str> for PurchaseStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            PurchaseStatus::Draft => "草稿",
+            PurchaseStatus::Ordered => "待收货",
+            PurchaseStatus::PartReceived => "部分收货",
+            PurchaseStatus::Received => "已完成",
+            PurchaseStatus::Cancelled => "已取消",
+        }
+    }
+}
+
+/// 采购单
+#[derive(Debug, sqlx::FromRow)]
+pub struct PurchaseOrder {
+    pub id: i64,
+    pub no: String,
+    pub supplier_id: i64,
+    // 收货仓库
+    pub warehouse_id: i64,
+    pub status: PurchaseStatus,
+    pub total_amount: PgMoney,
+    pub remark: String,
+    pub ordered_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// 采购单明细
+#[derive(Debug, sqlx::FromRow)]
+pub struct PurchaseOrderLine {
+    pub id: i64,
+    pub purchase_order_id: i64,
+    pub product_id: i64,
+    pub sku_id: i64,
+    pub sku_title: String,
+    pub quantity: i32,
+    // 已收货数量
+    pub received: i32,
+    pub unit_cost: PgMoney,
+}
+
+/// 收货记录
+#[derive(Debug, sqlx::FromRow)]
+pub struct PurchaseReceipt {
+    pub id: i64,
+    pub purchase_order_id: i64,
+    pub lines: Json<Vec<ReceiveLine>>,
+    pub remark: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl PurchaseOrder {
+    /// 创建采购单(草稿)
+    pub async fn create(
+        supplier_id: i64,
+        warehouse_id: i64,
+        lines: Vec<PurchaseLine>,
+        remark: String,
+    ) -> ApiResult<i64> {
+        Supplier::get(supplier_id).await?;
+        Warehouse::get(warehouse_id).await?;
+
+        let mut quantities: HashMap<i64, (i32, f64)> = HashMap::new();
+        for line in lines.iter() {
+            if line.quantity <= 0 || line.unit_cost < 0.0 {
+                return Err(ApiError::Error("采购数量或单价错误".to_string()));
+            }
+            if quantities
+                .insert(line.sku_id, (line.quantity, line.unit_cost))
+                .is_some()
+            {
+                return Err(ApiError::Error(format!("sku {} 重复", line.sku_id)));
+            }
+        }
+
+        let sku_ids = quantities.keys().cloned().collect::<Vec<i64>>();
+        let skus = sqlx::query("select id,product_id,title from product_skus where id = any($1)")
+            .bind(&sku_ids)
+            .fetch_all(common::postgres().await)
+            .await?;
+        if skus.len() != sku_ids.len() {
+            return Err(ApiError::Error("采购的sku不存在".to_string()));
+        }
+
+        let total = quantities
+            .values()
+            .map(|(quantity, cost)| (cost * 100.0).round() as i64 * *quantity as i64)
+            .sum::<i64>();
+        let now = chrono::Local::now().naive_local();
+        let mut tx = common::postgres().await.begin().await?;
+        let id = sqlx::query(
+            "insert into purchase_orders (no, supplier_id, warehouse_id, status, total_amount, remark, \
+            created_at, updated_at) values ($1, $2, $3, $4, $5, $6, $7, $7) RETURNING id",
+        )
+        .bind(common::snow_id().await.to_string())
+        .bind(supplier_id)
+        .bind(warehouse_id)
+        .bind(PurchaseStatus::Draft)
+        .bind(PgMoney(total))
+        .bind(remark)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+
+        let mut query_build: QueryBuilder<Postgres> = QueryBuilder::new(
+            "insert into purchase_order_lines (purchase_order_id, product_id, sku_id, sku_title, quantity, \
+            received, unit_cost) ",
+        );
+        query_build.push_values(skus.iter(), |mut b, sku| {
+            let sku_id = sku.get::<i64, _>("id");
+            let (quantity, cost) = quantities[&sku_id];
+            b.push_bind(id)
+                .push_bind(sku.get::<i64, _>("product_id"))
+                .push_bind(sku_id)
+                .push_bind(sku.get::<String, _>("title"))
+                .push_bind(quantity)
+                .push_bind(0)
+                .push_bind(PgMoney((cost * 100.0).round() as i64));
+        });
+        query_build.build().execute(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from purchase_orders where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("采购单不存在".to_string()))
+    }
+
+    pub async fn lines(&self) -> ApiResult<Vec<PurchaseOrderLine>> {
+        Ok(sqlx::query_as(
+            "select * from purchase_order_lines where purchase_order_id = $1 order by id asc",
+        )
+        .bind(self.id)
+        .fetch_all(common::postgres().await)
+        .await?)
+    }
+
+    /// 修改状态, from 为允许的当前状态
+    async fn transition(&self, from: &[PurchaseStatus], to: PurchaseStatus) -> ApiResult<()> {
+        if !from.contains(&self.status) {
+            return Err(ApiError::Error(format!(
+                "采购单{}, 不能执行此操作",
+                self.status.as_ref()
+            )));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let rows = sqlx::query(
+            "update purchase_orders set status = $1, \
+            ordered_at = case when $1 = $2 then $3 else ordered_at end, updated_at = $3 \
+            where id = $4 and status = $5",
+        )
+        .bind(to)
+        .bind(PurchaseStatus::Ordered)
+        .bind(now)
+        .bind(self.id)
+        .bind(self.status)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error(
+                "采购单状态已变更, 请刷新后重试".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 确认下单
+    pub async fn submit(&self) -> ApiResult<()> {
+        self.transition(&[PurchaseStatus::Draft], PurchaseStatus::Ordered)
+            .await
+    }
+
+    /// 取消, 已收货的采购单不能取消
+    pub async fn cancel(&self) -> ApiResult<()> {
+        self.transition(
+            &[PurchaseStatus::Draft, PurchaseStatus::Ordered],
+            PurchaseStatus::Cancelled,
+        )
+        .await
+    }
+
+    /// 收货, 允许分批收货, 收货数量入库到采购单的仓库
+    pub async fn receive(&self, lines: Vec<ReceiveLine>, remark: String) -> ApiResult<i64> {
+        const RECEIVABLE: [PurchaseStatus; 2] =
+            [PurchaseStatus::Ordered, PurchaseStatus::PartReceived];
+
+        let mut tx = common::postgres().await.begin().await?;
+        // 锁定采购单, 避免与取消同时执行
+        let status = sqlx::query("select status from purchase_orders where id = $1 for update")
+            .bind(self.id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(ApiError::Error("采购单不存在".to_string()))?
+            .get::<PurchaseStatus, _>("status");
+        if !RECEIVABLE.contains(&status) {
+            return Err(ApiError::Error(format!(
+                "采购单{}, 不能收货",
+                status.as_ref()
+            )));
+        }
+
+        let order_lines: Vec<PurchaseOrderLine> = sqlx::query_as(
+            "select * from purchase_order_lines where purchase_order_id = $1 order by id asc for update",
+        )
+        .bind(self.id)
+        .fetch_all(&mut tx)
+        .await?;
+        let product_ids = order_lines
+            .iter()
+            .map(|line| line.product_id)
+            .collect::<Vec<i64>>();
+        let watcher = SkuWatcher::watch(&product_ids, &mut tx).await?;
+
+        let (received, finished) = reorder::receipt(
+            &order_lines
+                .iter()
+                .map(|line| (line.id, line.quantity, line.received))
+                .collect::<Vec<(i64, i32, i32)>>(),
+            &lines
+                .iter()
+                .map(|line| (line.line_id, line.quantity))
+                .collect::<Vec<(i64, i32)>>(),
+        )
+        .map_err(
+            |line_id| match order_lines.iter().find(|line| line.id == line_id) {
+                Some(line) => ApiError::Error(format!(
+                    "{} 可收货数量为: {}",
+                    line.sku_title,
+                    line.quantity - line.received
+                )),
+                None => ApiError::Error(format!("采购明细 {} 不存在", line_id)),
+            },
+        )?;
+
+        let now = chrono::Local::now().naive_local();
+        let receipt_id = sqlx::query(
+            "insert into purchase_receipts (purchase_order_id, lines, remark, created_at) \
+            values ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(self.id)
+        .bind(Json(&lines))
+        .bind(remark)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+
+        for order_line in order_lines.iter() {
+            let quantity = match received.get(&order_line.id) {
+                Some(quantity) => *quantity,
+                None => continue,
+            };
+            let sku_id = Self::line_sku(order_line, &mut tx).await?;
+            sqlx::query(
+                "update purchase_order_lines set received = received + $1, sku_id = $2 where id = $3",
+            )
+            .bind(quantity)
+            .bind(sku_id)
+            .bind(order_line.id)
+            .execute(&mut tx)
+            .await?;
+            Warehouse::post_movement(
+                self.warehouse_id,
+                sku_id,
+                quantity,
+                "purchase",
+                receipt_id,
+                &mut tx,
+            )
+            .await?;
+        }
+
+        // 全部收货后完成
+        let status = if finished {
+            PurchaseStatus::Received
+        } else {
+            PurchaseStatus::PartReceived
+        };
+        let rows = sqlx::query(
+            "update purchase_orders set status = $1, updated_at = $2 where id = $3 and status = any($4)",
+        )
+        .bind(status)
+        .bind(now)
+        .bind(self.id)
+        .bind(RECEIVABLE.iter().map(|status| *status as i16).collect::<Vec<i16>>())
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if rows == 0 {
+            return Err(ApiError::Error(
+                "采购单状态已变更, 请刷新后重试".to_string(),
+            ));
+        }
+
+        // 库存增加后通知到货
+        let alerts = watcher.changes(&mut tx).await?;
+        tx.commit().await?;
+        FavoriteAlert::publish(alerts).await;
+
+        Ok(receipt_id)
+    }
+
+    // 采购明细当前的sku, 原sku已删除时按商品及sku名称重新关联
+    async fn line_sku(
+        line: &PurchaseOrderLine,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<i64> {
+        sqlx::query(
+            "select coalesce((select id from product_skus where id = $1), \
+            (select id from product_skus where product_id = $2 and title = $3 order by id asc limit 1)) as id",
+        )
+        .bind(line.sku_id)
+        .bind(line.product_id)
+        .bind(&line.sku_title)
+        .fetch_one(&mut *tx)
+        .await?
+        .get::<Option<i64>, _>("id")
+        .ok_or(ApiError::Error(format!(
+            "{} 已删除, 不能收货",
+            line.sku_title
+        )))
+    }
+
+    /// 详情: 明细及收货记录
+    pub async fn detail(&self) -> ApiResult<serde_json::Value> {
+        let lines = self.lines().await?;
+        let receipts: Vec<PurchaseReceipt> = sqlx::query_as(
+            "select * from purchase_receipts where purchase_order_id = $1 order by id asc",
+        )
+        .bind(self.id)
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let mut result = self.to_json();
+        result["lines"] = json!(lines
+            .iter()
+            .map(|line| json!({
+                "id": line.id,
+                "product_id": line.product_id,
+                "sku_id": line.sku_id,
+                "sku_title": line.sku_title,
+                "quantity": line.quantity,
+                "received": line.received,
+                "unit_cost": line.unit_cost.0 as f64 / 100.0,
+            }))
+            .collect::<Vec<serde_json::Value>>());
+        result["receipts"] = json!(receipts
+            .iter()
+            .map(|receipt| json!({
+                "id": receipt.id,
+                "lines": receipt.lines.0,
+                "remark": receipt.remark,
+                "created_at": common::time_ymd_his(receipt.created_at),
+            }))
+            .collect::<Vec<serde_json::Value>>());
+
+        Ok(result)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "no": self.no,
+            "supplier_id": self.supplier_id,
+            "warehouse_id": self.warehouse_id,
+            "status": self.status.as_ref(),
+            "total_amount": self.total_amount.0 as f64 / 100.0,
+            "remark": self.remark,
+            "ordered_at": self.ordered_at.map(common::time_ymd_his),
+            "created_at": common::time_ymd_his(self.created_at),
+        })
+    }
+
+    /// 采购单列表
+    pub async fn index(
+        supplier_id: Option<i64>,
+        status: Option<PurchaseStatus>,
+        pagination: &mut Pagination<serde_json::Value>,
+    ) -> ApiResult<()> {
+        let result: Vec<Self> = sqlx::query_as(
+            "select * from purchase_orders where ($1::INT8 is null or supplier_id = $1) \
+            and ($2::INT2 is null or status = $2) order by id desc limit $3 offset $4",
+        )
+        .bind(supplier_id)
+        .bind(status)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query(
+            "select count(*) as total from purchase_orders where ($1::INT8 is null or supplier_id = $1) \
+            and ($2::INT2 is null or status = $2)",
+        )
+        .bind(supplier_id)
+        .bind(status)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result.iter().map(|item| item.to_json()).collect());
+
+        Ok(())
+    }
+
+    /// 每日记录商品销量快照, 用于计算日均销量
+    pub async fn snapshot_sales() -> ApiResult<u64> {
+        Ok(sqlx::query(
+            "insert into product_sales_snapshots (product_id, sold_count, created_at) \
+            select id, sold_count, $1 from products where on_sale = true",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected())
+    }
+
+    /// 补货建议: 按 sold_count 的日均增长计算, 商品销量按sku的历史销量占比分摊到sku
+    pub async fn suggestions(
+        window_days: i64,
+        lead_days: u32,
+        cover_days: u32,
+    ) -> ApiResult<Vec<serde_json::Value>> {
+        let now = chrono::Local::now().naive_local();
+        // 商品id => (标题, 日均销量)
+        let products = sqlx::query(
+            "select p.id,p.title,p.sold_count,s.sold_count as sold_before,s.created_at from products as p \
+            inner join lateral (select sold_count,created_at from product_sales_snapshots \
+            where product_id = p.id and created_at >= $1 order by created_at asc limit 1) as s on true \
+            where p.on_sale = true",
+        )
+        .bind(now - chrono::Duration::days(window_days))
+        .fetch_all(common::postgres().await)
+        .await?
+        .iter()
+        .filter_map(|row| {
+            let days = (now - row.get::<chrono::NaiveDateTime, _>("created_at")).num_days();
+            let velocity = reorder::velocity(
+                row.get::<i64, _>("sold_count"),
+                row.get::<i64, _>("sold_before"),
+                days,
+            );
+            (velocity > 0.0).then(|| {
+                (
+                    row.get::<i64, _>("id"),
+                    (row.get::<String, _>("title"), velocity),
+                )
+            })
+        })
+        .collect::<HashMap<i64, (String, f64)>>();
+        if products.is_empty() {
+            return Ok(vec![]);
+        }
+        let product_ids = products.keys().cloned().collect::<Vec<i64>>();
+
+        // sku历史销量占比
+        let sold = sqlx::query(
+            "select (product_sku->>'sku_id')::INT8 as sku_id, sum((product_sku->>'amount')::INT8)::INT8 as amount \
+            from order_items where product_id = any($1) group by 1",
+        )
+        .bind(&product_ids)
+        .fetch_all(common::postgres().await)
+        .await?
+        .iter()
+        .map(|row| (row.get::<i64, _>("sku_id"), row.get::<i64, _>("amount")))
+        .collect::<HashMap<i64, i64>>();
+
+        // 在途: 已下单未收货的数量
+        let on_order = sqlx::query(
+            "select l.sku_id, sum(l.quantity - l.received)::INT8 as amount from purchase_order_lines as l \
+            inner join purchase_orders as o on l.purchase_order_id = o.id \
+            where o.status = any($1) and l.product_id = any($2) group by l.sku_id",
+        )
+        .bind(vec![
+            PurchaseStatus::Ordered as i16,
+            PurchaseStatus::PartReceived as i16,
+        ])
+        .bind(&product_ids)
+        .fetch_all(common::postgres().await)
+        .await?
+        .iter()
+        .map(|row| (row.get::<i64, _>("sku_id"), row.get::<i64, _>("amount")))
+        .collect::<HashMap<i64, i64>>();
+
+        let skus = sqlx::query(
+            "select id,product_id,title,stock from product_skus where product_id = any($1) order by id asc",
+        )
+        .bind(&product_ids)
+        .fetch_all(common::postgres().await)
+        .await?;
+        let mut product_sold: HashMap<i64, (i64, i64)> = HashMap::new();
+        for sku in skus.iter() {
+            let entry = product_sold
+                .entry(sku.get::<i64, _>("product_id"))
+                .or_default();
+            entry.0 += sold
+                .get(&sku.get::<i64, _>("id"))
+                .cloned()
+                .unwrap_or_default();
+            entry.1 += 1;
+        }
+
+        let mut result = vec![];
+        for sku in skus.iter() {
+            let sku_id = sku.get::<i64, _>("id");
+            let product_id = sku.get::<i64, _>("product_id");
+            let (title, velocity) = &products[&product_id];
+            let (total_sold, sku_count) = product_sold[&product_id];
+            let share = match total_sold {
+                0 => 1.0 / sku_count as f64,
+                _ => sold.get(&sku_id).cloned().unwrap_or_default() as f64 / total_sold as f64,
+            };
+            let stock = sku.get::<i32, _>("stock") as i64;
+            let incoming = on_order.get(&sku_id).cloned().unwrap_or_default();
+            let quantity =
+                reorder::suggest(velocity * share, stock, incoming, lead_days, cover_days);
+            if quantity > 0 {
+                result.push(json!({
+                    "product_id": product_id,
+                    "product_title": title,
+                    "sku_id": sku_id,
+                    "sku_title": sku.get::<String, _>("title"),
+                    "stock": stock,
+                    "on_order": incoming,
+                    "daily_sales": velocity * share,
+                    "suggest_quantity": quantity,
+                }));
+            }
+        }
+
+        Ok(result)
+    }
+}
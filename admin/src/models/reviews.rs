@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::Json;
+use sqlx::{Postgres, Row, Transaction};
+
+use common::error::{ApiError, ApiResult};
+use common::Pagination;
+
+use crate::models::order_items::OrderItems;
+use crate::models::products::Product;
+use crate::models::user::Admin;
+
+/// 评分统计缓存时间: 1天, 审核后主动刷新
+const RATING_CACHE_TTL: usize = 24 * 3600;
+
+/// 审核状态
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, sqlx::Type)]
+pub enum ReviewStatus {
+    // 待审核
+    Pending = 0,
+    // 已通过
+    Approved = 1,
+    // 已隐藏
+    Hidden = 2,
+}
+
+impl Default for ReviewStatus {
+    fn default() -> Self {
+        Self::Pending
+    }
+}
+
+impl TryFrom<&str> for ReviewStatus {
+    type Error = ApiError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "pending" => Ok(Self::Pending),
+            "approved" => Ok(Self::Approved),
+            "hidden" => Ok(Self::Hidden),
+            _ => Err(ApiError::Error("审核状态错误".to_string())),
+        }
+    }
+}
+
+/// 商品评价, parent_id 不为0时为追评
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProductReview {
+    pub id: i64,
+    pub parent_id: i64,
+    pub order_id: i64,
+    pub order_item_id: i64,
+    pub product_id: i64,
+    pub user_id: i64,
+    // 评分: 1-10, 追评为0
+    pub score: i16,
+    pub content: String,
+    pub photos: Json<Vec<String>>,
+    pub anonymous: bool,
+    pub status: ReviewStatus,
+    // 商家回复
+    pub reply: Option<String>,
+    pub replied_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// 商品评分统计
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RatingSummary {
+    // 1-10分的评价数
+    pub histogram: [i64; 10],
+    pub total: i64,
+    pub average: f64,
+}
+
+impl RatingSummary {
+    fn from_counts(counts: &[(i16, i64)]) -> Self {
+        let mut summary = RatingSummary::default();
+        let mut score_total = 0i64;
+        for &(score, count) in counts.iter() {
+            if (1..=10).contains(&score) {
+                summary.histogram[score as usize - 1] += count;
+                summary.total += count;
+                score_total += score as i64 * count;
+            }
+        }
+        if summary.total > 0 {
+            summary.average = (score_total as f64 / summary.total as f64 * 100.0).round() / 100.0;
+        }
+
+        summary
+    }
+}
+
+impl ProductReview {
+    /// 发表评价, 审核通过后展示
+    pub async fn create(
+        item: &OrderItems,
+        user_id: i64,
+        score: u8,
+        content: String,
+        photos: Vec<String>,
+        anonymous: bool,
+    ) -> ApiResult<i64> {
+        let mut tx = common::postgres().await.begin().await?;
+
+        let owner =
+            sqlx::query("select exists (select id from orders where id = $1 and user_id = $2)")
+                .bind(item.order_id)
+                .bind(user_id)
+                .fetch_one(&mut tx)
+                .await?
+                .get::<bool, _>("exists");
+        if !owner {
+            return Err(ApiError::Error("订单不存在".to_string()));
+        }
+
+        let exists = sqlx::query(
+            "select exists (select id from product_reviews where order_item_id = $1 and parent_id = 0)",
+        )
+        .bind(item.id)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<bool, _>("exists");
+        if exists {
+            return Err(ApiError::Error("该商品已评价".to_string()));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let id = sqlx::query(
+            "insert into product_reviews (parent_id, order_id, order_item_id, product_id, user_id, score, \
+            content, photos, anonymous, status, created_at, updated_at) \
+            values (0, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10) RETURNING id",
+        )
+        .bind(item.order_id)
+        .bind(item.id)
+        .bind(item.product_id)
+        .bind(user_id)
+        .bind(score as i16)
+        .bind(&content)
+        .bind(Json(photos))
+        .bind(anonymous)
+        .bind(ReviewStatus::Pending)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+
+        sqlx::query(
+            "update order_items set rating = $1, review = $2, reviewed_at = $3 where id = $4",
+        )
+        .bind(score as i16)
+        .bind(&content)
+        .bind(now)
+        .bind(item.id)
+        .execute(&mut tx)
+        .await?;
+
+        // 订单中的商品全部评价后, 订单标记为已评价
+        let total = sqlx::query(
+            "select count(*) as total from order_items where order_id = $1 and rating = 0",
+        )
+        .bind(item.order_id)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("total");
+        if total == 0 {
+            sqlx::query("update orders set reviewed = true where id = $1 and user_id = $2")
+                .bind(item.order_id)
+                .bind(user_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from product_reviews where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("评价不存在".to_string()))
+    }
+
+    /// 追评, 每条评价只能追评一次
+    pub async fn follow_up(
+        &self,
+        user_id: i64,
+        content: String,
+        photos: Vec<String>,
+    ) -> ApiResult<i64> {
+        if self.user_id != user_id || self.parent_id != 0 {
+            return Err(ApiError::Error("评价不存在".to_string()));
+        }
+
+        let exists =
+            sqlx::query("select exists (select id from product_reviews where parent_id = $1)")
+                .bind(self.id)
+                .fetch_one(common::postgres().await)
+                .await?
+                .get::<bool, _>("exists");
+        if exists {
+            return Err(ApiError::Error("已追评, 不能重复追评".to_string()));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        Ok(sqlx::query(
+            "insert into product_reviews (parent_id, order_id, order_item_id, product_id, user_id, score, \
+            content, photos, anonymous, status, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5, 0, $6, $7, $8, $9, $10, $10) RETURNING id",
+        )
+        .bind(self.id)
+        .bind(self.order_id)
+        .bind(self.order_item_id)
+        .bind(self.product_id)
+        .bind(user_id)
+        .bind(content)
+        .bind(Json(photos))
+        .bind(self.anonymous)
+        .bind(ReviewStatus::Pending)
+        .bind(now)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("id"))
+    }
+
+    /// 商家回复
+    pub async fn reply(&self, content: String) -> ApiResult<bool> {
+        Ok(sqlx::query(
+            "update product_reviews set reply = $1, replied_at = $2, updated_at = $2 where id = $3",
+        )
+        .bind(content)
+        .bind(chrono::Local::now().naive_local())
+        .bind(self.id)
+        .execute(common::postgres().await)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    /// 审核, 状态变化后刷新商品评分统计
+    pub async fn moderate(&self, status: ReviewStatus) -> ApiResult<bool> {
+        let mut tx = common::postgres().await.begin().await?;
+        let rows =
+            sqlx::query("update product_reviews set status = $1, updated_at = $2 where id = $3")
+                .bind(status)
+                .bind(chrono::Local::now().naive_local())
+                .bind(self.id)
+                .execute(&mut tx)
+                .await?
+                .rows_affected();
+
+        let summary = Self::calc_summary(self.product_id, &mut tx).await?;
+        sqlx::query("update products set review_count = $1, rating = $2 where id = $3")
+            .bind(summary.total as i32)
+            .bind(summary.average.round() as i64)
+            .bind(self.product_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        common::redis::set_ex(
+            &Self::summary_key(self.product_id),
+            &serde_json::to_string(&summary)?,
+            RATING_CACHE_TTL,
+        )
+        .await?;
+
+        Ok(rows > 0)
+    }
+
+    fn summary_key(product_id: i64) -> String {
+        format!("product_rating:{}", product_id)
+    }
+
+    async fn calc_summary(
+        product_id: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<RatingSummary> {
+        let counts = sqlx::query(
+            "select score, count(*) as total from product_reviews \
+            where product_id = $1 and parent_id = 0 and status = $2 group by score",
+        )
+        .bind(product_id)
+        .bind(ReviewStatus::Approved)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| (row.get::<i16, _>("score"), row.get::<i64, _>("total")))
+        .collect::<Vec<(i16, i64)>>();
+
+        Ok(RatingSummary::from_counts(&counts))
+    }
+
+    /// 商品评分统计, 优先读取缓存
+    pub async fn summary(product_id: i64) -> ApiResult<RatingSummary> {
+        let key = Self::summary_key(product_id);
+        if let Some(value) = common::redis::get(&key).await? {
+            if let Ok(summary) = serde_json::from_str::<RatingSummary>(&value) {
+                return Ok(summary);
+            }
+        }
+
+        let mut tx = common::postgres().await.begin().await?;
+        let summary = Self::calc_summary(product_id, &mut tx).await?;
+        tx.commit().await?;
+        common::redis::set_ex(&key, &serde_json::to_string(&summary)?, RATING_CACHE_TTL).await?;
+
+        Ok(summary)
+    }
+
+    /// 商品已审核通过的评价, 包含追评及商家回复
+    pub async fn index(
+        product_id: i64,
+        pagination: &mut Pagination<HashMap<String, serde_json::Value>>,
+    ) -> ApiResult<()> {
+        let reviews: Vec<Self> = sqlx::query_as(
+            "select * from product_reviews where product_id = $1 and parent_id = 0 and status = $2 \
+            order by id desc limit $3 offset $4",
+        )
+        .bind(product_id)
+        .bind(ReviewStatus::Approved)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query(
+            "select count(*) as total from product_reviews where product_id = $1 and parent_id = 0 and status = $2",
+        )
+        .bind(product_id)
+        .bind(ReviewStatus::Approved)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("total");
+
+        let ids = reviews.iter().map(|review| review.id).collect::<Vec<i64>>();
+        let follow_ups: HashMap<i64, Self> = sqlx::query_as::<_, Self>(
+            "select * from product_reviews where parent_id = any($1) and status = $2",
+        )
+        .bind(&ids)
+        .bind(ReviewStatus::Approved)
+        .fetch_all(common::postgres().await)
+        .await?
+        .into_iter()
+        .map(|review| (review.parent_id, review))
+        .collect();
+
+        let user_ids = reviews
+            .iter()
+            .map(|review| review.user_id)
+            .collect::<Vec<i64>>();
+        let users = Admin::user_maps(user_ids).await?;
+        let title = Product::product_maps(vec![product_id])
+            .await?
+            .get(&product_id)
+            .and_then(|product| product.get("title").cloned())
+            .unwrap_or_default();
+
+        let result = reviews
+            .iter()
+            .map(|review| {
+                let nickname = match (review.anonymous, users.get(&review.user_id)) {
+                    (false, Some(user)) => user.nickname.clone(),
+                    _ => "匿名用户".to_string(),
+                };
+                let follow_up = follow_ups.get(&review.id).map(|item| {
+                    json!({
+                        "content": item.content,
+                        "photos": item.photos,
+                        "reply": item.reply,
+                        "created_at": common::time_ymd_his(item.created_at),
+                    })
+                });
+
+                HashMap::from([
+                    ("id".to_string(), json!(review.id)),
+                    ("product_id".to_string(), json!(review.product_id)),
+                    ("title".to_string(), title.clone()),
+                    ("nickname".to_string(), json!(nickname)),
+                    ("rating".to_string(), json!(review.score)),
+                    ("content".to_string(), json!(review.content)),
+                    ("photos".to_string(), json!(review.photos)),
+                    ("reply".to_string(), json!(review.reply)),
+                    ("follow_up".to_string(), json!(follow_up)),
+                    (
+                        "reviewed_at".to_string(),
+                        json!(common::time_ymd_his(review.created_at)),
+                    ),
+                ])
+            })
+            .collect::<Vec<HashMap<String, serde_json::Value>>>();
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result);
+
+        Ok(())
+    }
+
+    /// 后台评价列表, 按审核状态筛选
+    pub async fn moderation_list(
+        status: ReviewStatus,
+        pagination: &mut Pagination<Self>,
+    ) -> ApiResult<()> {
+        let result: Vec<Self> = sqlx::query_as(
+            "select * from product_reviews where status = $1 order by id asc limit $2 offset $3",
+        )
+        .bind(status)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query("select count(*) as total from product_reviews where status = $1")
+            .bind(status)
+            .fetch_one(common::postgres().await)
+            .await?
+            .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(result);
+
+        Ok(())
+    }
+}
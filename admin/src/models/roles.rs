@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+
+use casbin::prelude::*;
+use serde_json::json;
+use sqlx::Row;
+
+use common::auth::ReqRole;
+use common::casbin::SUPER_ADMIN;
+use common::error::{ApiError, ApiResult};
+
+use crate::models::permissions::Permission;
+
+/// 角色, 权限及继承关系保存在 casbin 规则中
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Role {
+    pub id: i64,
+    // casbin 中的角色标识
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Role {
+    pub async fn index() -> ApiResult<Vec<Self>> {
+        Ok(sqlx::query_as("select * from roles order by id")
+            .fetch_all(common::postgres().await)
+            .await?)
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from roles where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("角色不存在".to_string()))
+    }
+
+    /// 角色标识只能包含小写字母、数字及下划线, 且不能与用户标识冲突
+    fn check_name(name: &str) -> ApiResult<()> {
+        let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            && name != SUPER_ADMIN;
+        if !valid {
+            return Err(ApiError::Error(
+                "角色标识只能包含小写字母、数字及下划线, 且以字母开头".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn create(payload: ReqRole, domain: &str) -> ApiResult<Self> {
+        let name = payload
+            .name
+            .clone()
+            .ok_or(ApiError::Error("角色标识不能为空".to_string()))?;
+        Self::check_name(&name)?;
+        let exists = sqlx::query("select count(*) as total from roles where name = $1")
+            .bind(&name)
+            .fetch_one(common::postgres().await)
+            .await?
+            .get::<i64, _>("total");
+        if exists > 0 {
+            return Err(ApiError::Error("角色标识已存在".to_string()));
+        }
+        // 先校验继承的角色, 校验失败时不创建角色
+        if let Some(inherits) = payload.inherits.clone() {
+            Self::check_inherits(&name, inherits, domain).await?;
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let mut tx = common::postgres().await.begin().await?;
+        let role: Self = sqlx::query_as(
+            "insert into roles (name, display_name, description, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5) RETURNING *",
+        )
+        .bind(&name)
+        .bind(payload.display_name.unwrap())
+        .bind(payload.description.unwrap_or_default())
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?;
+
+        if let Some(inherits) = payload.inherits {
+            role.set_inherits(inherits, domain).await?;
+        }
+        tx.commit().await?;
+
+        Ok(role)
+    }
+
+    /// 修改名称、描述及继承的角色, 角色标识不能修改
+    pub async fn update(id: i64, payload: ReqRole, domain: &str) -> ApiResult<Self> {
+        let role: Self = sqlx::query_as(
+            "update roles set display_name = $1, description = $2, updated_at = $3 where id = $4 RETURNING *",
+        )
+        .bind(payload.display_name.unwrap())
+        .bind(payload.description.unwrap_or_default())
+        .bind(chrono::Local::now().naive_local())
+        .bind(id)
+        .fetch_optional(common::postgres().await)
+        .await?
+        .ok_or(ApiError::Error("角色不存在".to_string()))?;
+
+        if let Some(inherits) = payload.inherits {
+            role.set_inherits(inherits, domain).await?;
+        }
+
+        Ok(role)
+    }
+
+    /// 删除角色, 同时删除用户的角色分配、角色权限及继承关系
+    ///
+    /// casbin 规则删除失败时角色不删除, 重新删除即可清理剩余的规则
+    pub async fn delete(id: i64) -> ApiResult<()> {
+        let role = Self::get(id).await?;
+        let mut tx = common::postgres().await.begin().await?;
+        sqlx::query("delete from roles where id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        enforcer
+            .remove_filtered_grouping_policy(0, vec![role.name.clone()])
+            .await?;
+        enforcer
+            .remove_filtered_grouping_policy(1, vec![role.name.clone()])
+            .await?;
+        enforcer
+            .remove_filtered_policy(0, vec![role.name.clone()])
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// 直接继承的角色
+    pub async fn inherits(&self, domain: &str) -> ApiResult<Vec<String>> {
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let enforcer = enforcer.write().await;
+
+        Ok(enforcer
+            .get_filtered_grouping_policy(0, vec![self.name.clone()])
+            .into_iter()
+            .filter(|rule| rule.len() >= 3 && rule[2] == domain)
+            .map(|rule| rule[1].clone())
+            .collect())
+    }
+
+    /// 校验继承的角色存在且不会形成循环继承
+    async fn check_inherits(
+        name: &str,
+        inherits: Vec<String>,
+        domain: &str,
+    ) -> ApiResult<HashSet<String>> {
+        let inherits = inherits
+            .into_iter()
+            .filter(|parent| !parent.is_empty())
+            .collect::<HashSet<String>>();
+        if !inherits.is_empty() {
+            let names = inherits.iter().cloned().collect::<Vec<String>>();
+            let total = sqlx::query("select count(*) as total from roles where name = any($1)")
+                .bind(&names)
+                .fetch_one(common::postgres().await)
+                .await?
+                .get::<i64, _>("total");
+            if total as usize != names.len() {
+                return Err(ApiError::Error("继承的角色不存在".to_string()));
+            }
+        }
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        for parent in inherits.iter() {
+            let ancestors = enforcer.get_implicit_roles_for_user(parent, Some(domain));
+            if parent == name || ancestors.iter().any(|ancestor| ancestor == name) {
+                return Err(ApiError::Error(format!(
+                    "不能继承角色 {}, 会形成循环继承",
+                    parent
+                )));
+            }
+        }
+
+        Ok(inherits)
+    }
+
+    /// 设置继承的角色, 会覆盖原有继承关系, 不能形成循环继承
+    pub async fn set_inherits(&self, inherits: Vec<String>, domain: &str) -> ApiResult<()> {
+        let inherits = Self::check_inherits(&self.name, inherits, domain).await?;
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        let current = enforcer
+            .get_filtered_grouping_policy(0, vec![self.name.clone()])
+            .into_iter()
+            .filter(|rule| rule.len() >= 3 && rule[2] == domain)
+            .collect::<Vec<Vec<String>>>();
+        if !current.is_empty() {
+            enforcer.remove_grouping_policies(current).await?;
+        }
+        if !inherits.is_empty() {
+            enforcer
+                .add_grouping_policies(
+                    inherits
+                        .into_iter()
+                        .map(|parent| vec![self.name.clone(), parent, domain.to_string()])
+                        .collect(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// 直接拥有该角色的用户
+    pub async fn users(&self, domain: &str) -> ApiResult<Vec<serde_json::Value>> {
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let user_ids = enforcer
+            .write()
+            .await
+            .get_users_for_role(&self.name, Some(domain))
+            .iter()
+            .filter_map(|subject| subject.strip_prefix("user:"))
+            .filter_map(|id| id.parse::<i64>().ok())
+            .collect::<Vec<i64>>();
+
+        Ok(
+            sqlx::query("select id, name, email, phone from users where id = any($1) order by id")
+                .bind(user_ids)
+                .fetch_all(common::postgres().await)
+                .await?
+                .iter()
+                .map(|row| {
+                    json!({
+                        "id": row.get::<i64, _>("id"),
+                        "name": row.get::<String, _>("name"),
+                        "email": row.get::<String, _>("email"),
+                        "phone": row.get::<String, _>("phone"),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// 角色直接分配的权限
+    pub async fn permissions(&self, domain: &str) -> ApiResult<Vec<Permission>> {
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let rules = enforcer
+            .write()
+            .await
+            .get_permissions_for_user(&self.name, Some(domain));
+        let catalog = Permission::index().await?;
+
+        Ok(Permission::matched(&catalog, &rules)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    /// 设置角色权限, 会覆盖原有权限
+    pub async fn set_permissions(&self, permission_ids: &[i64], domain: &str) -> ApiResult<()> {
+        let permissions = Permission::get_many(permission_ids).await?;
+        if permissions.len() != permission_ids.iter().collect::<HashSet<_>>().len() {
+            return Err(ApiError::Error("权限不存在".to_string()));
+        }
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        enforcer
+            .remove_filtered_policy(0, vec![self.name.clone(), domain.to_string()])
+            .await?;
+        if !permissions.is_empty() {
+            enforcer
+                .add_policies(
+                    permissions
+                        .into_iter()
+                        .map(|permission| {
+                            vec![
+                                self.name.clone(),
+                                domain.to_string(),
+                                permission.path,
+                                permission.method,
+                            ]
+                        })
+                        .collect(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "display_name": self.display_name,
+            "description": self.description,
+            "created_at": common::time_ymd_his(self.created_at),
+            "updated_at": common::time_ymd_his(self.updated_at),
+        })
+    }
+}
+
+/// 用户最终拥有的角色及权限, 包括继承的角色
+pub struct EffectivePermissions;
+
+impl EffectivePermissions {
+    /// 超级管理员拥有全部权限
+    pub async fn for_user(user_id: i64, domain: &str) -> ApiResult<serde_json::Value> {
+        let subject = common::casbin::subject(user_id);
+        let catalog = Permission::index().await?;
+        if subject == SUPER_ADMIN {
+            return Ok(json!({
+                "super_admin": true,
+                "roles": [],
+                "permissions": catalog.iter().map(|p| p.to_json()).collect::<Vec<_>>(),
+                "rules": [],
+            }));
+        }
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let mut enforcer = enforcer.write().await;
+        let roles = enforcer.get_implicit_roles_for_user(&subject, Some(domain));
+        let rules = enforcer.get_implicit_permissions_for_user(&subject, Some(domain));
+        drop(enforcer);
+
+        let permissions = Permission::matched(&catalog, &rules);
+        // 未登记到权限目录的规则
+        let others = rules
+            .iter()
+            .filter(|rule| rule.len() >= 4)
+            .filter(|rule| {
+                !permissions
+                    .iter()
+                    .any(|p| p.path == rule[2] && p.method == rule[3])
+            })
+            .map(|rule| json!({ "role": rule[0], "path": rule[2], "method": rule[3] }))
+            .collect::<Vec<serde_json::Value>>();
+
+        Ok(json!({
+            "super_admin": false,
+            "roles": roles,
+            "permissions": permissions.iter().map(|p| p.to_json()).collect::<Vec<_>>(),
+            "rules": others,
+        }))
+    }
+
+    /// 用户拥有的权限id, 超级管理员返回 None
+    pub async fn permission_ids(user_id: i64, domain: &str) -> ApiResult<Option<HashSet<i64>>> {
+        let subject = common::casbin::subject(user_id);
+        if subject == SUPER_ADMIN {
+            return Ok(None);
+        }
+
+        let enforcer = common::casbin::casbin_layer().await.get_enforcer().clone();
+        let rules = enforcer
+            .write()
+            .await
+            .get_implicit_permissions_for_user(&subject, Some(domain));
+        let catalog = Permission::index().await?;
+
+        Ok(Some(
+            Permission::matched(&catalog, &rules)
+                .iter()
+                .map(|p| p.id)
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use common::error::{ApiError, ApiResult};
+use common::jwt::{Claims, UserSource};
+use common::session::{check_owner, check_refresh, RefreshCheck};
+
+/// 活跃时间刷新间隔(秒), 避免每次请求都写redis
+const TOUCH_INTERVAL: i64 = 60;
+
+/// 会话中的 refresh_token 未变化时才写入, 返回 1 写入成功, 0 已被刷新, -1 会话不存在
+const SAVE_IF_UNCHANGED: &str = r#"
+local value = redis.call('get', KEYS[1])
+if not value then
+    return -1
+end
+if cjson.decode(value)['refresh_jti'] ~= ARGV[1] then
+    return 0
+end
+redis.call('set', KEYS[1], ARGV[2], 'EX', ARGV[3])
+return 1
+"#;
+
+/// 登录会话, 一次登录及其后续刷新的token属于同一个会话
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub sid: String,
+    pub user_id: i64,
+    // 设备
+    pub from: UserSource,
+    pub ip: String,
+    pub user_agent: String,
+    // 当前有效的 refresh_token
+    refresh_jti: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_active_at: chrono::NaiveDateTime,
+}
+
+impl Session {
+    fn key(sid: &str) -> String {
+        format!("session:{}", sid)
+    }
+
+    fn user_key(user_id: i64) -> String {
+        format!("user_sessions:{}", user_id)
+    }
+
+    /// 创建会话并签发token
+    pub async fn create(
+        claims: &mut Claims,
+        ip: String,
+        user_agent: String,
+    ) -> ApiResult<(String, String)> {
+        let now = chrono::Local::now().naive_local();
+        claims.sid = common::get_random_str(32);
+        let mut session = Session {
+            sid: claims.sid.clone(),
+            user_id: claims.id,
+            from: claims.from.clone(),
+            ip,
+            user_agent,
+            refresh_jti: "".to_string(),
+            created_at: now,
+            last_active_at: now,
+        };
+
+        let tokens = common::jwt::jwt().await.token_info(claims)?;
+        session.refresh_jti = claims.jti();
+        session.save().await?;
+
+        Ok(tokens)
+    }
+
+    async fn save(&self) -> ApiResult<()> {
+        let ttl = common::jwt::jwt().await.refresh_exp as usize;
+        common::redis::set_ex(&Self::key(&self.sid), &serde_json::to_string(self)?, ttl).await?;
+        common::redis::sadd_ex(&Self::user_key(self.user_id), &self.sid, ttl).await
+    }
+
+    // 读取、比较、写入在同一个lua脚本中执行, 避免并发刷新时同一个refresh_token被使用两次
+    async fn save_if_unchanged(&self, refresh_jti: &str) -> ApiResult<i64> {
+        let ttl = common::jwt::jwt().await.refresh_exp as usize;
+        let result = common::redis::eval::<i64>(
+            SAVE_IF_UNCHANGED,
+            &[&Self::key(&self.sid)],
+            &[
+                refresh_jti.to_string(),
+                serde_json::to_string(self)?,
+                ttl.to_string(),
+            ],
+        )
+        .await?;
+        if result == 1 {
+            common::redis::sadd_ex(&Self::user_key(self.user_id), &self.sid, ttl).await?;
+        }
+
+        Ok(result)
+    }
+
+    pub async fn get(sid: &str) -> ApiResult<Option<Self>> {
+        if sid.is_empty() {
+            return Ok(None);
+        }
+
+        match common::redis::get(&Self::key(sid)).await? {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 校验access_token所属会话, 并记录活跃时间
+    pub async fn check(claims: &Claims) -> ApiResult<()> {
+        let mut session = Self::get(&claims.sid)
+            .await?
+            .filter(|session| session.user_id == claims.id)
+            .ok_or(ApiError::Error("登录已失效, 请重新登录".to_string()))?;
+
+        let now = chrono::Local::now().naive_local();
+        if (now - session.last_active_at).num_seconds() >= TOUCH_INTERVAL {
+            session.last_active_at = now;
+            // 期间已刷新token时不覆盖新的会话
+            let refresh_jti = session.refresh_jti.clone();
+            if session.save_if_unchanged(&refresh_jti).await? < 0 {
+                return Err(ApiError::Error("登录已失效, 请重新登录".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 刷新token, refresh_token 只能使用一次, 旧token重复使用时注销整个会话
+    pub async fn refresh(claims: &mut Claims) -> ApiResult<(String, String)> {
+        let session = Self::get(&claims.sid).await?;
+        let check = check_refresh(
+            session
+                .as_ref()
+                .map(|session| (session.user_id, session.refresh_jti.as_str())),
+            claims.id,
+            &claims.jti(),
+        );
+        let mut session = match (check, session) {
+            (RefreshCheck::Valid, Some(session)) => session,
+            (RefreshCheck::Reused, Some(session)) => return Self::reused(&session).await,
+            _ => return Err(ApiError::Error("登录已失效, 请重新登录".to_string())),
+        };
+
+        let refresh_jti = session.refresh_jti.clone();
+        let tokens = common::jwt::jwt().await.token_info(claims)?;
+        session.refresh_jti = claims.jti();
+        session.last_active_at = chrono::Local::now().naive_local();
+        match session.save_if_unchanged(&refresh_jti).await? {
+            1 => Ok(tokens),
+            // 并发请求已使用该refresh_token
+            0 => Self::reused(&session).await,
+            _ => Err(ApiError::Error("登录已失效, 请重新登录".to_string())),
+        }
+    }
+
+    async fn reused(session: &Session) -> ApiResult<(String, String)> {
+        Self::revoke(session.user_id, &session.sid).await?;
+
+        Err(ApiError::Error(
+            "refresh_token 已被使用, 请重新登录".to_string(),
+        ))
+    }
+
+    /// 注销会话, 只能注销自己的会话
+    pub async fn revoke(user_id: i64, sid: &str) -> ApiResult<()> {
+        let session = Self::get(sid).await?;
+        check_owner(session.map(|session| session.user_id), user_id)?;
+
+        common::redis::del(&Self::key(sid)).await?;
+        common::redis::srem(&Self::user_key(user_id), &[sid.to_string()]).await?;
+
+        Ok(())
+    }
+
+    /// 注销用户全部会话
+    pub async fn revoke_all(user_id: i64) -> ApiResult<usize> {
+        let sids = common::redis::smembers(&Self::user_key(user_id)).await?;
+        for sid in sids.iter() {
+            common::redis::del(&Self::key(sid)).await?;
+        }
+        common::redis::del(&Self::user_key(user_id)).await?;
+
+        Ok(sids.len())
+    }
+
+    pub fn to_json(&self, current_sid: &str) -> serde_json::Value {
+        json!({
+            "sid": self.sid,
+            "from": self.from,
+            "ip": self.ip,
+            "user_agent": self.user_agent,
+            "current": self.sid == current_sid,
+            "created_at": common::time_ymd_his(self.created_at),
+            "last_active_at": common::time_ymd_his(self.last_active_at),
+        })
+    }
+
+    /// 用户会话列表, 按最后活跃时间倒序
+    pub async fn list(user_id: i64) -> ApiResult<Vec<Self>> {
+        let mut sessions = vec![];
+        let mut expired = vec![];
+        for sid in common::redis::smembers(&Self::user_key(user_id)).await? {
+            match Self::get(&sid).await? {
+                Some(session) => sessions.push(session),
+                None => expired.push(sid),
+            }
+        }
+        // 清理已过期的会话
+        common::redis::srem(&Self::user_key(user_id), &expired).await?;
+
+        sessions.sort_by(|a, b| b.last_active_at.cmp(&a.last_active_at));
+        Ok(sessions)
+    }
+}
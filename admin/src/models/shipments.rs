@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Postgres, QueryBuilder, Row, Transaction};
+use tracing::error;
+
+use common::carrier::{self, TrackingStatus};
+use common::error::{ApiError, ApiResult};
+
+use crate::models::orders::Orders;
+use crate::models::LogisticStatus;
+
+/// 包裹, 一个订单可拆分为多个包裹发货
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Shipment {
+    pub id: i64,
+    pub order_id: i64,
+    // 物流公司名称
+    pub company: String,
+    // 物流公司编码, 用于查询物流轨迹
+    pub carrier: String,
+    pub tracking_no: String,
+    pub status: TrackingStatus,
+    pub shipped_at: chrono::NaiveDateTime,
+    pub delivered_at: Option<chrono::NaiveDateTime>,
+    // 最近一次查询物流轨迹的时间
+    pub polled_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// 物流轨迹
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ShipmentEvent {
+    pub id: i64,
+    pub shipment_id: i64,
+    pub status: TrackingStatus,
+    pub description: String,
+    pub location: String,
+    pub occurred_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl Shipment {
+    /// 创建包裹, item_ids 为空时包含所有未发货的商品
+    pub async fn create(
+        order: &Orders,
+        company: String,
+        carrier: String,
+        tracking_no: String,
+        item_ids: Vec<i64>,
+    ) -> ApiResult<i64> {
+        let mut tx = common::postgres().await.begin().await?;
+        // 锁定订单, 避免同一商品被重复分配到多个包裹
+        sqlx::query("select id from orders where id = $1 for update")
+            .bind(order.id)
+            .execute(&mut tx)
+            .await?;
+
+        let all_ids = sqlx::query("select id from order_items where order_id = $1")
+            .bind(order.id)
+            .fetch_all(&mut tx)
+            .await?
+            .iter()
+            .map(|row| row.get::<i64, _>("id"))
+            .collect::<HashSet<i64>>();
+        let assigned = sqlx::query("select order_item_id from shipment_items where order_id = $1")
+            .bind(order.id)
+            .fetch_all(&mut tx)
+            .await?
+            .iter()
+            .map(|row| row.get::<i64, _>("order_item_id"))
+            .collect::<HashSet<i64>>();
+
+        let item_ids = if item_ids.is_empty() {
+            all_ids.difference(&assigned).cloned().collect::<Vec<i64>>()
+        } else {
+            let mut ids = item_ids;
+            ids.sort();
+            ids.dedup();
+            if let Some(id) = ids
+                .iter()
+                .find(|id| !all_ids.contains(id) || assigned.contains(id))
+            {
+                return Err(ApiError::Error(format!("订单商品 {} 不存在或已发货", id)));
+            }
+            ids
+        };
+        if item_ids.is_empty() {
+            return Err(ApiError::Error("没有待发货的商品".to_string()));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let id = sqlx::query(
+            "insert into shipments (order_id, company, carrier, tracking_no, status, shipped_at, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5, $6, $6, $6) RETURNING id",
+        )
+        .bind(order.id)
+        .bind(&company)
+        .bind(&carrier)
+        .bind(&tracking_no)
+        .bind(TrackingStatus::Collected)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+
+        let mut query_build: QueryBuilder<Postgres> =
+            QueryBuilder::new("insert into shipment_items (shipment_id, order_id, order_item_id) ");
+        query_build.push_values(item_ids.iter(), |mut b, item_id| {
+            b.push_bind(id).push_bind(order.id).push_bind(*item_id);
+        });
+        query_build.build().execute(&mut tx).await?;
+
+        // 全部商品发货后订单变为待收货
+        let ship_status = if assigned.len() + item_ids.len() >= all_ids.len() {
+            LogisticStatus::ToBeReceived
+        } else {
+            LogisticStatus::PartShipped
+        };
+        let mut ship_data = order.ship_data.0.clone();
+        ship_data.push(HashMap::from([
+            ("shipment_id".to_string(), json!(id)),
+            ("express_no".to_string(), json!(tracking_no)),
+            ("company".to_string(), json!(company)),
+        ]));
+        sqlx::query(
+            "update orders set ship_status = $1, ship_data = $2, updated_at = $3 where id = $4",
+        )
+        .bind::<i8>(ship_status.into())
+        .bind(json!(ship_data))
+        .bind(now)
+        .bind(order.id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// 售后换货包裹, 不关联订单商品, 不影响订单发货状态
+    pub async fn create_replacement(
+        order_id: i64,
+        company: String,
+        carrier: String,
+        tracking_no: String,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<i64> {
+        let now = chrono::Local::now().naive_local();
+        Ok(sqlx::query(
+            "insert into shipments (order_id, company, carrier, tracking_no, status, shipped_at, created_at, updated_at) \
+            values ($1, $2, $3, $4, $5, $6, $6, $6) RETURNING id",
+        )
+        .bind(order_id)
+        .bind(company)
+        .bind(carrier)
+        .bind(tracking_no)
+        .bind(TrackingStatus::Collected)
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await?
+        .get::<i64, _>("id"))
+    }
+
+    /// 查询未签收包裹的物流轨迹, 每个包裹30分钟内只查询一次
+    pub async fn poll_due() -> ApiResult<u64> {
+        let shipments: Vec<Self> = sqlx::query_as(
+            "select * from shipments where status != $1 \
+            and (polled_at is null or polled_at <= now() - interval '30 minutes') order by id asc limit 100",
+        )
+        .bind(TrackingStatus::Delivered)
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let mut counter = 0u64;
+        for shipment in shipments.iter() {
+            match shipment.refresh().await {
+                Ok(true) => counter += 1,
+                Ok(false) => {}
+                Err(e) => error!("包裹 {} 物流轨迹查询失败: {}", shipment.id, e),
+            }
+        }
+
+        Ok(counter)
+    }
+
+    /// 同步物流轨迹, 返回状态是否有变化
+    pub async fn refresh(&self) -> ApiResult<bool> {
+        let now = chrono::Local::now().naive_local();
+        let events = match carrier::carrier(&self.carrier) {
+            Ok(adapter) => adapter.track(&self.tracking_no).await?,
+            Err(_) => vec![],
+        };
+
+        let mut tx = common::postgres().await.begin().await?;
+        for event in events.iter() {
+            sqlx::query(
+                "insert into shipment_events (shipment_id, status, description, location, occurred_at, created_at) \
+                select $1, $2, $3, $4, $5, $6 where not exists \
+                (select id from shipment_events where shipment_id = $1 and occurred_at = $5 and description = $3)",
+            )
+            .bind(self.id)
+            .bind(event.status)
+            .bind(&event.description)
+            .bind(&event.location)
+            .bind(event.occurred_at)
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        let status = carrier::latest_status(&events).unwrap_or(self.status);
+        let delivered_at = match status {
+            TrackingStatus::Delivered => events
+                .iter()
+                .filter(|event| event.status == TrackingStatus::Delivered)
+                .map(|event| event.occurred_at)
+                .max(),
+            _ => None,
+        };
+        sqlx::query(
+            "update shipments set status = $1, delivered_at = $2, polled_at = $3, updated_at = $3 where id = $4",
+        )
+        .bind(status)
+        .bind(delivered_at)
+        .bind(now)
+        .bind(self.id)
+        .execute(&mut tx)
+        .await?;
+
+        if status == TrackingStatus::Delivered {
+            Self::sync_order(self.order_id, &mut tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(status != self.status)
+    }
+
+    /// 全部商品发货且全部包裹签收后, 订单变为已签收
+    async fn sync_order(order_id: i64, tx: &mut Transaction<'_, Postgres>) -> ApiResult<()> {
+        sqlx::query(
+            "update orders set ship_status = $1, updated_at = $2 where id = $3 and ship_status = $4 \
+            and not exists (select id from shipments where order_id = $3 and status != $5)",
+        )
+        .bind::<i8>(LogisticStatus::Delivered.into())
+        .bind(chrono::Local::now().naive_local())
+        .bind(order_id)
+        .bind::<i8>(LogisticStatus::ToBeReceived.into())
+        .bind(TrackingStatus::Delivered)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 订单物流信息: 包裹、包裹内商品及物流轨迹
+    pub async fn timeline(order_id: i64) -> ApiResult<Vec<serde_json::Value>> {
+        let shipments: Vec<Self> =
+            sqlx::query_as("select * from shipments where order_id = $1 order by id asc")
+                .bind(order_id)
+                .fetch_all(common::postgres().await)
+                .await?;
+        let ids = shipments.iter().map(|item| item.id).collect::<Vec<i64>>();
+
+        let mut items: HashMap<i64, Vec<serde_json::Value>> = HashMap::new();
+        let item_rows = sqlx::query(
+            "select s.shipment_id,i.id,i.product_id,i.product_sku from shipment_items as s \
+            inner join order_items as i on s.order_item_id = i.id where s.shipment_id = any($1)",
+        )
+        .bind(&ids)
+        .fetch_all(common::postgres().await)
+        .await?;
+        for row in item_rows.iter() {
+            items
+                .entry(row.get::<i64, _>("shipment_id"))
+                .or_default()
+                .push(json!({
+                    "id": row.get::<i64, _>("id"),
+                    "product_id": row.get::<i64, _>("product_id"),
+                    "product_sku": row.get::<serde_json::Value, _>("product_sku"),
+                }));
+        }
+
+        let mut events: HashMap<i64, Vec<serde_json::Value>> = HashMap::new();
+        let event_rows: Vec<ShipmentEvent> = sqlx::query_as(
+            "select * from shipment_events where shipment_id = any($1) order by occurred_at desc",
+        )
+        .bind(&ids)
+        .fetch_all(common::postgres().await)
+        .await?;
+        for event in event_rows {
+            events.entry(event.shipment_id).or_default().push(json!({
+                "status": event.status.as_ref(),
+                "description": event.description,
+                "location": event.location,
+                "occurred_at": common::time_ymd_his(event.occurred_at),
+            }));
+        }
+
+        Ok(shipments
+            .iter()
+            .map(|shipment| {
+                json!({
+                    "id": shipment.id,
+                    "company": shipment.company,
+                    "tracking_no": shipment.tracking_no,
+                    "status": shipment.status.as_ref(),
+                    "shipped_at": common::time_ymd_his(shipment.shipped_at),
+                    "delivered_at": shipment.delivered_at.map(common::time_ymd_his),
+                    "items": items.remove(&shipment.id).unwrap_or_default(),
+                    "events": events.remove(&shipment.id).unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::Row;
+
+use common::error::{ApiError, ApiResult};
+use common::freight::{ChargeType, Parcel, ShippingRule};
+
+/// 运费模板
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ShippingTemplate {
+    pub id: i64,
+    pub name: String,
+    pub charge_type: ChargeType,
+    pub rules: Json<Vec<ShippingRule>>,
+    // 默认模板, 未设置运费模板的商品使用
+    pub is_default: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// 计算运费的订单商品
+#[derive(Debug)]
+pub struct FreightItem {
+    pub product_id: i64,
+    // sku重量(克)
+    pub weight: i32,
+    pub amount: i32,
+    // 商品金额(分)
+    pub money: i64,
+}
+
+impl ShippingTemplate {
+    /// 未设置默认规则时, 规则之外的地区不配送
+    fn check_rules(rules: &[ShippingRule]) -> ApiResult<()> {
+        for rule in rules.iter() {
+            if rule.first_fee < 0.0 || rule.additional_fee < 0.0 || rule.free_amount < 0.0 {
+                return Err(ApiError::Error("运费不能小于0".to_string()));
+            }
+            if rule.additional_fee > 0.0 && rule.additional == 0 {
+                return Err(ApiError::Error("请设置续重或续件数量".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn index() -> ApiResult<Vec<Self>> {
+        Ok(
+            sqlx::query_as("select * from shipping_templates order by id asc")
+                .fetch_all(common::postgres().await)
+                .await?,
+        )
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from shipping_templates where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("运费模板不存在".to_string()))
+    }
+
+    /// 创建或修改, id 为0时创建
+    pub async fn save(
+        id: i64,
+        name: String,
+        charge_type: ChargeType,
+        rules: Vec<ShippingRule>,
+        is_default: bool,
+    ) -> ApiResult<i64> {
+        Self::check_rules(&rules)?;
+
+        let mut tx = common::postgres().await.begin().await?;
+        if is_default {
+            sqlx::query("update shipping_templates set is_default = false where is_default = true")
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let id = if id == 0 {
+            sqlx::query(
+                "insert into shipping_templates (name, charge_type, rules, is_default, created_at, updated_at) \
+                values ($1, $2, $3, $4, $5, $5) RETURNING id",
+            )
+            .bind(name)
+            .bind(charge_type)
+            .bind(Json(rules))
+            .bind(is_default)
+            .bind(now)
+            .fetch_one(&mut tx)
+            .await?
+            .get::<i64, _>("id")
+        } else {
+            let rows = sqlx::query(
+                "update shipping_templates set name = $1, charge_type = $2, rules = $3, is_default = $4, \
+                updated_at = $5 where id = $6",
+            )
+            .bind(name)
+            .bind(charge_type)
+            .bind(Json(rules))
+            .bind(is_default)
+            .bind(now)
+            .bind(id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+            if rows == 0 {
+                return Err(ApiError::Error("运费模板不存在".to_string()));
+            }
+            id
+        };
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// 删除, 使用该模板的商品改为使用默认模板
+    pub async fn delete(id: i64) -> ApiResult<bool> {
+        let mut tx = common::postgres().await.begin().await?;
+        sqlx::query("update products set shipping_template_id = 0 where shipping_template_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let rows = sqlx::query("delete from shipping_templates where id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+
+        Ok(rows > 0)
+    }
+
+    /// 设置商品的运费模板
+    pub async fn bind_products(&self, product_ids: Vec<i64>) -> ApiResult<u64> {
+        Ok(
+            sqlx::query("update products set shipping_template_id = $1 where id = any($2)")
+                .bind(self.id)
+                .bind(product_ids)
+                .execute(common::postgres().await)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    /// 收货地址的地区id: 区、市、省
+    fn regions(address: &HashMap<String, serde_json::Value>) -> Vec<i32> {
+        ["district_id", "city_id", "province_id"]
+            .iter()
+            .filter_map(|key| address.get(*key).and_then(|id| id.as_i64()))
+            .map(|id| id as i32)
+            .collect()
+    }
+
+    /// 计算订单运费(分), 按商品的运费模板分组计算后累加
+    pub async fn freight(
+        address: &HashMap<String, serde_json::Value>,
+        items: &[FreightItem],
+    ) -> ApiResult<i64> {
+        if items.is_empty() {
+            return Ok(0);
+        }
+
+        let product_ids = items
+            .iter()
+            .map(|item| item.product_id)
+            .collect::<Vec<i64>>();
+        let product_templates =
+            sqlx::query("select id,shipping_template_id from products where id = any($1)")
+                .bind(&product_ids)
+                .fetch_all(common::postgres().await)
+                .await?
+                .iter()
+                .map(|row| {
+                    (
+                        row.get::<i64, _>("id"),
+                        row.get::<i64, _>("shipping_template_id"),
+                    )
+                })
+                .collect::<HashMap<i64, i64>>();
+
+        let templates: Vec<Self> = sqlx::query_as(
+            "select * from shipping_templates where id = any($1) or is_default = true",
+        )
+        .bind(product_templates.values().cloned().collect::<Vec<i64>>())
+        .fetch_all(common::postgres().await)
+        .await?;
+        let default_id = templates
+            .iter()
+            .find(|template| template.is_default)
+            .map(|template| template.id);
+
+        // 模板id => 商品合计
+        let mut groups: HashMap<i64, Parcel> = HashMap::new();
+        for item in items.iter() {
+            let template_id = match product_templates.get(&item.product_id) {
+                Some(id) if templates.iter().any(|template| template.id == *id) => *id,
+                _ => match default_id {
+                    Some(id) => id,
+                    // 未设置运费模板时包邮
+                    None => continue,
+                },
+            };
+            groups
+                .entry(template_id)
+                .or_default()
+                .add(item.weight, item.amount, item.money);
+        }
+
+        let regions = Self::regions(address);
+        let mut total = 0i64;
+        for (template_id, parcel) in groups {
+            let template = templates
+                .iter()
+                .find(|template| template.id == template_id)
+                .unwrap();
+            total += parcel
+                .fee(template.charge_type, &template.rules.0, &regions)
+                .ok_or(ApiError::Error("收货地址不在配送范围内".to_string()))?;
+        }
+
+        Ok(total)
+    }
+}
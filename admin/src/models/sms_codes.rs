@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use common::error::{ApiError, ApiResult};
+use common::sms::{self, SmsMessage};
+
+/// 验证码位数
+const CODE_LENGTH: usize = 6;
+
+/// 短信验证码, 保存在redis中
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SmsCode {
+    pub code: String,
+}
+
+impl SmsCode {
+    fn key(scene: &str, phone: &str) -> String {
+        format!("sms_code:{}:{}", scene, phone)
+    }
+
+    fn attempts_key(scene: &str, phone: &str) -> String {
+        format!("sms_code_attempts:{}:{}", scene, phone)
+    }
+
+    /// 发送验证码, 限制同一手机号发送间隔、每天次数及同一ip每小时次数
+    pub async fn send(scene: &str, phone: &str, ip: &str) -> ApiResult<()> {
+        let cfg = &common::application_config().await.sms;
+
+        let interval_key = format!("sms_interval:{}", phone);
+        if common::redis::get(&interval_key).await?.is_some() {
+            return Err(ApiError::Error("发送过于频繁, 请稍后再试".to_string()));
+        }
+        let today = chrono::Local::now().format("%Y%m%d");
+        let daily =
+            common::redis::incr_ex(&format!("sms_daily:{}:{}", phone, today), 86400).await?;
+        if daily > cfg.phone_daily_limit {
+            return Err(ApiError::Error("今日发送次数已达上限".to_string()));
+        }
+        if !ip.is_empty() {
+            let hour = chrono::Local::now().format("%Y%m%d%H");
+            let hourly = common::redis::incr_ex(&format!("sms_ip:{}:{}", ip, hour), 3600).await?;
+            if hourly > cfg.ip_hourly_limit {
+                return Err(ApiError::Error("发送过于频繁, 请稍后再试".to_string()));
+            }
+        }
+
+        let code = SmsCode {
+            code: sms::random_code(CODE_LENGTH),
+        };
+        common::sms()
+            .await
+            .send(&SmsMessage {
+                phone: phone.to_string(),
+                template: cfg.code_template.clone(),
+                params: HashMap::from([
+                    ("code".to_string(), code.code.clone()),
+                    ("minutes".to_string(), (cfg.code_expires / 60).to_string()),
+                ]),
+            })
+            .await?;
+
+        common::redis::set_ex(
+            &Self::key(scene, phone),
+            &serde_json::to_string(&code)?,
+            cfg.code_expires,
+        )
+        .await?;
+        common::redis::del(&Self::attempts_key(scene, phone)).await?;
+        common::redis::set_ex(&interval_key, "1", cfg.resend_interval).await
+    }
+
+    /// 校验验证码, 成功后失效; 超过校验次数后需重新获取
+    pub async fn verify(scene: &str, phone: &str, code: &str) -> ApiResult<()> {
+        let cfg = &common::application_config().await.sms;
+        let key = Self::key(scene, phone);
+        let attempts_key = Self::attempts_key(scene, phone);
+        let saved: SmsCode = match common::redis::get(&key).await? {
+            Some(value) => serde_json::from_str(&value)?,
+            None => return Err(ApiError::Error("验证码已过期, 请重新获取".to_string())),
+        };
+
+        // 先计数再比较, 并发请求不能绕过次数限制
+        let attempts = common::redis::incr_ex(&attempts_key, cfg.code_expires).await?;
+        if attempts > cfg.max_attempts as i64 {
+            common::redis::del(&key).await?;
+            return Err(ApiError::Error("验证码已失效, 请重新获取".to_string()));
+        }
+
+        if saved.code == code.trim() {
+            common::redis::del(&key).await?;
+            return common::redis::del(&attempts_key).await;
+        }
+        if attempts >= cfg.max_attempts as i64 {
+            common::redis::del(&key).await?;
+        }
+
+        Err(ApiError::Error("验证码错误".to_string()))
+    }
+}
@@ -1,8 +1,11 @@
 use std::collections::HashMap;
 
-use sqlx::Row;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Row, Transaction};
 
 use common::error::ApiError;
+use common::jwt::UserType;
+use common::pwd::{self, Argon2Encoder, PassWordAnalyze};
 use common::request::user::ReqGetUser;
 use common::{
     error::ApiResult,
@@ -15,6 +18,161 @@ use common::{
 use crate::models::favorite_products::FavoriteProducts;
 use crate::models::product_skus::ProductSku;
 
+/// 邮箱验证token有效期(秒)
+const EMAIL_VERIFY_TTL: usize = 24 * 60 * 60;
+
+/// 账号状态
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum UserStatus {
+    // 正常
+    Normal = 1,
+    // 禁用
+    Disabled = 2,
+    // 锁定, 到期自动解锁
+    Locked = 3,
+}
+
+impl Default for UserStatus {
+    fn default() -> Self {
+        UserStatus::Normal
+    }
+}
+
+impl TryFrom<u8> for UserStatus {
+    type Error = ApiError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(UserStatus::Normal),
+            2 => Ok(UserStatus::Disabled),
+            3 => Ok(UserStatus::Locked),
+            _ => Err(ApiError::Error("账号状态错误".to_string())),
+        }
+    }
+}
+
+impl AsRef<str> for UserStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            UserStatus::Normal => "正常",
+            UserStatus::Disabled => "已禁用",
+            UserStatus::Locked => "已锁定",
+        }
+    }
+}
+
+/// 登录凭证
+#[derive(Debug, sqlx::FromRow)]
+pub struct Credential {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    pub status: UserStatus,
+    pub locked_until: Option<chrono::NaiveDateTime>,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
+    // 1: 普通用户, 2: 管理员, 3: 超级管理员
+    pub user_type: i16,
+}
+
+impl Credential {
+    pub async fn by_email(email: &str) -> ApiResult<Option<Self>> {
+        Ok(sqlx::query_as(
+            "select id,name,email,password,status,locked_until,email_verified_at,user_type from users where email = $1",
+        )
+        .bind(email)
+        .fetch_optional(common::postgres().await)
+        .await?)
+    }
+
+    pub async fn by_id(id: i64) -> ApiResult<Option<Self>> {
+        Ok(sqlx::query_as(
+            "select id,name,email,password,status,locked_until,email_verified_at,user_type from users where id = $1",
+        )
+        .bind(id)
+        .fetch_optional(common::postgres().await)
+        .await?)
+    }
+
+    /// 只匹配已通过短信验证的手机号
+    pub async fn by_phone(phone: &str) -> ApiResult<Option<Self>> {
+        Ok(sqlx::query_as(
+            "select id,name,email,password,status,locked_until,email_verified_at,user_type from users where phone = $1 and phone_verified_at is not null",
+        )
+        .bind(phone)
+        .fetch_optional(common::postgres().await)
+        .await?)
+    }
+
+    pub fn user_type(&self) -> UserType {
+        match self.user_type {
+            2 => UserType::Admin,
+            3 => UserType::SuperAdmin,
+            _ => UserType::User,
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        !matches!(self.user_type(), UserType::User)
+    }
+
+    /// 校验密码及账号状态, 旧的md5密码校验通过后升级为argon2id
+    pub async fn check(&self, password: &str) -> ApiResult<()> {
+        // 未设置密码的账号不能使用密码登录
+        let (valid, rehash) = match self.password.is_empty() {
+            true => (false, None),
+            false => {
+                // argon2id 计算耗时, 放到阻塞线程中执行
+                let cfg = common::application_config().await.password.clone();
+                let (hash, password) = (self.password.clone(), password.to_string());
+                tokio::task::spawn_blocking(move || pwd::verify_upgrade(&cfg, &hash, &password))
+                    .await
+                    .map_err(|e| ApiError::Error(e.to_string()))??
+            }
+        };
+        if !valid {
+            return Err(ApiError::Error("用户名或密码错误".to_string()));
+        }
+        if let Some(hash) = rehash {
+            sqlx::query("update users set password = $1 where id = $2 and password = $3")
+                .bind(hash)
+                .bind(self.id)
+                .bind(&self.password)
+                .execute(common::postgres().await)
+                .await?;
+        }
+
+        self.check_status()?;
+        if self.email_verified_at.is_none() {
+            return Err(ApiError::Error("邮箱未验证, 请先完成邮箱验证".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// 账号状态, 禁用或锁定中的账号不能登录
+    pub fn check_status(&self) -> ApiResult<()> {
+        match self.status {
+            UserStatus::Normal => {}
+            UserStatus::Disabled => return Err(ApiError::Error("账号已被禁用".to_string())),
+            UserStatus::Locked => {
+                let now = chrono::Local::now().naive_local();
+                if self.locked_until.map_or(true, |until| until > now) {
+                    return Err(ApiError::Error(match self.locked_until {
+                        Some(until) => {
+                            format!("账号已锁定, 请于 {} 后重试", common::time_ymd_his(until))
+                        }
+                        None => "账号已锁定, 请联系管理员".to_string(),
+                    }));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 pub struct Admin {
     pub id: i64,
     pub name: String,
@@ -140,18 +298,212 @@ impl Admin {
         Ok(())
     }
 
-    /// create 创建用户
+    /// create 创建用户, 后台创建的账号无需邮箱验证
     pub async fn create(info: ReqCrateUser) -> ApiResult<u64> {
+        let password = info.password.clone().unwrap_or_default();
+        if password != info.password_confirm.clone().unwrap_or_default() {
+            return Err(ApiError::Error("两次密码不一致".to_string()));
+        }
         let phone = &info.phone.unwrap()[3..].to_string();
-        let id: i64 = sqlx::query("insert into users (name, age, nickname, phone, email) values($1, $2, $3, $4, $5) RETURNING id")
+        let password = Self::hash_password(&password).await?;
+
+        let mut tx = common::postgres().await.begin().await?;
+        Self::check_unique(info.email.as_deref().unwrap_or_default(), phone, &mut tx).await?;
+        let id: i64 = sqlx::query("insert into users (name, age, nickname, phone, email, password, status, email_verified_at) values($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id")
             .bind(&info.name).bind(&info.age).bind(&info.nickname)
             .bind(phone).bind(&info.email)
-            .fetch_one(common::postgres().await)
+            .bind(password).bind(UserStatus::Normal)
+            .bind(chrono::Local::now().naive_local())
+            .fetch_one(&mut tx)
             .await?.get::<i64, &str>("id");
+        tx.commit().await?;
 
         Ok(id as u64)
     }
 
+    /// 按当前配置加密密码
+    pub async fn hash_password(password: &str) -> ApiResult<String> {
+        let encoder = Argon2Encoder::new(&common::application_config().await.password)?;
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || encoder.encode(&password))
+            .await
+            .map_err(|e| ApiError::Error(e.to_string()))?
+    }
+
+    /// 邮箱、手机号唯一
+    async fn check_unique(
+        email: &str,
+        phone: &str,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        let row = sqlx::query(
+            "select count(*) filter (where email = $1) as emails, \
+            count(*) filter (where $2 <> '' and phone = $2) as phones from users",
+        )
+        .bind(email)
+        .bind(phone)
+        .fetch_one(tx)
+        .await?;
+        if row.get::<i64, _>("emails") > 0 {
+            return Err(ApiError::Error("该邮箱已注册".to_string()));
+        }
+        if row.get::<i64, _>("phones") > 0 {
+            return Err(ApiError::Error("该手机号已注册".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// 手机号通过短信验证后才能绑定, 同时解除其他账号未验证的同一手机号
+    async fn release_phone(phone: &str, tx: &mut Transaction<'_, Postgres>) -> ApiResult<()> {
+        sqlx::query("update users set phone = '' where phone = $1 and phone_verified_at is null")
+            .bind(phone)
+            .execute(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 用户注册, 注册后需完成邮箱验证才能登录; 填写的手机号已在注册前通过短信验证
+    pub async fn register(email: String, phone: Option<String>, password: &str) -> ApiResult<i64> {
+        let phone = phone
+            .map(|phone| phone.trim_start_matches("+86").to_string())
+            .unwrap_or_default();
+        let password = Self::hash_password(password).await?;
+        let phone_verified_at = (!phone.is_empty()).then(|| chrono::Local::now().naive_local());
+
+        let mut tx = common::postgres().await.begin().await?;
+        if !phone.is_empty() {
+            Self::release_phone(&phone, &mut tx).await?;
+        }
+        Self::check_unique(&email, &phone, &mut tx).await?;
+        let name = email.split('@').next().unwrap_or_default().to_string();
+        let id = sqlx::query(
+            "insert into users (name, age, nickname, phone, email, password, status, phone_verified_at) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(&name)
+        .bind(0i16)
+        .bind(&name)
+        .bind(phone)
+        .bind(email)
+        .bind(password)
+        .bind(UserStatus::Normal)
+        .bind(phone_verified_at)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// 手机号注册, 验证码登录时手机号未注册则自动注册
+    pub async fn register_phone(phone: &str) -> ApiResult<i64> {
+        let name = format!("用户{}", &phone[phone.len().saturating_sub(4)..]);
+        let mut tx = common::postgres().await.begin().await?;
+        Self::release_phone(phone, &mut tx).await?;
+        let id = sqlx::query(
+            "insert into users (name, age, nickname, phone, email, password, status, phone_verified_at) \
+            values ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(&name)
+        .bind(0i16)
+        .bind(&name)
+        .bind(phone)
+        .bind("")
+        .bind("")
+        .bind(UserStatus::Normal)
+        .bind(chrono::Local::now().naive_local())
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// 第三方账号注册, 首次第三方登录时自动注册
+    pub async fn register_oauth(
+        tx: &mut Transaction<'_, Postgres>,
+        nickname: Option<&str>,
+    ) -> ApiResult<i64> {
+        let name = match nickname {
+            Some(nickname) => nickname.to_string(),
+            None => format!("用户{}", common::get_random_str(6)),
+        };
+        let id = sqlx::query(
+            "insert into users (name, age, nickname, phone, email, password, status) \
+            values ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+        )
+        .bind(&name)
+        .bind(0i16)
+        .bind(&name)
+        .bind("")
+        .bind("")
+        .bind("")
+        .bind(UserStatus::Normal)
+        .fetch_one(tx)
+        .await?
+        .get::<i64, _>("id");
+
+        Ok(id)
+    }
+
+    /// 生成邮箱验证token
+    pub async fn email_verify_token(user_id: i64) -> ApiResult<String> {
+        let token = common::get_random_str(32);
+        common::redis::set_ex(
+            &format!("email_verify:{}", token),
+            &user_id.to_string(),
+            EMAIL_VERIFY_TTL,
+        )
+        .await?;
+
+        Ok(token)
+    }
+
+    /// 验证邮箱, token 只能使用一次
+    pub async fn verify_email(token: &str) -> ApiResult<i64> {
+        let key = format!("email_verify:{}", token);
+        let user_id = common::redis::get(&key)
+            .await?
+            .and_then(|id| id.parse::<i64>().ok())
+            .ok_or(ApiError::Error("验证链接无效或已过期".to_string()))?;
+        common::redis::del(&key).await?;
+
+        sqlx::query(
+            "update users set email_verified_at = $1 where id = $2 and email_verified_at is null",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .bind(user_id)
+        .execute(common::postgres().await)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    /// 修改账号状态, 锁定时 locked_until 为空表示需手动解锁
+    pub async fn set_status(
+        id: i64,
+        status: UserStatus,
+        locked_until: Option<chrono::NaiveDateTime>,
+    ) -> ApiResult<bool> {
+        let locked_until = match status {
+            UserStatus::Locked => locked_until,
+            _ => None,
+        };
+        let rows = sqlx::query("update users set status = $1, locked_until = $2 where id = $3")
+            .bind(status)
+            .bind(locked_until)
+            .bind(id)
+            .execute(common::postgres().await)
+            .await?
+            .rows_affected();
+
+        Ok(rows > 0)
+    }
+
     /// update 更新用户信息
     pub async fn update(info: ReqUpdateUser) -> ApiResult<bool> {
         let rows_num = sqlx::query("update users set name = $1, age = $2 where id = $3")
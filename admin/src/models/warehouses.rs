@@ -0,0 +1,731 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::Json;
+use sqlx::{Postgres, Row, Transaction};
+use tracing::warn;
+
+use common::allocation::{self, Line, WarehouseStock};
+use common::error::{ApiError, ApiResult};
+use common::warehouse::ReqWarehouse;
+use common::Pagination;
+
+use crate::models::favorite_alerts::{FavoriteAlert, SkuWatcher};
+use crate::models::LogisticStatus;
+
+/// 仓库
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Warehouse {
+    pub id: i64,
+    pub name: String,
+    pub code: String,
+    // 仓库所在省份
+    pub province_id: i32,
+    // 相邻省份
+    pub nearby: Json<Vec<i32>>,
+    // 同等距离时优先级高的仓库优先发货
+    pub priority: i32,
+    // 默认仓库, 商品保存时新增的sku库存入此仓库
+    pub is_default: bool,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Warehouse {
+    pub async fn index() -> ApiResult<Vec<Self>> {
+        Ok(
+            sqlx::query_as("select * from warehouses order by priority desc, id asc")
+                .fetch_all(common::postgres().await)
+                .await?,
+        )
+    }
+
+    pub async fn get(id: i64) -> ApiResult<Self> {
+        sqlx::query_as("select * from warehouses where id = $1")
+            .bind(id)
+            .fetch_optional(common::postgres().await)
+            .await?
+            .ok_or(ApiError::Error("仓库不存在".to_string()))
+    }
+
+    /// 创建或修改, id 为0时创建
+    pub async fn save(id: i64, info: ReqWarehouse) -> ApiResult<i64> {
+        let code = info.code.unwrap_or_default();
+        let is_default = info.is_default.unwrap_or_default();
+        let mut tx = common::postgres().await.begin().await?;
+        let exists = sqlx::query("select id from warehouses where code = $1 and id != $2")
+            .bind(&code)
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?;
+        if exists.is_some() {
+            return Err(ApiError::Error(format!("仓库编码 {} 已存在", code)));
+        }
+        if is_default {
+            sqlx::query("update warehouses set is_default = false where is_default = true")
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let id = if id == 0 {
+            sqlx::query(
+                "insert into warehouses (name, code, province_id, nearby, priority, is_default, enabled, \
+                created_at, updated_at) values ($1, $2, $3, $4, $5, $6, $7, $8, $8) RETURNING id",
+            )
+            .bind(&info.name)
+            .bind(&code)
+            .bind(info.province_id)
+            .bind(Json(info.nearby.clone().unwrap_or_default()))
+            .bind(info.priority.unwrap_or_default())
+            .bind(is_default)
+            .bind(info.enabled.unwrap_or(true))
+            .bind(now)
+            .fetch_one(&mut tx)
+            .await?
+            .get::<i64, _>("id")
+        } else {
+            let rows = sqlx::query(
+                "update warehouses set name = $1, code = $2, province_id = $3, nearby = $4, priority = $5, \
+                is_default = $6, enabled = $7, updated_at = $8 where id = $9",
+            )
+            .bind(&info.name)
+            .bind(&code)
+            .bind(info.province_id)
+            .bind(Json(info.nearby.clone().unwrap_or_default()))
+            .bind(info.priority.unwrap_or_default())
+            .bind(is_default)
+            .bind(info.enabled.unwrap_or(true))
+            .bind(now)
+            .bind(id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+            if rows == 0 {
+                return Err(ApiError::Error("仓库不存在".to_string()));
+            }
+            id
+        };
+        // 启用仓库前已有的sku, 以商品库存存入仓库
+        Self::seed_stocks(None, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// 增加仓库sku库存, 库存记录不存在时创建
+    async fn add_stock(
+        warehouse_id: i64,
+        sku_id: i64,
+        amount: i32,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        if !Self::return_stock(warehouse_id, sku_id, amount, tx).await? {
+            return Err(ApiError::Error("sku不存在".to_string()));
+        }
+
+        Ok(())
+    }
+
+    // 退回仓库sku库存, 库存记录不存在时创建, sku已删除时返回 false
+    async fn return_stock(
+        warehouse_id: i64,
+        sku_id: i64,
+        amount: i32,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<bool> {
+        let rows = sqlx::query(
+            "insert into sku_stocks (warehouse_id, product_id, sku_id, sku_title, stock, updated_at) \
+            select $1, product_id, id, title, $3, $4 from product_skus where id = $2 \
+            on conflict (warehouse_id, sku_id) do update set stock = sku_stocks.stock + $3, updated_at = $4",
+        )
+        .bind(warehouse_id)
+        .bind(sku_id)
+        .bind(amount)
+        .bind(chrono::Local::now().naive_local())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    /// 没有仓库库存记录的sku, 以商品库存存入默认仓库, 未设置默认仓库时存入优先级最高的仓库
+    ///
+    /// 启用仓库前已有的sku没有仓库库存记录, 分仓时会被当作无库存
+    async fn seed_stocks(
+        product_ids: Option<&[i64]>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        let warehouse_id = sqlx::query(
+            "select id from warehouses where is_default = true or enabled = true \
+            order by is_default desc, priority desc, id asc limit 1",
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.get::<i64, _>("id"));
+        if let Some(warehouse_id) = warehouse_id {
+            sqlx::query(
+                "insert into sku_stocks (warehouse_id, product_id, sku_id, sku_title, stock, updated_at) \
+                select $1, k.product_id, k.id, k.title, k.stock, $2 from product_skus as k \
+                where ($3::INT8[] is null or k.product_id = any($3)) \
+                and not exists (select id from sku_stocks where sku_id = k.id)",
+            )
+            .bind(warehouse_id)
+            .bind(chrono::Local::now().naive_local())
+            .bind(product_ids)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// 商品总库存为各仓库库存之和
+    async fn sync_total(sku_ids: &[i64], tx: &mut Transaction<'_, Postgres>) -> ApiResult<()> {
+        sqlx::query(
+            "update product_skus as k set stock = (select coalesce(sum(s.stock), 0) from sku_stocks as s \
+            where s.sku_id = k.id) where k.id = any($1)",
+        )
+        .bind(sku_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 入库并记录库存流水, kind 为业务类型, ref_id 为业务单据id
+    pub async fn post_movement(
+        warehouse_id: i64,
+        sku_id: i64,
+        change: i32,
+        kind: &str,
+        ref_id: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        Self::add_stock(warehouse_id, sku_id, change, tx).await?;
+        sqlx::query(
+            "insert into stock_movements (warehouse_id, sku_id, change, kind, ref_id, created_at) \
+            values ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(warehouse_id)
+        .bind(sku_id)
+        .bind(change)
+        .bind(kind)
+        .bind(ref_id)
+        .bind(chrono::Local::now().naive_local())
+        .execute(&mut *tx)
+        .await?;
+
+        Self::sync_total(&[sku_id], tx).await
+    }
+
+    /// 商品保存后同步仓库库存
+    ///
+    /// sku修改时id不变, 同步仓库库存中的sku名称并删除已删除sku的库存; 新增的sku以填写的库存存入默认仓库
+    pub async fn sync_skus(product_id: i64, tx: &mut Transaction<'_, Postgres>) -> ApiResult<()> {
+        sqlx::query(
+            "update sku_stocks as s set sku_title = k.title from product_skus as k \
+            where s.product_id = $1 and s.sku_id = k.id and s.sku_title != k.title",
+        )
+        .bind(product_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "delete from sku_stocks where product_id = $1 \
+            and sku_id not in (select id from product_skus where product_id = $1)",
+        )
+        .bind(product_id)
+        .execute(&mut *tx)
+        .await?;
+        Self::seed_stocks(Some(&[product_id]), tx).await?;
+
+        let sku_ids = sqlx::query(
+            "select id from product_skus as k where product_id = $1 \
+            and exists (select id from sku_stocks where sku_id = k.id)",
+        )
+        .bind(product_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.get::<i64, _>("id"))
+        .collect::<Vec<i64>>();
+
+        Self::sync_total(&sku_ids, tx).await
+    }
+
+    /// 订单分仓并扣减库存, 未启用仓库时返回 false
+    pub async fn allocate(
+        order_id: i64,
+        province_id: i32,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<bool> {
+        let warehouses: Vec<Self> = sqlx::query_as(
+            "select * from warehouses where enabled = true order by priority desc, id asc",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        if warehouses.is_empty() {
+            return Ok(false);
+        }
+
+        let mut lines = vec![];
+        let rows =
+            sqlx::query("select id,product_id,product_sku from order_items where order_id = $1")
+                .bind(order_id)
+                .fetch_all(&mut *tx)
+                .await?;
+        let mut product_ids = vec![];
+        for row in rows.iter() {
+            let sku = row.get::<Json<HashMap<String, serde_json::Value>>, _>("product_sku");
+            lines.push(Line {
+                item_id: row.get("id"),
+                sku_id: sku
+                    .get("sku_id")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or_default(),
+                amount: sku
+                    .get("amount")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or_default() as i32,
+            });
+            product_ids.push(row.get::<i64, _>("product_id"));
+        }
+        let mut sku_ids = lines.iter().map(|line| line.sku_id).collect::<Vec<i64>>();
+        sku_ids.sort();
+        sku_ids.dedup();
+        Self::seed_stocks(Some(&product_ids), tx).await?;
+
+        let mut stocks: HashMap<i64, HashMap<i64, i32>> = HashMap::new();
+        let rows = sqlx::query(
+            "select warehouse_id,sku_id,stock from sku_stocks where sku_id = any($1) for update",
+        )
+        .bind(&sku_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in rows.iter() {
+            stocks
+                .entry(row.get("warehouse_id"))
+                .or_default()
+                .insert(row.get("sku_id"), row.get("stock"));
+        }
+
+        let warehouse_stocks = warehouses
+            .iter()
+            .map(|warehouse| WarehouseStock {
+                id: warehouse.id,
+                rank: allocation::rank(province_id, warehouse.province_id, &warehouse.nearby),
+                stock: stocks.remove(&warehouse.id).unwrap_or_default(),
+            })
+            .collect::<Vec<WarehouseStock>>();
+        let plan = allocation::route(&warehouse_stocks, &lines)
+            .ok_or(ApiError::Error("商品库存不足".to_string()))?;
+
+        let now = chrono::Local::now().naive_local();
+        for (warehouse_id, lines) in plan.iter() {
+            for line in lines.iter() {
+                sqlx::query(
+                    "update sku_stocks set stock = stock - $1, updated_at = $2 where warehouse_id = $3 and sku_id = $4",
+                )
+                .bind(line.amount)
+                .bind(now)
+                .bind(warehouse_id)
+                .bind(line.sku_id)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query(
+                    "insert into order_allocations (order_id, order_item_id, warehouse_id, sku_id, amount, \
+                    released, created_at) values ($1, $2, $3, $4, $5, false, $6)",
+                )
+                .bind(order_id)
+                .bind(line.item_id)
+                .bind(warehouse_id)
+                .bind(line.sku_id)
+                .bind(line.amount)
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        Self::sync_total(&sku_ids, tx).await?;
+
+        Ok(true)
+    }
+
+    /// 订单取消后库存退回原仓库, 订单未分仓时返回 false
+    pub async fn release(order_id: i64, tx: &mut Transaction<'_, Postgres>) -> ApiResult<bool> {
+        let rows = sqlx::query(
+            "update order_allocations set released = true where order_id = $1 and released = false \
+            RETURNING warehouse_id,sku_id,amount",
+        )
+        .bind(order_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            return Ok(false);
+        }
+
+        let mut sku_ids = vec![];
+        for row in rows.iter() {
+            let sku_id = row.get::<i64, _>("sku_id");
+            let amount = row.get::<i32, _>("amount");
+            // 库存记录被删除时重新创建, sku已删除时无法退回
+            if !Self::return_stock(row.get("warehouse_id"), sku_id, amount, tx).await? {
+                warn!(
+                    "订单[{}]的sku[{}]已删除, 库存 {} 未退回",
+                    order_id, sku_id, amount
+                );
+                continue;
+            }
+            sku_ids.push(sku_id);
+        }
+        Self::sync_total(&sku_ids, tx).await?;
+
+        Ok(true)
+    }
+
+    /// 售后退货入库: 退回发货仓库, 未分仓的订单退回默认仓库
+    pub async fn restock(
+        order_item_id: i64,
+        sku_id: i64,
+        amount: i32,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        let warehouse_id = sqlx::query(
+            "select coalesce((select warehouse_id from order_allocations where order_item_id = $1 \
+            and sku_id = $2 limit 1), (select id from warehouses where is_default = true limit 1)) as warehouse_id",
+        )
+        .bind(order_item_id)
+        .bind(sku_id)
+        .fetch_one(&mut *tx)
+        .await?
+        .get::<Option<i64>, _>("warehouse_id");
+
+        let returned = match warehouse_id {
+            Some(warehouse_id) => {
+                let returned = Self::return_stock(warehouse_id, sku_id, amount, tx).await?;
+                Self::sync_total(&[sku_id], tx).await?;
+                returned
+            }
+            None => {
+                sqlx::query("update product_skus set stock = stock + $1 where id = $2")
+                    .bind(amount)
+                    .bind(sku_id)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected()
+                    > 0
+            }
+        };
+        if !returned {
+            warn!(
+                "售后商品[{}]的sku[{}]已删除, 库存 {} 未退回",
+                order_item_id, sku_id, amount
+            );
+        }
+
+        Ok(())
+    }
+
+    /// 换货出库: 优先从原发货仓库扣减, 库存不足时从其他仓库扣减; 未启用仓库时扣减商品库存
+    pub async fn deduct(
+        order_item_id: i64,
+        sku_id: i64,
+        amount: i32,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> ApiResult<()> {
+        let enabled = sqlx::query("select exists (select id from warehouses where enabled = true)")
+            .fetch_one(&mut *tx)
+            .await?
+            .get::<bool, _>("exists");
+        if !enabled {
+            let rows = sqlx::query(
+                "update product_skus set stock = stock - $1 where id = $2 and stock >= $1",
+            )
+            .bind(amount)
+            .bind(sku_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if rows == 0 {
+                return Err(ApiError::Error("换货商品库存不足".to_string()));
+            }
+            return Ok(());
+        }
+
+        let warehouse_id = sqlx::query(
+            "select s.warehouse_id from sku_stocks as s inner join warehouses as w on s.warehouse_id = w.id \
+            where s.sku_id = $1 and s.stock >= $2 and w.enabled = true \
+            order by s.warehouse_id in (select warehouse_id from order_allocations where order_item_id = $3) desc, \
+            w.priority desc, w.id asc limit 1 for update of s",
+        )
+        .bind(sku_id)
+        .bind(amount)
+        .bind(order_item_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.get::<i64, _>("warehouse_id"))
+        .ok_or(ApiError::Error("换货商品库存不足".to_string()))?;
+
+        sqlx::query(
+            "update sku_stocks set stock = stock - $1, updated_at = $2 where warehouse_id = $3 and sku_id = $4",
+        )
+        .bind(amount)
+        .bind(chrono::Local::now().naive_local())
+        .bind(warehouse_id)
+        .bind(sku_id)
+        .execute(&mut *tx)
+        .await?;
+
+        Self::sync_total(&[sku_id], tx).await
+    }
+
+    /// 仓库间调拨
+    pub async fn transfer(
+        sku_id: i64,
+        from_id: i64,
+        to_id: i64,
+        amount: i32,
+        remark: String,
+    ) -> ApiResult<i64> {
+        if from_id == to_id {
+            return Err(ApiError::Error("调出和调入仓库不能相同".to_string()));
+        }
+        Self::get(to_id).await?;
+
+        let mut tx = common::postgres().await.begin().await?;
+        let stock = sqlx::query(
+            "select stock from sku_stocks where warehouse_id = $1 and sku_id = $2 for update",
+        )
+        .bind(from_id)
+        .bind(sku_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .map(|row| row.get::<i32, _>("stock"))
+        .unwrap_or_default();
+        if stock < amount {
+            return Err(ApiError::Error(format!(
+                "调出仓库库存不足, 当前库存: {}",
+                stock
+            )));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        sqlx::query(
+            "update sku_stocks set stock = stock - $1, updated_at = $2 where warehouse_id = $3 and sku_id = $4",
+        )
+        .bind(amount)
+        .bind(now)
+        .bind(from_id)
+        .bind(sku_id)
+        .execute(&mut tx)
+        .await?;
+        Self::add_stock(to_id, sku_id, amount, &mut tx).await?;
+
+        let id = sqlx::query(
+            "insert into stock_transfers (sku_id, from_warehouse_id, to_warehouse_id, amount, remark, created_at) \
+            values ($1, $2, $3, $4, $5, $6) RETURNING id",
+        )
+        .bind(sku_id)
+        .bind(from_id)
+        .bind(to_id)
+        .bind(amount)
+        .bind(remark)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?
+        .get::<i64, _>("id");
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// 盘点, 设置仓库sku库存
+    pub async fn adjust(&self, sku_id: i64, stock: i32) -> ApiResult<()> {
+        let mut tx = common::postgres().await.begin().await?;
+        let product_id = sqlx::query("select product_id from product_skus where id = $1")
+            .bind(sku_id)
+            .fetch_optional(&mut tx)
+            .await?
+            .map(|row| row.get::<i64, _>("product_id"))
+            .ok_or(ApiError::Error("sku不存在".to_string()))?;
+        let watcher = SkuWatcher::watch(&[product_id], &mut tx).await?;
+
+        sqlx::query(
+            "insert into sku_stocks (warehouse_id, product_id, sku_id, sku_title, stock, updated_at) \
+            select $1, product_id, id, title, $3, $4 from product_skus where id = $2 \
+            on conflict (warehouse_id, sku_id) do update set stock = $3, updated_at = $4",
+        )
+        .bind(self.id)
+        .bind(sku_id)
+        .bind(stock)
+        .bind(chrono::Local::now().naive_local())
+        .execute(&mut tx)
+        .await?;
+        Self::sync_total(&[sku_id], &mut tx).await?;
+
+        // 库存增加后通知到货
+        let alerts = watcher.changes(&mut tx).await?;
+        tx.commit().await?;
+        FavoriteAlert::publish(alerts).await;
+
+        Ok(())
+    }
+
+    /// 仓库库存明细, low 为低库存阈值
+    pub async fn stocks(
+        &self,
+        low: Option<i32>,
+        pagination: &mut Pagination<serde_json::Value>,
+    ) -> ApiResult<()> {
+        let rows = sqlx::query(
+            "select s.sku_id,s.product_id,s.sku_title,s.stock,s.updated_at,p.title from sku_stocks as s \
+            inner join products as p on s.product_id = p.id where s.warehouse_id = $1 \
+            and ($2::INT4 is null or s.stock <= $2) order by s.stock asc, s.sku_id asc limit $3 offset $4",
+        )
+        .bind(self.id)
+        .bind(low)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let total = sqlx::query(
+            "select count(*) as total from sku_stocks where warehouse_id = $1 \
+            and ($2::INT4 is null or stock <= $2)",
+        )
+        .bind(self.id)
+        .bind(low)
+        .fetch_one(common::postgres().await)
+        .await?
+        .get::<i64, _>("total");
+
+        pagination.set_total(total as usize);
+        pagination.set_data(
+            rows.iter()
+                .map(|row| {
+                    json!({
+                        "sku_id": row.get::<i64, _>("sku_id"),
+                        "product_id": row.get::<i64, _>("product_id"),
+                        "product_title": row.get::<String, _>("title"),
+                        "sku_title": row.get::<String, _>("sku_title"),
+                        "stock": row.get::<i32, _>("stock"),
+                        "updated_at": common::time_ymd_his(row.get("updated_at")),
+                    })
+                })
+                .collect(),
+        );
+
+        Ok(())
+    }
+
+    /// 各仓库库存汇总: sku数、总库存、低库存sku数、待发货占用
+    pub async fn report(low: i32) -> ApiResult<Vec<serde_json::Value>> {
+        let warehouses = Self::index().await?;
+        let stocks = sqlx::query(
+            "select warehouse_id, count(*) as skus, coalesce(sum(stock), 0)::INT8 as stock, \
+            count(*) filter (where stock <= $1) as low from sku_stocks group by warehouse_id",
+        )
+        .bind(low)
+        .fetch_all(common::postgres().await)
+        .await?
+        .iter()
+        .map(|row| {
+            (
+                row.get::<i64, _>("warehouse_id"),
+                (
+                    row.get::<i64, _>("skus"),
+                    row.get::<i64, _>("stock"),
+                    row.get::<i64, _>("low"),
+                ),
+            )
+        })
+        .collect::<HashMap<i64, (i64, i64, i64)>>();
+        let allocated = sqlx::query(
+            "select a.warehouse_id, coalesce(sum(a.amount), 0)::INT8 as amount from order_allocations as a \
+            inner join orders as o on a.order_id = o.id where a.released = false and o.closed = false \
+            and o.ship_status = any($1) group by a.warehouse_id",
+        )
+        .bind(vec![
+            i8::from(LogisticStatus::Processing),
+            i8::from(LogisticStatus::PartShipped),
+        ])
+        .fetch_all(common::postgres().await)
+        .await?
+        .iter()
+        .map(|row| (row.get::<i64, _>("warehouse_id"), row.get::<i64, _>("amount")))
+        .collect::<HashMap<i64, i64>>();
+
+        Ok(warehouses
+            .iter()
+            .map(|warehouse| {
+                let (skus, stock, low) = stocks.get(&warehouse.id).cloned().unwrap_or_default();
+                json!({
+                    "id": warehouse.id,
+                    "name": warehouse.name,
+                    "code": warehouse.code,
+                    "enabled": warehouse.enabled,
+                    "skus": skus,
+                    "stock": stock,
+                    "low_stock_skus": low,
+                    "allocated": allocated.get(&warehouse.id).cloned().unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    /// 订单分仓结果, 按仓库分组的订单商品
+    pub async fn order_plan(order_id: i64) -> ApiResult<Vec<serde_json::Value>> {
+        let rows = sqlx::query(
+            "select a.warehouse_id,a.order_item_id,a.sku_id,a.amount,w.name, \
+            exists (select id from shipment_items where order_item_id = a.order_item_id) as shipped \
+            from order_allocations as a inner join warehouses as w on a.warehouse_id = w.id \
+            where a.order_id = $1 and a.released = false order by a.warehouse_id asc, a.id asc",
+        )
+        .bind(order_id)
+        .fetch_all(common::postgres().await)
+        .await?;
+
+        let mut result: Vec<serde_json::Value> = vec![];
+        for row in rows.iter() {
+            let warehouse_id = row.get::<i64, _>("warehouse_id");
+            let item = json!({
+                "order_item_id": row.get::<i64, _>("order_item_id"),
+                "sku_id": row.get::<i64, _>("sku_id"),
+                "amount": row.get::<i32, _>("amount"),
+                "shipped": row.get::<bool, _>("shipped"),
+            });
+            match result
+                .iter_mut()
+                .find(|group| group["warehouse_id"] == json!(warehouse_id))
+            {
+                Some(group) => group["items"].as_array_mut().unwrap().push(item),
+                None => result.push(json!({
+                    "warehouse_id": warehouse_id,
+                    "warehouse": row.get::<String, _>("name"),
+                    "items": [item],
+                })),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// 仓库待发货的订单商品
+    pub async fn pending_items(order_id: i64, warehouse_id: i64) -> ApiResult<Vec<i64>> {
+        Ok(sqlx::query(
+            "select distinct a.order_item_id from order_allocations as a where a.order_id = $1 \
+            and a.warehouse_id = $2 and a.released = false \
+            and not exists (select id from shipment_items where order_item_id = a.order_item_id)",
+        )
+        .bind(order_id)
+        .bind(warehouse_id)
+        .fetch_all(common::postgres().await)
+        .await?
+        .iter()
+        .map(|row| row.get::<i64, _>("order_item_id"))
+        .collect())
+    }
+}
@@ -1,13 +1,25 @@
 use axum::middleware as AxumMiddleware;
-use axum::routing::{delete, get, post};
+use axum::routing::{get, post};
 use axum::Router;
 use tower::ServiceBuilder;
 
+use common::route_catalog::{self, CatalogRouter};
 use middleware::casbin::CasbinAuthLayer;
 
+use crate::controller::after_sales::AfterSaleController;
 use crate::controller::categories::CategoriesController;
 use crate::controller::coupons::CouponController;
+use crate::controller::guest_cart::GuestCartController;
+use crate::controller::identities::IdentityController;
+use crate::controller::invoices::InvoiceController;
+use crate::controller::menus::MenuController;
+use crate::controller::mfa::MfaController;
 use crate::controller::products::ProductController;
+use crate::controller::purchases::PurchaseController;
+use crate::controller::reviews::ReviewController;
+use crate::controller::roles::RoleController;
+use crate::controller::shipping_templates::ShippingTemplateController;
+use crate::controller::warehouses::WarehouseController;
 use crate::controller::{
     address::AddressController, auth::RolePermissionController, order::OrderController,
     user::AdminController, CommController,
@@ -17,172 +29,891 @@ use crate::middleware;
 // Path  GET    格式: /user/132
 // Query GET    格式: /user/test?id=123&name=456
 
+// 后台路由的完整前缀, 与 casbin 规则中的路由一致
+const CATALOG_PREFIX: &str = "/api/admin";
+
 pub async fn admin() -> Router {
     let login = Router::new()
         .route("/register", post(AdminController::register))
-        .route("/login", post(AdminController::login));
-    let users = Router::new().nest(
-        "/users",
-        Router::new()
-            .route(
-                "/",
-                get(AdminController::lists).post(AdminController::create),
-            )
-            .route(
-                "/:id",
-                get(AdminController::get)
-                    .put(AdminController::update)
-                    .delete(AdminController::delete),
-            )
-            .route(
-                "/carts",
-                get(AdminController::carts)
-                    .post(AdminController::add_cart)
-                    .delete(AdminController::delete_carts),
-            ),
-    );
-    let address = Router::new().nest(
-        "/address",
-        Router::new()
-            .route(
-                "/",
-                get(AddressController::list_address).post(AddressController::create_address),
-            )
-            .route("/result/:pid", get(AddressController::addr_result))
-            .route(
-                "/:id",
-                get(AddressController::get_address)
-                    .put(AddressController::update_address)
-                    .delete(AddressController::delete_address),
-            ),
-    );
-    let auth = Router::new().nest(
-        "/auth",
-        Router::new()
-            .route(
-                "/perm_for_role",
-                post(RolePermissionController::get_permissions_for_role),
-            )
-            .route(
-                "/perm_for_user",
-                post(RolePermissionController::get_permissions_for_user),
-            )
-            .route(
-                "/roles_for_user",
-                post(RolePermissionController::get_roles_for_user),
-            )
-            .route(
-                "/user_roles",
-                post(RolePermissionController::add_user_roles),
-            )
-            .route(
-                "/role_permissions",
-                post(RolePermissionController::add_role_permissions),
-            )
-            .route(
-                "/delete_role_permission",
-                delete(RolePermissionController::delete_role_permission),
-            )
-            .route(
-                "/delete_user_permission",
-                delete(RolePermissionController::delete_user_permission),
-            ),
-    );
-    let products = Router::new().nest(
-        "/products",
-        Router::new()
-            .route(
-                "/",
-                get(ProductController::products).post(ProductController::create),
-            )
-            .route(
-                "/:id/user/:id",
-                get(ProductController::get)
-                    .post(ProductController::favorite_product)
-                    .delete(ProductController::un_favorite_product),
-            )
-            .route(
-                "/:id",
-                post(ProductController::update).delete(ProductController::delete),
-            ),
+        .route("/verify_email", post(AdminController::verify_email))
+        .route("/verify_email/resend", post(AdminController::resend_verify))
+        .route("/login", post(AdminController::login))
+        .route("/login/sms", post(AdminController::sms_login))
+        .route("/login/sms/code", post(AdminController::sms_code))
+        .route("/login/mfa", post(MfaController::login_verify))
+        .route("/login/mfa/setup", post(MfaController::login_setup))
+        .route("/login/oauth/:provider", post(IdentityController::login))
+        .route("/.well-known/jwks.json", get(CommController::jwks));
+    let guest_carts = Router::new().route(
+        "/guest/carts",
+        get(GuestCartController::carts)
+            .post(GuestCartController::add_cart)
+            .put(GuestCartController::update_cart)
+            .delete(GuestCartController::delete_carts),
     );
 
-    let orders = Router::new().nest(
-        "/orders",
-        Router::new()
-            .route(
-                "/",
-                get(OrderController::index).post(OrderController::store),
-            )
-            .route("/received/:id", post(OrderController::received))
-            .route("/ship", post(OrderController::ship))
-            .route(
-                "/evaluate/:id",
-                get(OrderController::evaluate_list).post(OrderController::evaluate),
-            )
-            .route(
-                "/payment/:id/installment",
-                post(OrderController::pay_by_installments),
-            )
-            .route(
-                "/installment/index",
-                get(OrderController::installment_index),
-            )
-            .route(
-                "/:id",
-                get(OrderController::get).post(OrderController::update),
-            ),
-    );
+    // 需要登录及鉴权的路由, 注册时登记权限标识、说明及分组
+    let users = CatalogRouter::new("用户管理")
+        .get("/", AdminController::lists, "users.index", "用户列表")
+        .post("/", AdminController::create, "users.create", "创建用户")
+        .get("/:id", AdminController::get, "users.show", "用户详情")
+        .put("/:id", AdminController::update, "users.update", "修改用户")
+        .delete("/:id", AdminController::delete, "users.delete", "删除用户")
+        .put(
+            "/:id/status",
+            AdminController::set_status,
+            "users.status",
+            "启用/禁用用户",
+        )
+        .post(
+            "/mfa/setup",
+            MfaController::setup,
+            "users.mfa_setup",
+            "生成两步验证密钥",
+        )
+        .post(
+            "/mfa/enable",
+            MfaController::enable,
+            "users.mfa_enable",
+            "开启两步验证",
+        )
+        .post(
+            "/mfa/disable",
+            MfaController::disable,
+            "users.mfa_disable",
+            "关闭两步验证",
+        )
+        .post(
+            "/mfa/recovery_codes",
+            MfaController::recovery_codes,
+            "users.mfa_recovery_codes",
+            "重新生成恢复码",
+        )
+        .get(
+            "/identities",
+            IdentityController::index,
+            "users.identities",
+            "第三方账号列表",
+        )
+        .post(
+            "/identities/:provider",
+            IdentityController::link,
+            "users.identity_link",
+            "绑定第三方账号",
+        )
+        .delete(
+            "/identities/:provider",
+            IdentityController::unlink,
+            "users.identity_unlink",
+            "解绑第三方账号",
+        )
+        .get(
+            "/login_events",
+            AdminController::login_events,
+            "users.login_events",
+            "登录记录",
+        )
+        .get(
+            "/sessions",
+            AdminController::sessions,
+            "users.sessions",
+            "登录会话列表",
+        )
+        .delete(
+            "/sessions/:sid",
+            AdminController::revoke_session,
+            "users.session_revoke",
+            "注销登录会话",
+        )
+        .get(
+            "/notifications",
+            AdminController::notifications,
+            "users.notifications",
+            "通知列表",
+        )
+        .post(
+            "/notifications/read",
+            AdminController::read_notifications,
+            "users.notifications_read",
+            "标记通知已读",
+        )
+        .get(
+            "/carts",
+            AdminController::carts,
+            "users.carts",
+            "购物车列表",
+        )
+        .post(
+            "/carts",
+            AdminController::add_cart,
+            "users.cart_add",
+            "加入购物车",
+        )
+        .delete(
+            "/carts",
+            AdminController::delete_carts,
+            "users.cart_delete",
+            "删除购物车商品",
+        );
 
-    let coupons = Router::new().nest(
-        "/coupons",
-        Router::new()
-            .route(
-                "/",
-                get(CouponController::index).post(CouponController::create),
-            )
-            .route(
-                "/:id",
-                get(CouponController::get)
-                    .post(CouponController::update)
-                    .delete(CouponController::delete),
-            )
-            .route("/:id/:code", post(CouponController::show)),
-    );
+    let address = CatalogRouter::new("地址管理")
+        .get(
+            "/",
+            AddressController::list_address,
+            "address.index",
+            "收货地址列表",
+        )
+        .post(
+            "/",
+            AddressController::create_address,
+            "address.create",
+            "添加收货地址",
+        )
+        .get(
+            "/result/:pid",
+            AddressController::addr_result,
+            "address.regions",
+            "省市区列表",
+        )
+        .get(
+            "/invoice_titles",
+            InvoiceController::titles,
+            "address.invoice_titles",
+            "发票抬头列表",
+        )
+        .post(
+            "/invoice_titles",
+            InvoiceController::create_title,
+            "address.invoice_title_create",
+            "添加发票抬头",
+        )
+        .put(
+            "/invoice_titles/:id",
+            InvoiceController::update_title,
+            "address.invoice_title_update",
+            "修改发票抬头",
+        )
+        .delete(
+            "/invoice_titles/:id",
+            InvoiceController::delete_title,
+            "address.invoice_title_delete",
+            "删除发票抬头",
+        )
+        .get(
+            "/:id",
+            AddressController::get_address,
+            "address.show",
+            "收货地址详情",
+        )
+        .put(
+            "/:id",
+            AddressController::update_address,
+            "address.update",
+            "修改收货地址",
+        )
+        .delete(
+            "/:id",
+            AddressController::delete_address,
+            "address.delete",
+            "删除收货地址",
+        );
 
-    let categories = Router::new().nest(
-        "/categories",
-        Router::new()
-            .route(
-                "/",
-                get(CategoriesController::index).post(CategoriesController::create),
-            )
-            .route(
-                "/:id",
-                get(CategoriesController::get)
-                    .patch(CategoriesController::update)
-                    .delete(CategoriesController::delete),
-            ),
+    let auth = CatalogRouter::new("权限管理")
+        .post(
+            "/perm_for_role",
+            RolePermissionController::get_permissions_for_role,
+            "auth.perm_for_role",
+            "查询角色的权限规则",
+        )
+        .post(
+            "/perm_for_user",
+            RolePermissionController::get_permissions_for_user,
+            "auth.perm_for_user",
+            "查询用户的权限规则",
+        )
+        .post(
+            "/roles_for_user",
+            RolePermissionController::get_roles_for_user,
+            "auth.roles_for_user",
+            "查询用户的角色",
+        )
+        .post(
+            "/user_roles",
+            RolePermissionController::add_user_roles,
+            "auth.user_roles",
+            "分配用户角色",
+        )
+        .post(
+            "/role_permissions",
+            RolePermissionController::add_role_permissions,
+            "auth.role_permissions",
+            "添加角色权限规则",
+        )
+        .delete(
+            "/delete_role_permission",
+            RolePermissionController::delete_role_permission,
+            "auth.role_permission_delete",
+            "删除角色权限规则",
+        )
+        .delete(
+            "/delete_user_permission",
+            RolePermissionController::delete_user_permission,
+            "auth.user_permission_delete",
+            "删除用户权限规则",
+        )
+        .get("/roles", RoleController::index, "roles.index", "角色列表")
+        .post("/roles", RoleController::create, "roles.create", "创建角色")
+        .put(
+            "/roles/:id",
+            RoleController::update,
+            "roles.update",
+            "修改角色",
+        )
+        .delete(
+            "/roles/:id",
+            RoleController::delete,
+            "roles.delete",
+            "删除角色",
+        )
+        .get(
+            "/roles/:id/users",
+            RoleController::users,
+            "roles.users",
+            "角色的用户",
+        )
+        .get(
+            "/roles/:id/permissions",
+            RoleController::permissions,
+            "roles.permissions",
+            "角色的权限",
+        )
+        .put(
+            "/roles/:id/permissions",
+            RoleController::set_permissions,
+            "roles.set_permissions",
+            "设置角色权限",
+        )
+        .get(
+            "/permissions",
+            RoleController::catalog,
+            "permissions.index",
+            "权限目录",
+        )
+        .post(
+            "/permissions",
+            RoleController::create_permission,
+            "permissions.create",
+            "添加权限",
+        )
+        .get(
+            "/permissions/stale",
+            RoleController::stale_permissions,
+            "permissions.stale",
+            "失效的权限及规则",
+        )
+        .put(
+            "/permissions/:id",
+            RoleController::update_permission,
+            "permissions.update",
+            "修改权限",
+        )
+        .delete(
+            "/permissions/:id",
+            RoleController::delete_permission,
+            "permissions.delete",
+            "删除权限",
+        )
+        .get(
+            "/users/:id/permissions",
+            RoleController::user_permissions,
+            "permissions.user",
+            "用户的最终权限",
+        )
+        .get("/menus", MenuController::tree, "menus.index", "菜单树")
+        .post("/menus", MenuController::create, "menus.create", "创建菜单")
+        .get(
+            "/menus/mine",
+            MenuController::mine,
+            "menus.mine",
+            "当前用户的菜单",
+        )
+        .put(
+            "/menus/:id",
+            MenuController::update,
+            "menus.update",
+            "修改菜单",
+        )
+        .delete(
+            "/menus/:id",
+            MenuController::delete,
+            "menus.delete",
+            "删除菜单",
+        );
+
+    let products = CatalogRouter::new("商品管理")
+        .get(
+            "/",
+            ProductController::products,
+            "products.index",
+            "商品列表",
+        )
+        .post(
+            "/",
+            ProductController::create,
+            "products.create",
+            "创建商品",
+        )
+        .post(
+            "/import",
+            ProductController::import,
+            "products.import",
+            "导入商品",
+        )
+        .get(
+            "/export",
+            ProductController::export,
+            "products.export",
+            "导出商品",
+        )
+        .get(
+            "/:id/revisions",
+            ProductController::revisions,
+            "products.revisions",
+            "商品修改记录",
+        )
+        .get(
+            "/:id/revisions/diff",
+            ProductController::revision_diff,
+            "products.revision_diff",
+            "对比商品修改记录",
+        )
+        .post(
+            "/:id/revisions/:revision_id/restore",
+            ProductController::restore_revision,
+            "products.revision_restore",
+            "恢复商品修改记录",
+        )
+        .get(
+            "/:id/schedules",
+            ProductController::schedules,
+            "products.schedules",
+            "商品定时任务列表",
+        )
+        .post(
+            "/:id/schedules",
+            ProductController::create_schedule,
+            "products.schedule_create",
+            "创建商品定时任务",
+        )
+        .delete(
+            "/:id/schedules/:schedule_id",
+            ProductController::cancel_schedule,
+            "products.schedule_cancel",
+            "取消商品定时任务",
+        )
+        .put(
+            "/:id/alerts",
+            ProductController::favorite_alerts,
+            "products.favorite_alerts",
+            "设置收藏提醒",
+        )
+        .get(
+            "/:id/user/:id",
+            ProductController::get,
+            "products.show",
+            "商品详情",
+        )
+        .post(
+            "/:id/user/:id",
+            ProductController::favorite_product,
+            "products.favorite",
+            "收藏商品",
+        )
+        .delete(
+            "/:id/user/:id",
+            ProductController::un_favorite_product,
+            "products.un_favorite",
+            "取消收藏商品",
+        )
+        .post(
+            "/:id",
+            ProductController::update,
+            "products.update",
+            "修改商品",
+        )
+        .delete(
+            "/:id",
+            ProductController::delete,
+            "products.delete",
+            "删除商品",
+        );
+
+    let orders = CatalogRouter::new("订单管理")
+        .get("/", OrderController::index, "orders.index", "订单列表")
+        .post("/", OrderController::store, "orders.store", "创建订单")
+        .post(
+            "/checkout",
+            OrderController::checkout,
+            "orders.checkout",
+            "订单结算",
+        )
+        .post(
+            "/received/:id",
+            OrderController::received,
+            "orders.received",
+            "确认收货",
+        )
+        .post("/ship", OrderController::ship, "orders.ship", "订单发货")
+        .get(
+            "/evaluate/:id",
+            OrderController::evaluate_list,
+            "orders.evaluate_list",
+            "订单评价列表",
+        )
+        .post(
+            "/evaluate/:id",
+            OrderController::evaluate,
+            "orders.evaluate",
+            "评价订单",
+        )
+        .post(
+            "/evaluate/:id/follow_up",
+            OrderController::follow_up,
+            "orders.follow_up",
+            "追加评价",
+        )
+        .post(
+            "/payment/:id/installment",
+            OrderController::pay_by_installments,
+            "orders.installment_pay",
+            "分期付款",
+        )
+        .get(
+            "/installment/index",
+            OrderController::installment_index,
+            "orders.installments",
+            "分期付款列表",
+        )
+        .get(
+            "/:id/shipments",
+            OrderController::shipments,
+            "orders.shipments",
+            "订单物流",
+        )
+        .get(
+            "/:id/allocations",
+            OrderController::allocations,
+            "orders.allocations",
+            "订单发货仓库",
+        )
+        .get(
+            "/:id/invoice",
+            InvoiceController::download,
+            "orders.invoice_download",
+            "下载发票",
+        )
+        .post(
+            "/:id/invoice",
+            InvoiceController::apply,
+            "orders.invoice_apply",
+            "申请发票",
+        )
+        .get("/:id", OrderController::get, "orders.show", "订单详情")
+        .post("/:id", OrderController::update, "orders.update", "修改订单");
+
+    let coupons = CatalogRouter::new("优惠券管理")
+        .get("/", CouponController::index, "coupons.index", "优惠券列表")
+        .post(
+            "/",
+            CouponController::create,
+            "coupons.create",
+            "创建优惠券",
+        )
+        .get("/:id", CouponController::get, "coupons.show", "优惠券详情")
+        .post(
+            "/:id",
+            CouponController::update,
+            "coupons.update",
+            "修改优惠券",
+        )
+        .delete(
+            "/:id",
+            CouponController::delete,
+            "coupons.delete",
+            "删除优惠券",
+        )
+        .post(
+            "/:id/:code",
+            CouponController::show,
+            "coupons.check",
+            "校验优惠码",
+        );
+
+    let categories = CatalogRouter::new("商品分类")
+        .get(
+            "/",
+            CategoriesController::index,
+            "categories.index",
+            "分类列表",
+        )
+        .post(
+            "/",
+            CategoriesController::create,
+            "categories.create",
+            "创建分类",
+        )
+        .get(
+            "/tree",
+            CategoriesController::tree,
+            "categories.tree",
+            "分类树",
+        )
+        .get(
+            "/batch",
+            CategoriesController::batch,
+            "categories.batch",
+            "批量查询分类",
+        )
+        .post(
+            "/sort",
+            CategoriesController::sort,
+            "categories.sort",
+            "分类排序",
+        )
+        .post(
+            "/rebuild_path",
+            CategoriesController::rebuild_path,
+            "categories.rebuild_path",
+            "重建分类路径",
+        )
+        .patch(
+            "/:id/move",
+            CategoriesController::move_to,
+            "categories.move",
+            "移动分类",
+        )
+        .get(
+            "/:id",
+            CategoriesController::get,
+            "categories.show",
+            "分类详情",
+        )
+        .patch(
+            "/:id",
+            CategoriesController::update,
+            "categories.update",
+            "修改分类",
+        )
+        .delete(
+            "/:id",
+            CategoriesController::delete,
+            "categories.delete",
+            "删除分类",
+        );
+
+    let reviews = CatalogRouter::new("评价管理")
+        .get("/", ReviewController::index, "reviews.index", "评价列表")
+        .patch(
+            "/:id/status",
+            ReviewController::moderate,
+            "reviews.moderate",
+            "审核评价",
+        )
+        .post(
+            "/:id/reply",
+            ReviewController::reply,
+            "reviews.reply",
+            "回复评价",
+        );
+
+    let invoices = CatalogRouter::new("发票管理")
+        .get("/", InvoiceController::index, "invoices.index", "发票列表")
+        .post(
+            "/:id/issue",
+            InvoiceController::issue,
+            "invoices.issue",
+            "开具发票",
+        )
+        .post(
+            "/:id/reject",
+            InvoiceController::reject,
+            "invoices.reject",
+            "驳回发票申请",
+        );
+
+    let warehouses = CatalogRouter::new("仓库管理")
+        .get(
+            "/",
+            WarehouseController::index,
+            "warehouses.index",
+            "仓库列表",
+        )
+        .post(
+            "/",
+            WarehouseController::create,
+            "warehouses.create",
+            "创建仓库",
+        )
+        .get(
+            "/report",
+            WarehouseController::report,
+            "warehouses.report",
+            "库存报表",
+        )
+        .post(
+            "/transfers",
+            WarehouseController::transfer,
+            "warehouses.transfer",
+            "库存调拨",
+        )
+        .put(
+            "/:id",
+            WarehouseController::update,
+            "warehouses.update",
+            "修改仓库",
+        )
+        .get(
+            "/:id/stocks",
+            WarehouseController::stocks,
+            "warehouses.stocks",
+            "仓库库存",
+        )
+        .put(
+            "/:id/stocks",
+            WarehouseController::adjust,
+            "warehouses.adjust",
+            "调整库存",
+        );
+
+    let suppliers = CatalogRouter::new("采购管理")
+        .get(
+            "/",
+            PurchaseController::suppliers,
+            "suppliers.index",
+            "供应商列表",
+        )
+        .post(
+            "/",
+            PurchaseController::create_supplier,
+            "suppliers.create",
+            "创建供应商",
+        )
+        .put(
+            "/:id",
+            PurchaseController::update_supplier,
+            "suppliers.update",
+            "修改供应商",
+        );
+    let purchase_orders = CatalogRouter::new("采购管理")
+        .get(
+            "/",
+            PurchaseController::index,
+            "purchase_orders.index",
+            "采购单列表",
+        )
+        .post(
+            "/",
+            PurchaseController::create,
+            "purchase_orders.create",
+            "创建采购单",
+        )
+        .get(
+            "/suggestions",
+            PurchaseController::suggestions,
+            "purchase_orders.suggestions",
+            "补货建议",
+        )
+        .get(
+            "/:id",
+            PurchaseController::get,
+            "purchase_orders.show",
+            "采购单详情",
+        )
+        .post(
+            "/:id/submit",
+            PurchaseController::submit,
+            "purchase_orders.submit",
+            "提交采购单",
+        )
+        .post(
+            "/:id/cancel",
+            PurchaseController::cancel,
+            "purchase_orders.cancel",
+            "取消采购单",
+        )
+        .post(
+            "/:id/receive",
+            PurchaseController::receive,
+            "purchase_orders.receive",
+            "采购入库",
+        );
+
+    let shipping_templates = CatalogRouter::new("运费模板")
+        .get(
+            "/",
+            ShippingTemplateController::index,
+            "shipping_templates.index",
+            "运费模板列表",
+        )
+        .post(
+            "/",
+            ShippingTemplateController::create,
+            "shipping_templates.create",
+            "创建运费模板",
+        )
+        .get(
+            "/:id",
+            ShippingTemplateController::get,
+            "shipping_templates.show",
+            "运费模板详情",
+        )
+        .put(
+            "/:id",
+            ShippingTemplateController::update,
+            "shipping_templates.update",
+            "修改运费模板",
+        )
+        .delete(
+            "/:id",
+            ShippingTemplateController::delete,
+            "shipping_templates.delete",
+            "删除运费模板",
+        )
+        .post(
+            "/:id/products",
+            ShippingTemplateController::products,
+            "shipping_templates.products",
+            "设置商品运费模板",
+        );
+
+    let after_sales = CatalogRouter::new("售后管理")
+        .get(
+            "/",
+            AfterSaleController::index,
+            "after_sales.index",
+            "售后申请列表",
+        )
+        .post(
+            "/",
+            AfterSaleController::apply,
+            "after_sales.apply",
+            "申请售后",
+        )
+        .get(
+            "/manage",
+            AfterSaleController::manage_index,
+            "after_sales.manage_index",
+            "售后管理列表",
+        )
+        .get(
+            "/manage/:id",
+            AfterSaleController::manage_get,
+            "after_sales.manage_show",
+            "售后管理详情",
+        )
+        .post(
+            "/manage/:id/review",
+            AfterSaleController::review,
+            "after_sales.review",
+            "审核售后",
+        )
+        .post(
+            "/manage/:id/inspect",
+            AfterSaleController::inspect,
+            "after_sales.inspect",
+            "售后验货",
+        )
+        .post(
+            "/manage/:id/refund",
+            AfterSaleController::refund,
+            "after_sales.refund",
+            "售后退款",
+        )
+        .post(
+            "/manage/:id/reship",
+            AfterSaleController::reship,
+            "after_sales.reship",
+            "售后补发",
+        )
+        .get(
+            "/:id",
+            AfterSaleController::get,
+            "after_sales.show",
+            "售后申请详情",
+        )
+        .post(
+            "/:id/cancel",
+            AfterSaleController::cancel,
+            "after_sales.cancel",
+            "取消售后",
+        )
+        .post(
+            "/:id/ship_back",
+            AfterSaleController::ship_back,
+            "after_sales.ship_back",
+            "售后寄回",
+        );
+
+    let (protected, permissions) = CatalogRouter::new("系统")
+        .post(
+            "/refresh_token",
+            CommController::refresh_token,
+            "system.refresh_token",
+            "刷新令牌",
+        )
+        .post(
+            "/logout",
+            AdminController::logout,
+            "system.logout",
+            "退出登录",
+        )
+        .post(
+            "/logout/all",
+            AdminController::logout_all,
+            "system.logout_all",
+            "退出全部设备",
+        )
+        .post(
+            "/test_mq",
+            CommController::test_mq,
+            "system.test_mq",
+            "测试消息队列",
+        )
+        .nest("/users", users)
+        .nest("/address", address)
+        .nest("/auth", auth)
+        .nest("/products", products)
+        .nest("/orders", orders)
+        .nest("/coupons", coupons)
+        .nest("/categories", categories)
+        .nest("/reviews", reviews)
+        .nest("/shipping_templates", shipping_templates)
+        .nest("/after_sales", after_sales)
+        .nest("/invoices", invoices)
+        .nest("/warehouses", warehouses)
+        .nest("/suppliers", suppliers)
+        .nest("/purchase_orders", purchase_orders)
+        .into_parts();
+    route_catalog::register(
+        permissions
+            .into_iter()
+            .map(|mut permission| {
+                permission.path = route_catalog::join_path(CATALOG_PREFIX, &permission.path);
+                permission
+            })
+            .collect(),
     );
 
     Router::new().nest(
         "/admin",
-        Router::new()
-            .route("/refresh_token", post(CommController::refresh_token))
-            .route("/test_mq", post(CommController::test_mq))
-            .merge(users)
-            .merge(address)
-            .merge(auth)
-            .merge(products)
-            .merge(orders)
-            .merge(coupons)
-            .merge(categories)
+        protected
             .layer(
                 ServiceBuilder::new()
                     .layer(AxumMiddleware::from_fn(middleware::auth_guard))
                     .layer(CasbinAuthLayer)
                     .layer(common::casbin::casbin_layer().await),
             )
-            .merge(login),
+            .merge(login)
+            .merge(guest_carts),
     )
 }
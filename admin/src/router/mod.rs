@@ -1,5 +1,7 @@
+use axum::extract::DefaultBodyLimit;
 use axum::routing::{get, post};
 use axum::Router;
+use common::picture::IMAGE_MAX_SIZE;
 
 use crate::controller::CommController;
 
@@ -12,7 +14,10 @@ pub async fn routers() -> Router {
         Router::new()
             .route("/test_redis", post(CommController::test_redis))
             .route("/get_config", post(CommController::get_application))
-            .route("/upload/files", post(CommController::upload_file))
+            .route(
+                "/upload/files",
+                post(CommController::upload_file).layer(DefaultBodyLimit::max(IMAGE_MAX_SIZE * 2)),
+            )
             .route("/public/:path", get(CommController::show_image))
             .route("/debug/:param", get(CommController::debug))
             .merge(admin::admin().await)
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use async_once::AsyncOnce;
@@ -10,7 +11,16 @@ use serde_yaml::value::Value;
 use tokio::sync::RwLock;
 use tokio::time::Duration;
 
+use crate::cookie::CookieConfig;
 use crate::error::{ApiError, ApiResult};
+use crate::invoice_pdf::InvoiceConfig;
+use crate::jwt::{JwtConfig, JWT};
+use crate::login_guard::LoginGuardConfig;
+use crate::oauth::OAuthConfig;
+use crate::pwd::PasswordConfig;
+use crate::sms::SmsConfig;
+use crate::storage::StorageConfig;
+use crate::totp::MfaConfig;
 
 lazy_static! {
     pub static ref APP_CONFIG: AsyncOnce<RwLock<Arc<Application>>> = AsyncOnce::new(async {
@@ -46,6 +56,8 @@ pub struct Application {
     pub port: u16,
     // 配置文件更新频率
     pub update_frequency: u16,
+    // 可信的反向代理地址
+    pub trusted_proxies: Vec<IpAddr>,
     pub postgres: PostgresConfig,
     pub redis: RedisConfig,
     pub rabbitmq: RabbitMQConfig,
@@ -54,6 +66,16 @@ pub struct Application {
     pub installment_fine_rate: f32,
     pub alipay: AlipayConfig,
     pub elasticsearch: ElasticsearchConfig,
+    pub storage: StorageConfig,
+    pub shipment: ShipmentConfig,
+    pub invoice: InvoiceConfig,
+    pub password: PasswordConfig,
+    pub jwt: JwtConfig,
+    pub cookie: CookieConfig,
+    pub mfa: MfaConfig,
+    pub sms: SmsConfig,
+    pub oauth: OAuthConfig,
+    pub login_guard: LoginGuardConfig,
 }
 
 #[async_trait]
@@ -86,10 +108,11 @@ impl Application {
     pub async fn init() -> ApiResult<Self> {
         let cfg = Application::file_get_content().await?;
 
-        Ok(Application {
+        Application {
             host: Self::analysis::<String>("host", &cfg)?,
             port: Self::analysis::<u16>("port", &cfg)?,
             update_frequency: Self::analysis::<u16>("profile_refresh_rate", &cfg)?,
+            trusted_proxies: Self::analysis::<Vec<IpAddr>>("trusted_proxies", &cfg)?,
             postgres: Self::analysis::<PostgresConfig>("postgres", &cfg)?,
             redis: Self::analysis::<RedisConfig>("redis", &cfg)?,
             rabbitmq: Self::analysis::<RabbitMQConfig>("rabbit", &cfg)?,
@@ -98,7 +121,29 @@ impl Application {
             installment_fine_rate: Self::analysis::<f32>("installment_fine_rate", &cfg)?,
             alipay: Self::analysis::<AlipayConfig>("alipay", &cfg)?,
             elasticsearch: Self::analysis::<ElasticsearchConfig>("elasticsearch", &cfg)?,
-        })
+            storage: Self::analysis::<StorageConfig>("storage", &cfg)?,
+            shipment: Self::analysis::<ShipmentConfig>("shipment", &cfg)?,
+            invoice: Self::analysis::<InvoiceConfig>("invoice", &cfg)?,
+            password: Self::analysis::<PasswordConfig>("password", &cfg)?,
+            jwt: Self::analysis::<JwtConfig>("jwt", &cfg)?,
+            cookie: Self::analysis::<CookieConfig>("cookie", &cfg)?,
+            mfa: Self::analysis::<MfaConfig>("mfa", &cfg)?,
+            sms: Self::analysis::<SmsConfig>("sms", &cfg)?,
+            oauth: Self::analysis::<OAuthConfig>("oauth", &cfg)?,
+            login_guard: Self::analysis::<LoginGuardConfig>("login_guard", &cfg)?,
+        }
+        .check()
+    }
+
+    // 密钥未配置时不能启动, 避免使用示例配置中的默认值
+    fn check(self) -> ApiResult<Self> {
+        if self.cookie.key().is_empty() {
+            return Err(ApiError::Error("cookie.secret 未配置".to_string()));
+        }
+        JWT::new(&self.jwt)?;
+        crate::login_guard::from_config(&self.login_guard.captcha)?;
+
+        Ok(self)
     }
 
     fn analysis<T: DeserializeOwned>(key: &str, value: &Value) -> ApiResult<T> {
@@ -193,6 +238,13 @@ pub struct ElasticsearchConfig {
     pub password: String,
 }
 
+/// 物流配置
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShipmentConfig {
+    // 签收后自动确认收货天数
+    pub auto_receive_days: u16,
+}
+
 #[cfg(test)]
 mod test {
     use crate::application::Application;
@@ -210,6 +210,78 @@ impl From<lapin::Error> for ApiError {
     }
 }
 
+impl From<csv::Error> for ApiError {
+    fn from(value: csv::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<calamine::XlsxError> for ApiError {
+    fn from(value: calamine::XlsxError) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<rust_xlsxwriter::XlsxError> for ApiError {
+    fn from(value: rust_xlsxwriter::XlsxError) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<image::ImageError> for ApiError {
+    fn from(value: image::ImageError) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<s3::error::S3Error> for ApiError {
+    fn from(value: s3::error::S3Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<printpdf::Error> for ApiError {
+    fn from(value: printpdf::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<rsa::pkcs8::spki::Error> for ApiError {
+    fn from(value: rsa::pkcs8::spki::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<argon2::Error> for ApiError {
+    fn from(value: argon2::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<argon2::password_hash::Error> for ApiError {
+    fn from(value: argon2::password_hash::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(value: reqwest::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
+impl From<pay::PayError> for ApiError {
+    fn from(value: pay::PayError) -> Self {
+        ApiError::Error(format!("{:?}", value))
+    }
+}
+
+impl From<casbin::Error> for ApiError {
+    fn from(value: casbin::Error) -> Self {
+        ApiError::Error(value.to_string())
+    }
+}
+
 struct ApiVisitor;
 
 impl<'de> Visitor<'de> for ApiVisitor {
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqAfterSale {
+    #[validate(required, range(min = 1, message = "订单ID错误"))]
+    pub order_id: Option<i64>,
+    #[validate(required, range(min = 1, message = "订单详情ID错误"))]
+    pub order_item_id: Option<i64>,
+    // 1: 退货退款, 2: 换货
+    #[validate(required, range(min = 1, max = 2, message = "售后类型错误"))]
+    pub r#type: Option<u8>,
+    #[validate(required, range(min = 1, max = 10000, message = "售后数量错误"))]
+    pub amount: Option<i16>,
+    #[validate(
+        required,
+        length(min = 4, max = 255, message = "售后原因必须在4-255字符之间")
+    )]
+    pub reason: Option<String>,
+    #[validate(length(max = 9, message = "最多上传9张图片"))]
+    pub photos: Option<Vec<String>>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqAfterSaleShipBack {
+    #[validate(
+        required,
+        length(min = 2, max = 100, message = "公司名称必须在2-100字符之间")
+    )]
+    pub company: Option<String>,
+    #[validate(
+        required,
+        length(min = 4, max = 100, message = "运单号必须在4-100字符之间")
+    )]
+    pub tracking_no: Option<String>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqAfterSaleReview {
+    #[validate(required)]
+    pub approved: Option<bool>,
+    #[validate(length(max = 255, message = "备注不能超过255个字符"))]
+    pub remark: Option<String>,
+    // 退款金额, 不填时按商品金额全额退款
+    #[validate(range(min = 0.01, message = "退款金额最低为0.01元"))]
+    pub refund_amount: Option<f64>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqAfterSaleInspect {
+    #[validate(required)]
+    pub passed: Option<bool>,
+    #[validate(length(max = 255, message = "备注不能超过255个字符"))]
+    pub remark: Option<String>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqAfterSaleReship {
+    #[validate(
+        required,
+        length(min = 2, max = 100, message = "公司名称必须在2-100字符之间")
+    )]
+    pub company: Option<String>,
+    // 物流公司编码, 用于查询物流轨迹
+    pub carrier: Option<String>,
+    #[validate(
+        required,
+        length(min = 4, max = 100, message = "运单号必须在4-100字符之间")
+    )]
+    pub tracking_no: Option<String>,
+}
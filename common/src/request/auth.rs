@@ -22,3 +22,75 @@ pub struct ReqRolePermissions {
     #[validate(required)]
     pub domain: Option<String>,
 }
+
+/// 角色, name 为 casbin 中的角色标识, 创建后不能修改
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct ReqRole {
+    #[validate(length(min = 2, max = 50, message = "角色标识必须在2-50字符之间"))]
+    pub name: Option<String>,
+    #[validate(
+        required,
+        length(min = 1, max = 50, message = "角色名称必须在1-50字符之间")
+    )]
+    pub display_name: Option<String>,
+    #[validate(length(max = 255, message = "描述不能超过255字符"))]
+    pub description: Option<String>,
+    // 继承的角色标识
+    pub inherits: Option<Vec<String>>,
+}
+
+/// 权限目录, 对应一个路由及请求方式
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct ReqPermission {
+    #[validate(
+        required,
+        length(min = 2, max = 100, message = "权限标识必须在2-100字符之间")
+    )]
+    pub name: Option<String>,
+    #[validate(
+        required,
+        length(min = 1, max = 50, message = "权限名称必须在1-50字符之间")
+    )]
+    pub display_name: Option<String>,
+    // 分组, 用于前端展示
+    #[validate(length(max = 50, message = "分组不能超过50字符"))]
+    pub group_name: Option<String>,
+    // 路由, 支持 :id 形式的参数
+    #[validate(required, length(min = 1, max = 255, message = "路由必须在1-255字符之间"))]
+    pub path: Option<String>,
+    // 请求方式, 支持正则如 (GET)|(POST)
+    #[validate(required, length(min = 1, max = 50, message = "请求方式必须在1-50字符之间"))]
+    pub method: Option<String>,
+}
+
+/// 设置角色权限, 会覆盖原有权限
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct ReqRoleGrant {
+    #[validate(required)]
+    pub permission_ids: Option<Vec<i64>>,
+}
+
+/// 菜单
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct ReqMenu {
+    pub parent_id: Option<i64>,
+    #[validate(
+        required,
+        length(min = 1, max = 50, message = "菜单名称必须在1-50字符之间")
+    )]
+    pub name: Option<String>,
+    // 前端路由
+    #[validate(length(max = 255, message = "路由不能超过255字符"))]
+    pub path: Option<String>,
+    #[validate(length(max = 100, message = "图标不能超过100字符"))]
+    pub icon: Option<String>,
+    pub sort: Option<i32>,
+    // 需要的权限, 为空时所有管理员可见
+    pub permission_id: Option<i64>,
+}
+
+/// 域名, 不传时使用默认域名
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReqDomain {
+    pub domain: Option<String>,
+}
@@ -14,3 +14,18 @@ pub struct UpdateCategories {
     #[validate(length(min = 2, max = 30, message = "内部名称必须在2-30个字符之间"))]
     pub name: Option<String>,
 }
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct ReqMoveCategory {
+    #[validate(range(min = 0, message = "父级类目不存在"))]
+    pub parent_id: Option<i64>,
+    pub sort: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct ReqSortCategories {
+    #[validate(range(min = 0, message = "父级类目不存在"))]
+    pub parent_id: Option<i64>,
+    #[validate(length(min = 1, message = "请选择需要排序的类目"))]
+    pub ids: Option<Vec<i64>>,
+}
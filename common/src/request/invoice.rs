@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqInvoiceTitle {
+    // 1: 个人, 2: 企业
+    #[validate(required, range(min = 1, max = 2, message = "抬头类型错误"))]
+    pub r#type: Option<u8>,
+    #[validate(
+        required,
+        length(min = 2, max = 100, message = "发票抬头必须在2-100字符之间")
+    )]
+    pub title: Option<String>,
+    // 企业抬头必填
+    #[validate(length(min = 15, max = 20, message = "税号必须在15-20字符之间"))]
+    pub tax_no: Option<String>,
+    #[validate(length(max = 255, message = "注册地址不能超过255字符"))]
+    pub address: Option<String>,
+    #[validate(length(max = 30, message = "注册电话不能超过30字符"))]
+    pub phone: Option<String>,
+    #[validate(length(max = 100, message = "开户银行不能超过100字符"))]
+    pub bank_name: Option<String>,
+    #[validate(length(max = 50, message = "银行账号不能超过50字符"))]
+    pub bank_account: Option<String>,
+    // 接收电子发票的邮箱
+    #[validate(email(message = "邮箱格式错误"))]
+    pub email: Option<String>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqInvoiceApply {
+    #[validate(required, range(min = 1, message = "请选择发票抬头"))]
+    pub title_id: Option<i64>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqInvoiceReject {
+    #[validate(
+        required,
+        length(min = 2, max = 255, message = "拒绝原因必须在2-255字符之间")
+    )]
+    pub reason: Option<String>,
+}
@@ -1,6 +1,11 @@
 pub mod address;
+pub mod after_sale;
 pub mod auth;
 pub mod categories;
 pub mod coupon;
+pub mod invoice;
 pub mod order;
+pub mod purchase;
+pub mod shipping;
 pub mod user;
+pub mod warehouse;
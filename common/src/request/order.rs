@@ -16,6 +16,23 @@ pub struct ReqCreateOrder {
     pub coupon_code: Option<String>,
 }
 
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqCheckoutCart {
+    #[validate(length(
+        min = 1,
+        max = 50,
+        message = "请选择你需要结算的商品, 单次最多允许结算50个商品"
+    ))]
+    pub cart_ids: Option<Vec<i64>>,
+    #[validate(range(min = 1, message = "请选择收获地址"))]
+    pub address_id: Option<i64>,
+    #[validate(length(min = 0, max = 255, message = "备注信息不能超过255个字符"))]
+    pub remark: Option<String>,
+    pub coupon_code: Option<String>,
+    // 确认接受变动后的价格
+    pub confirm_price: Option<bool>,
+}
+
 #[derive(Validate, Deserialize, Serialize, Clone)]
 pub struct OrderProduct {
     #[validate(range(min = 1, message = "非法的商品"))]
@@ -34,6 +51,13 @@ pub struct OrderShip {
     pub express_company: Option<String>,
     #[validate(length(min = 4, max = 100, message = "公司名称必须在4-100字符之间"))]
     pub express_no: Option<String>,
+    // 物流公司编码, 用于查询物流轨迹
+    pub carrier: Option<String>,
+    // 包裹内的订单商品, 为空时包含所有未发货的商品
+    #[validate(length(max = 50, message = "单个包裹最多50个商品"))]
+    pub item_ids: Option<Vec<i64>>,
+    // 发货仓库, 未指定商品时包含该仓库分配的待发货商品
+    pub warehouse_id: Option<i64>,
 }
 
 #[derive(Validate, Deserialize, Serialize, Clone)]
@@ -42,10 +66,34 @@ pub struct OrderEvaluate {
     pub id: Option<i64>,
     #[validate(range(min = 1, message = "订单ID错误"))]
     pub order_id: Option<i64>,
-    #[validate(range(min = 1, message = "分数：1-10分之间哦"))]
+    #[validate(required, range(min = 1, max = 10, message = "分数：1-10分之间哦"))]
     pub score: Option<i8>,
-    #[validate(length(min = 4, max = 100, message = "评价内容必须在4-255字符之间"))]
+    #[validate(required, length(min = 4, max = 255, message = "评价内容必须在4-255字符之间"))]
     pub content: Option<String>,
+    #[validate(length(max = 9, message = "最多上传9张图片"))]
+    pub photos: Option<Vec<String>>,
+    // 匿名评价
+    pub anonymous: Option<bool>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqReviewFollowUp {
+    #[validate(required, length(min = 4, max = 255, message = "追评内容必须在4-255字符之间"))]
+    pub content: Option<String>,
+    #[validate(length(max = 9, message = "最多上传9张图片"))]
+    pub photos: Option<Vec<String>>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqReviewReply {
+    #[validate(required, length(min = 1, max = 255, message = "回复内容必须在1-255字符之间"))]
+    pub content: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ReqReviewStatus {
+    // pending: 待审核, approved: 通过, hidden: 隐藏
+    pub status: String,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqSupplier {
+    #[validate(
+        required,
+        length(min = 2, max = 100, message = "供应商名称必须在2-100字符之间")
+    )]
+    pub name: Option<String>,
+    #[validate(length(max = 30, message = "联系人不能超过30字符"))]
+    pub contact_name: Option<String>,
+    #[validate(length(max = 30, message = "联系电话不能超过30字符"))]
+    pub contact_phone: Option<String>,
+    #[validate(email(message = "邮箱格式错误"))]
+    pub email: Option<String>,
+    #[validate(length(max = 255, message = "地址不能超过255字符"))]
+    pub address: Option<String>,
+    // 采购周期(天)
+    #[validate(range(min = 0, max = 365, message = "采购周期在0-365天之间"))]
+    pub lead_days: Option<i32>,
+    pub enabled: Option<bool>,
+}
+
+/// 采购单明细
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PurchaseLine {
+    pub sku_id: i64,
+    pub quantity: i32,
+    // 采购单价(元)
+    pub unit_cost: f64,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqPurchaseOrder {
+    #[validate(required, range(min = 1, message = "请选择供应商"))]
+    pub supplier_id: Option<i64>,
+    // 收货仓库
+    #[validate(required, range(min = 1, message = "请选择收货仓库"))]
+    pub warehouse_id: Option<i64>,
+    #[validate(
+        required,
+        length(min = 1, max = 200, message = "采购明细在1-200条之间")
+    )]
+    pub lines: Option<Vec<PurchaseLine>>,
+    #[validate(length(max = 255, message = "备注不能超过255字符"))]
+    pub remark: Option<String>,
+}
+
+/// 收货明细
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReceiveLine {
+    pub line_id: i64,
+    pub quantity: i32,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqPurchaseReceive {
+    #[validate(
+        required,
+        length(min = 1, max = 200, message = "收货明细在1-200条之间")
+    )]
+    pub lines: Option<Vec<ReceiveLine>>,
+    #[validate(length(max = 255, message = "备注不能超过255字符"))]
+    pub remark: Option<String>,
+}
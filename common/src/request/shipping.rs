@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::freight::ShippingRule;
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqShippingTemplate {
+    #[validate(
+        required,
+        length(min = 2, max = 30, message = "模板名称必须在2-30字符之间")
+    )]
+    pub name: Option<String>,
+    // 1: 按重量, 2: 按件数
+    #[validate(required, range(min = 1, max = 2, message = "计费方式错误"))]
+    pub charge_type: Option<u8>,
+    #[validate(
+        required,
+        length(min = 1, max = 100, message = "运费规则在1-100条之间")
+    )]
+    pub rules: Option<Vec<ShippingRule>>,
+    // 默认模板, 未设置运费模板的商品使用
+    pub is_default: Option<bool>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqTemplateProducts {
+    #[validate(length(min = 1, max = 500, message = "单次最多设置500个商品"))]
+    pub product_ids: Vec<i64>,
+}
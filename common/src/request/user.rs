@@ -54,18 +54,90 @@ pub struct ReqLogin {
     pub email: Option<String>,
     #[validate(required)]
     pub password: Option<String>,
+    // 失败次数过多后需要的人机验证token
+    pub captcha: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct ReqRegister {
-    #[validate(email)]
+    #[validate(required, email(message = "邮箱格式错误"))]
     pub email: Option<String>,
-    #[validate(length(min = 8, max = 255, message = "密码长度超过最大限制"))]
+    #[validate(
+        required,
+        length(min = 8, max = 255, message = "密码长度必须在8-255字符之间")
+    )]
     pub password: Option<String>,
-    #[validate(required)]
+    #[validate(required, must_match(other = "password", message = "两次密码不一致"))]
     pub confirm_password: Option<String>,
     #[validate(phone)]
     pub phone: Option<String>,
     #[validate(length(min = 6, max = 6, message = "验证码错误"))]
     pub code: Option<String>,
 }
+
+/// 邮箱验证
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqVerifyEmail {
+    #[validate(required, length(min = 32, max = 32, message = "验证链接无效"))]
+    pub token: Option<String>,
+}
+
+/// 重新发送验证邮件
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqResendVerify {
+    #[validate(required, email(message = "邮箱格式错误"))]
+    pub email: Option<String>,
+}
+
+/// 修改账号状态
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqUserStatus {
+    // 1: 正常, 2: 禁用, 3: 锁定
+    #[validate(required, range(min = 1, max = 3, message = "账号状态错误"))]
+    pub status: Option<u8>,
+    // 锁定时长(分钟), 为空时永久锁定直到手动解锁
+    #[validate(range(min = 1, message = "锁定时长错误"))]
+    pub locked_minutes: Option<i64>,
+}
+
+/// 两步验证码, 可以是验证器的6位验证码或恢复码
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqMfaCode {
+    #[validate(required, length(min = 6, max = 20, message = "验证码错误"))]
+    pub code: Option<String>,
+}
+
+/// 登录第二步
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqMfaLogin {
+    #[validate(
+        required,
+        length(min = 32, max = 32, message = "登录已过期, 请重新登录")
+    )]
+    pub mfa_token: Option<String>,
+    #[validate(length(min = 6, max = 20, message = "验证码错误"))]
+    pub code: Option<String>,
+}
+
+/// 发送短信验证码
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqSmsSend {
+    #[validate(required, phone(message = "手机号格式错误"))]
+    pub phone: Option<String>,
+}
+
+/// 短信验证码登录, 未注册的手机号自动注册
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqSmsLogin {
+    #[validate(required, phone(message = "手机号格式错误"))]
+    pub phone: Option<String>,
+    #[validate(required, length(min = 6, max = 6, message = "验证码错误"))]
+    pub code: Option<String>,
+}
+
+/// 第三方登录及绑定, code 为平台返回的授权码
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReqOAuthCode {
+    #[validate(required, length(min = 1, message = "授权码不能为空"))]
+    pub code: Option<String>,
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqWarehouse {
+    #[validate(
+        required,
+        length(min = 2, max = 50, message = "仓库名称必须在2-50字符之间")
+    )]
+    pub name: Option<String>,
+    #[validate(
+        required,
+        length(min = 2, max = 20, message = "仓库编码必须在2-20字符之间")
+    )]
+    pub code: Option<String>,
+    // 仓库所在省份
+    #[validate(required, range(min = 1, message = "请选择仓库所在省份"))]
+    pub province_id: Option<i32>,
+    // 相邻省份, 优先由该仓库发货
+    pub nearby: Option<Vec<i32>>,
+    // 同等距离时优先级高的仓库优先发货
+    pub priority: Option<i32>,
+    pub is_default: Option<bool>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqStockTransfer {
+    #[validate(required, range(min = 1, message = "sku错误"))]
+    pub sku_id: Option<i64>,
+    #[validate(required, range(min = 1, message = "调出仓库错误"))]
+    pub from_warehouse_id: Option<i64>,
+    #[validate(required, range(min = 1, message = "调入仓库错误"))]
+    pub to_warehouse_id: Option<i64>,
+    #[validate(required, range(min = 1, max = 1000000, message = "调拨数量错误"))]
+    pub amount: Option<i32>,
+    #[validate(length(max = 255, message = "备注不能超过255字符"))]
+    pub remark: Option<String>,
+}
+
+#[derive(Validate, Deserialize, Serialize, Clone)]
+pub struct ReqStockAdjust {
+    #[validate(required, range(min = 1, message = "sku错误"))]
+    pub sku_id: Option<i64>,
+    #[validate(required, range(min = 0, max = 1000000, message = "库存数量错误"))]
+    pub stock: Option<i32>,
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+/// 仓库库存, rank 越小距离收货地址越近
+#[derive(Debug, Clone, Default)]
+pub struct WarehouseStock {
+    pub id: i64,
+    pub rank: u32,
+    // sku_id => 库存
+    pub stock: HashMap<i64, i32>,
+}
+
+/// 仓库到收货省份的距离等级: 同省 0, 相邻省份 1, 其他 2
+pub fn rank(province_id: i32, warehouse_province: i32, nearby: &[i32]) -> u32 {
+    if province_id == warehouse_province {
+        0
+    } else if nearby.contains(&province_id) {
+        1
+    } else {
+        2
+    }
+}
+
+/// 订单商品行, 同一行只从一个仓库发货
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub item_id: i64,
+    pub sku_id: i64,
+    pub amount: i32,
+}
+
+// 仓库库存能整行满足的商品行, 同一sku的多行依次扣减库存
+fn coverable(warehouse: &WarehouseStock, lines: &[Line]) -> Vec<usize> {
+    let mut stock = warehouse.stock.clone();
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let rest = stock.get_mut(&line.sku_id)?;
+            if *rest < line.amount {
+                return None;
+            }
+            *rest -= line.amount;
+            Some(idx)
+        })
+        .collect()
+}
+
+/// 订单分仓: 优先由最近的单个仓库发货, 无法满足时按商品行拆分到多个仓库, 同一商品行不拆分
+///
+/// warehouses 需按优先级排序, 返回 仓库id => 商品行, 没有仓库能整行满足某个商品行时返回 None
+pub fn route(warehouses: &[WarehouseStock], lines: &[Line]) -> Option<Vec<(i64, Vec<Line>)>> {
+    let mut warehouses = warehouses.to_vec();
+    // 稳定排序, 同等级时保持传入的优先级顺序
+    warehouses.sort_by_key(|warehouse| warehouse.rank);
+
+    let mut remaining = lines
+        .iter()
+        .filter(|line| line.amount > 0)
+        .cloned()
+        .collect::<Vec<Line>>();
+    let mut result: Vec<(i64, Vec<Line>)> = vec![];
+    while !remaining.is_empty() {
+        // 整行满足最多的仓库, 整单满足时即为最近的可发货仓库
+        let (idx, picked) = warehouses
+            .iter()
+            .enumerate()
+            .map(|(idx, warehouse)| (idx, coverable(warehouse, &remaining)))
+            .filter(|(_, picked)| !picked.is_empty())
+            .max_by(|a, b| a.1.len().cmp(&b.1.len()).then(b.0.cmp(&a.0)))?;
+
+        let warehouse = &mut warehouses[idx];
+        let mut taken = vec![];
+        for (pos, line) in std::mem::take(&mut remaining).into_iter().enumerate() {
+            if picked.contains(&pos) {
+                *warehouse.stock.get_mut(&line.sku_id).unwrap() -= line.amount;
+                taken.push(line);
+            } else {
+                remaining.push(line);
+            }
+        }
+        match result.iter_mut().find(|(id, _)| *id == warehouse.id) {
+            Some((_, lines)) => lines.extend(taken),
+            None => result.push((warehouse.id, taken)),
+        }
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn warehouse(id: i64, rank: u32, stock: Vec<(i64, i32)>) -> WarehouseStock {
+        WarehouseStock {
+            id,
+            rank,
+            stock: stock.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn rank_province() {
+        assert_eq!(rank(440000, 440000, &[]), 0);
+        assert_eq!(rank(450000, 440000, &[450000, 430000]), 1);
+        assert_eq!(rank(110000, 440000, &[450000, 430000]), 2);
+    }
+
+    fn line(item_id: i64, sku_id: i64, amount: i32) -> Line {
+        Line {
+            item_id,
+            sku_id,
+            amount,
+        }
+    }
+
+    #[test]
+    fn nearest_single_warehouse() {
+        let warehouses = vec![
+            warehouse(1, 2, vec![(10, 5), (11, 5)]),
+            warehouse(2, 0, vec![(10, 1), (11, 5)]),
+            warehouse(3, 1, vec![(10, 5), (11, 5)]),
+        ];
+        let lines = vec![line(1, 10, 2), line(2, 11, 1)];
+
+        let result = route(&warehouses, &lines).unwrap();
+        assert_eq!(result, vec![(3, lines)]);
+    }
+
+    #[test]
+    fn split_by_line() {
+        let warehouses = vec![
+            warehouse(1, 0, vec![(10, 5)]),
+            warehouse(2, 2, vec![(11, 5), (12, 5)]),
+        ];
+        let lines = vec![line(1, 10, 2), line(2, 11, 1), line(3, 12, 1)];
+
+        let mut result = route(&warehouses, &lines).unwrap();
+        result.sort_by_key(|(id, _)| *id);
+        assert_eq!(
+            result,
+            vec![
+                (1, vec![line(1, 10, 2)]),
+                (2, vec![line(2, 11, 1), line(3, 12, 1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_not_split() {
+        let warehouses = vec![
+            warehouse(1, 1, vec![(10, 3)]),
+            warehouse(2, 0, vec![(10, 2)]),
+        ];
+
+        // 近的仓库库存不足时, 整行由远的仓库发货
+        assert_eq!(
+            route(&warehouses, &[line(1, 10, 3)]).unwrap(),
+            vec![(1, vec![line(1, 10, 3)])]
+        );
+        // 总库存足够, 但没有仓库能整行满足
+        assert!(route(&warehouses, &[line(1, 10, 4)]).is_none());
+    }
+
+    #[test]
+    fn same_sku_lines() {
+        let warehouses = vec![
+            warehouse(1, 0, vec![(10, 3)]),
+            warehouse(2, 1, vec![(10, 2)]),
+        ];
+        let lines = vec![line(1, 10, 2), line(2, 10, 2)];
+
+        // 同一sku的两行不能都由仓库1发货
+        let mut result = route(&warehouses, &lines).unwrap();
+        result.sort_by_key(|(id, _)| *id);
+        assert_eq!(
+            result,
+            vec![(1, vec![line(1, 10, 2)]), (2, vec![line(2, 10, 2)])]
+        );
+        assert!(route(
+            &warehouses,
+            &[line(1, 10, 2), line(2, 10, 2), line(3, 10, 2)]
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::async_trait;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ApiError, ApiResult};
+
+/// 物流轨迹状态
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize, sqlx::Type)]
+pub enum TrackingStatus {
+    // 已揽收
+    Collected = 1,
+    // 运输中
+    InTransit = 2,
+    // 派送中
+    Delivering = 3,
+    // 已签收
+    Delivered = 4,
+    // 异常
+    Exception = 5,
+}
+
+impl Default for TrackingStatus {
+    fn default() -> Self {
+        TrackingStatus::Collected
+    }
+}
+
+impl AsRef<str> for TrackingStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            TrackingStatus::Collected => "已揽收",
+            TrackingStatus::InTransit => "运输中",
+            TrackingStatus::Delivering => "派送中",
+            TrackingStatus::Delivered => "已签收",
+            TrackingStatus::Exception => "异常",
+        }
+    }
+}
+
+/// 物流轨迹
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackingEvent {
+    pub status: TrackingStatus,
+    pub description: String,
+    pub location: String,
+    pub occurred_at: chrono::NaiveDateTime,
+}
+
+/// 物流公司接口, 按运单号查询物流轨迹
+#[async_trait]
+pub trait Carrier: Send + Sync {
+    /// 物流公司编码
+    fn code(&self) -> &'static str;
+
+    async fn track(&self, tracking_no: &str) -> ApiResult<Vec<TrackingEvent>>;
+}
+
+lazy_static! {
+    static ref FAKE_CARRIER: Arc<FakeCarrier> = Arc::new(FakeCarrier::default());
+}
+
+/// 根据物流公司编码获取对应的接口
+pub fn carrier(code: &str) -> ApiResult<Arc<dyn Carrier>> {
+    match code {
+        "fake" => Ok(FAKE_CARRIER.clone()),
+        code => Err(ApiError::Error(format!("不支持的物流公司: {}", code))),
+    }
+}
+
+/// 本地模拟物流, 用于测试及开发环境
+#[derive(Default)]
+pub struct FakeCarrier {
+    events: Mutex<HashMap<String, Vec<TrackingEvent>>>,
+}
+
+impl FakeCarrier {
+    pub fn instance() -> Arc<FakeCarrier> {
+        FAKE_CARRIER.clone()
+    }
+
+    /// 添加物流轨迹
+    pub fn push(&self, tracking_no: &str, event: TrackingEvent) {
+        self.events
+            .lock()
+            .unwrap()
+            .entry(tracking_no.to_string())
+            .or_default()
+            .push(event);
+    }
+}
+
+#[async_trait]
+impl Carrier for FakeCarrier {
+    fn code(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn track(&self, tracking_no: &str) -> ApiResult<Vec<TrackingEvent>> {
+        Ok(self
+            .events
+            .lock()
+            .unwrap()
+            .get(tracking_no)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// 最新的物流状态, 按发生时间取最后一条
+pub fn latest_status(events: &[TrackingEvent]) -> Option<TrackingStatus> {
+    events
+        .iter()
+        .max_by_key(|event| event.occurred_at)
+        .map(|event| event.status)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(status: TrackingStatus, time: &str) -> TrackingEvent {
+        TrackingEvent {
+            status,
+            description: status.as_ref().to_string(),
+            location: "深圳".to_string(),
+            occurred_at: chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn fake_carrier() {
+        let fake = FakeCarrier::default();
+        fake.push(
+            "SF001",
+            event(TrackingStatus::Collected, "2023-05-01 10:00:00"),
+        );
+        fake.push(
+            "SF001",
+            event(TrackingStatus::InTransit, "2023-05-01 18:00:00"),
+        );
+
+        assert_eq!(fake.track("SF001").await.unwrap().len(), 2);
+        assert!(fake.track("SF002").await.unwrap().is_empty());
+        assert!(carrier("fake").is_ok());
+        assert!(carrier("unknown").is_err());
+    }
+
+    #[test]
+    fn latest() {
+        let events = vec![
+            event(TrackingStatus::Delivered, "2023-05-03 09:00:00"),
+            event(TrackingStatus::Collected, "2023-05-01 10:00:00"),
+            event(TrackingStatus::Delivering, "2023-05-02 08:00:00"),
+        ];
+
+        assert_eq!(latest_status(&events), Some(TrackingStatus::Delivered));
+        assert_eq!(latest_status(&[]), None);
+    }
+}
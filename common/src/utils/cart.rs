@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use crate::error::{ApiError, ApiResult};
+
+/// 购物车数量增减, 返回新的数量; 小于等于0时应移除该商品
+///
+/// 增加数量时不能超过库存, 减少数量时不校验库存
+pub fn change_amount(current: i16, delta: i32, stock: i32) -> ApiResult<i16> {
+    let amount = i16::try_from(delta)
+        .ok()
+        .and_then(|delta| current.checked_add(delta))
+        .ok_or(ApiError::Error("购买数量超出限制".to_string()))?;
+    if delta > 0 && amount as i32 > stock {
+        return Err(ApiError::Error(format!(
+            "商品库存不足, 当前库存: {}",
+            stock
+        )));
+    }
+
+    Ok(amount)
+}
+
+/// 合并同一sku的数量, 不超过库存
+pub fn merge_amount(current: i16, other: i16, stock: i32) -> i16 {
+    (current as i32 + other as i32)
+        .min(stock)
+        .min(i16::MAX as i32)
+        .max(0) as i16
+}
+
+/// 商品金额(分), 单价(元)先四舍五入到分再乘数量
+pub fn line_money(price: f64, amount: i64) -> i64 {
+    (price * 100.0).round() as i64 * amount
+}
+
+/// 结算的商品总金额(分), lines 为每行的 (sku id, 金额)
+///
+/// 订单按sku分行, 同一商品的不同sku各占一行; 同一sku重复出现时无法对应订单行及库存, 返回错误
+pub fn order_total(lines: &[(i64, i64)]) -> ApiResult<i64> {
+    let mut sku_ids = HashSet::new();
+    let mut total = 0i64;
+    for (sku_id, money) in lines.iter() {
+        if !sku_ids.insert(*sku_id) {
+            return Err(ApiError::Error("结算商品重复".to_string()));
+        }
+        total += money;
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn change_within_stock() {
+        assert_eq!(change_amount(2, 3, 10).unwrap(), 5);
+        assert_eq!(change_amount(2, 8, 10).unwrap(), 10);
+        assert!(change_amount(2, 9, 10).is_err());
+        // 减少数量不受库存限制
+        assert_eq!(change_amount(5, -3, 0).unwrap(), 2);
+        assert_eq!(change_amount(2, -3, 0).unwrap(), -1);
+    }
+
+    #[test]
+    fn change_overflow() {
+        assert!(change_amount(i16::MAX, 1, i32::MAX).is_err());
+        assert!(change_amount(1, 40000, i32::MAX).is_err());
+        assert!(change_amount(i16::MIN, -1, 0).is_err());
+    }
+
+    #[test]
+    fn merge_clamped() {
+        assert_eq!(merge_amount(2, 3, 10), 5);
+        assert_eq!(merge_amount(8, 5, 10), 10);
+        assert_eq!(merge_amount(i16::MAX, i16::MAX, i32::MAX), i16::MAX);
+        assert_eq!(merge_amount(1, 1, -5), 0);
+    }
+
+    #[test]
+    fn money_in_cents() {
+        assert_eq!(line_money(19.99, 1), 1999);
+        assert_eq!(line_money(19.99, 3), 5997);
+        assert_eq!(line_money(0.1 + 0.2, 1), 30);
+    }
+
+    #[test]
+    fn total_by_sku() {
+        // 同一商品的两个sku分别计入订单
+        let lines = [(11, line_money(19.99, 1)), (12, line_money(29.99, 2))];
+        assert_eq!(order_total(&lines).unwrap(), 7997);
+        assert!(order_total(&[(11, 1999), (11, 1999)]).is_err());
+        assert_eq!(order_total(&[]).unwrap(), 0);
+    }
+}
@@ -30,6 +30,20 @@ use tower::{Layer, Service};
 
 use crate::{error::ApiResult, ApiResponse, ConnPool};
 
+/// 默认域名
+pub const DEFAULT_DOMAIN: &str = "localhost";
+
+/// 超级管理员, 不受权限限制
+pub const SUPER_ADMIN: &str = "super_admin";
+
+/// 用户在 casbin 中的标识, 1 号用户为超级管理员
+pub fn subject(user_id: i64) -> String {
+    match user_id {
+        1 => SUPER_ADMIN.to_string(),
+        _ => format!("user:{}", user_id),
+    }
+}
+
 #[derive(Clone)]
 pub struct CasbinVals {
     pub subject: String,
@@ -0,0 +1,97 @@
+use axum::http::{header, HeaderMap};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 签名cookie配置
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CookieConfig {
+    // HMAC 密钥, 未配置时服务不能启动
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+}
+
+impl CookieConfig {
+    pub fn key(&self) -> &str {
+        self.secret.as_deref().unwrap_or_default()
+    }
+}
+
+/// 对cookie值签名, 格式: value.signature
+pub fn sign(secret: &str, value: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(value.as_bytes());
+
+    format!("{}.{:x}", value, mac.finalize().into_bytes())
+}
+
+/// 校验签名, 成功时返回原始值
+pub fn verify(secret: &str, signed: &str) -> Option<String> {
+    let (value, _) = signed.rsplit_once('.')?;
+    let expected = sign(secret, value);
+
+    // 定长比较, 避免时序攻击
+    if expected.len() != signed.len() {
+        return None;
+    }
+    let diff = expected
+        .bytes()
+        .zip(signed.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+
+    (diff == 0).then(|| value.to_string())
+}
+
+/// 读取请求中的cookie
+pub fn get(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// 读取并校验签名cookie
+pub async fn get_signed(headers: &HeaderMap, name: &str) -> Option<String> {
+    let cfg = crate::application_config().await;
+    get(headers, name).and_then(|value| verify(cfg.cookie.key(), &value))
+}
+
+/// 生成签名cookie的 Set-Cookie 值
+pub async fn set_signed(name: &str, value: &str, max_age: usize) -> String {
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        name,
+        sign(crate::application_config().await.cookie.key(), value),
+        max_age
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let signed = sign("secret", "ABC123");
+        assert_eq!(verify("secret", &signed), Some("ABC123".to_string()));
+        assert_eq!(verify("secret", &signed.replace("ABC", "ABD")), None);
+        assert_eq!(verify("secret", "ABC123"), None);
+        // 更换密钥后原签名失效
+        assert_eq!(verify("other", &signed), None);
+    }
+
+    #[test]
+    fn read_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "a=1; guest_cart=xyz".parse().unwrap());
+        assert_eq!(get(&headers, "guest_cart"), Some("xyz".to_string()));
+        assert_eq!(get(&headers, "b"), None);
+    }
+}
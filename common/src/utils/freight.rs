@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+
+/// 运费计费方式
+#[repr(i16)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum ChargeType {
+    // 按重量: 首重 + 续重
+    Weight = 1,
+    // 按件数: 首件 + 续件
+    Piece = 2,
+}
+
+impl Default for ChargeType {
+    fn default() -> Self {
+        ChargeType::Weight
+    }
+}
+
+impl TryFrom<u8> for ChargeType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ChargeType::Weight),
+            2 => Ok(ChargeType::Piece),
+            _ => Err(format!("不支持的计费方式: {}", value)),
+        }
+    }
+}
+
+/// 运费规则
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShippingRule {
+    // 适用地区(省、市、区的id), 为空时为默认规则
+    #[serde(default)]
+    pub regions: Vec<i32>,
+    // 首重(克)或首件
+    pub first: u32,
+    pub first_fee: f64,
+    // 续重(克)或续件
+    pub additional: u32,
+    pub additional_fee: f64,
+    // 包邮门槛金额, 0为不包邮
+    #[serde(default)]
+    pub free_amount: f64,
+}
+
+impl ShippingRule {
+    /// 计算运费(分), quantity 为总重量(克)或总件数, money 为商品金额(分)
+    pub fn fee(&self, quantity: u32, money: i64) -> i64 {
+        if self.free_amount > 0.0 && money >= (self.free_amount * 100.0).round() as i64 {
+            return 0;
+        }
+
+        let mut fee = (self.first_fee * 100.0).round() as i64;
+        if quantity > self.first && self.additional > 0 {
+            let times = (quantity - self.first + self.additional - 1) / self.additional;
+            fee += times as i64 * (self.additional_fee * 100.0).round() as i64;
+        }
+
+        fee
+    }
+}
+
+/// 订单中使用同一运费模板的商品合计
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Parcel {
+    // 总重量(克)
+    pub weight: u32,
+    // 总件数
+    pub pieces: u32,
+    // 商品金额(分)
+    pub money: i64,
+}
+
+impl Parcel {
+    /// 加入商品, weight 为单件重量(克)
+    pub fn add(&mut self, weight: i32, amount: i32, money: i64) {
+        let amount = amount.max(0) as u32;
+        self.weight = self
+            .weight
+            .saturating_add((weight.max(0) as u32).saturating_mul(amount));
+        self.pieces = self.pieces.saturating_add(amount);
+        self.money += money;
+    }
+
+    /// 按模板计算运费(分), 收货地区不在配送范围内时返回 None
+    pub fn fee(
+        &self,
+        charge_type: ChargeType,
+        rules: &[ShippingRule],
+        regions: &[i32],
+    ) -> Option<i64> {
+        let quantity = match charge_type {
+            ChargeType::Weight => self.weight,
+            ChargeType::Piece => self.pieces,
+        };
+
+        match_rule(rules, regions).map(|rule| rule.fee(quantity, self.money))
+    }
+}
+
+/// 匹配收货地区的运费规则, regions 按 区、市、省 的顺序传入, 越具体的地区优先
+pub fn match_rule<'a>(rules: &'a [ShippingRule], regions: &[i32]) -> Option<&'a ShippingRule> {
+    regions
+        .iter()
+        .filter(|region| **region > 0)
+        .find_map(|region| rules.iter().find(|rule| rule.regions.contains(region)))
+        .or_else(|| rules.iter().find(|rule| rule.regions.is_empty()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(regions: Vec<i32>, first_fee: f64) -> ShippingRule {
+        ShippingRule {
+            regions,
+            first: 1000,
+            first_fee,
+            additional: 500,
+            additional_fee: 2.5,
+            free_amount: 99.0,
+        }
+    }
+
+    #[test]
+    fn fee() {
+        let rule = rule(vec![], 10.0);
+        assert_eq!(rule.fee(800, 1000), 1000);
+        assert_eq!(rule.fee(1000, 1000), 1000);
+        assert_eq!(rule.fee(1001, 1000), 1250);
+        assert_eq!(rule.fee(2000, 1000), 1500);
+        // 满99包邮
+        assert_eq!(rule.fee(2000, 9900), 0);
+    }
+
+    #[test]
+    fn match_region() {
+        let rules = vec![
+            rule(vec![], 10.0),
+            rule(vec![440000], 8.0),
+            rule(vec![440300], 6.0),
+        ];
+
+        // 深圳市: 区 > 市 > 省
+        assert_eq!(
+            match_rule(&rules, &[440305, 440300, 440000])
+                .unwrap()
+                .first_fee,
+            6.0
+        );
+        // 广州市匹配广东省
+        assert_eq!(
+            match_rule(&rules, &[440106, 440100, 440000])
+                .unwrap()
+                .first_fee,
+            8.0
+        );
+        // 其他地区使用默认规则
+        assert_eq!(
+            match_rule(&rules, &[110105, 110100, 110000])
+                .unwrap()
+                .first_fee,
+            10.0
+        );
+        assert!(match_rule(&rules[1..], &[110105, 110100, 110000]).is_none());
+    }
+
+    #[test]
+    fn parcel_fee() {
+        let rules = vec![rule(vec![], 10.0), rule(vec![440000], 8.0)];
+        let regions = [440305, 440300, 440000];
+
+        // 两件商品合计 1600 克: 首重 8 元 + 续重 2 * 2.5 元
+        let mut parcel = Parcel::default();
+        parcel.add(300, 2, 2000);
+        parcel.add(1000, 1, 3000);
+        assert_eq!(parcel.weight, 1600);
+        assert_eq!(parcel.pieces, 3);
+        assert_eq!(parcel.fee(ChargeType::Weight, &rules, &regions), Some(1300));
+
+        // 按件计费: 件数未超过首件, 只收首件费用
+        assert_eq!(parcel.fee(ChargeType::Piece, &rules, &regions), Some(800));
+
+        // 合计金额达到包邮门槛
+        parcel.add(0, 1, 4900);
+        assert_eq!(parcel.fee(ChargeType::Weight, &rules, &regions), Some(0));
+
+        // 不在配送范围内
+        assert_eq!(
+            parcel.fee(ChargeType::Weight, &rules[1..], &[110105, 110100, 110000]),
+            None
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::io::Cursor;
+
+use printpdf::{Mm, PdfDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ApiError, ApiResult};
+
+/// 发票配置: 开票方信息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvoiceConfig {
+    pub company_name: String,
+    pub tax_no: String,
+    pub address: String,
+    pub phone: String,
+    pub bank: String,
+    // 税率, 如: 0.13
+    pub tax_rate: f64,
+    // 中文字体文件(ttf), 必须配置, 内置字体无法显示中文
+    pub font_path: Option<String>,
+}
+
+/// 发票明细
+#[derive(Debug, Clone)]
+pub struct InvoiceLine {
+    pub title: String,
+    pub amount: i64,
+    // 单价(分)
+    pub price: i64,
+}
+
+/// 发票内容
+#[derive(Debug, Clone)]
+pub struct InvoiceDocument {
+    pub no: String,
+    pub order_no: String,
+    // 购买方
+    pub buyer_title: String,
+    pub buyer_tax_no: String,
+    pub lines: Vec<InvoiceLine>,
+    // 含税总金额(分)
+    pub total: i64,
+    pub issued_at: chrono::NaiveDateTime,
+}
+
+/// 价税分离: 返回 (不含税金额, 税额), 单位: 分
+pub fn tax_breakdown(total: i64, rate: f64) -> (i64, i64) {
+    let net = (total as f64 / (1.0 + rate)).round() as i64;
+
+    (net, total - net)
+}
+
+/// 明细合计与发票总金额不一致时(优惠券、金额分摊尾差等), 补充一行优惠明细
+pub fn balance(lines: &mut Vec<InvoiceLine>, total: i64) {
+    let sum = lines
+        .iter()
+        .map(|line| line.price * line.amount)
+        .sum::<i64>();
+    if sum != total {
+        lines.push(InvoiceLine {
+            title: "优惠".to_string(),
+            amount: 1,
+            price: total - sum,
+        });
+    }
+}
+
+fn yuan(cents: i64) -> String {
+    format!("{:.2}", cents as f64 / 100.0)
+}
+
+/// 生成PDF发票
+pub fn render(cfg: &InvoiceConfig, invoice: &InvoiceDocument) -> ApiResult<Vec<u8>> {
+    let (doc, page, layer) = PdfDocument::new(
+        format!("Invoice {}", invoice.no),
+        Mm(210.0),
+        Mm(297.0),
+        "invoice",
+    );
+    let path = cfg
+        .font_path
+        .as_ref()
+        .filter(|path| !path.is_empty())
+        .ok_or(ApiError::Error(
+            "未配置发票中文字体(invoice.font_path), 不能开票".to_string(),
+        ))?;
+    let font = doc.add_external_font(Cursor::new(std::fs::read(path)?))?;
+    let layer = doc.get_page(page).get_layer(layer);
+
+    let (net, tax) = tax_breakdown(invoice.total, cfg.tax_rate);
+    let mut lines = vec![
+        ("电子发票 / INVOICE".to_string(), 18.0),
+        (format!("发票号码: {}", invoice.no), 10.0),
+        (format!("订单号: {}", invoice.order_no), 10.0),
+        (
+            format!("开票日期: {}", invoice.issued_at.format("%F")),
+            10.0,
+        ),
+        (String::new(), 10.0),
+        (format!("购买方: {}", invoice.buyer_title), 11.0),
+        (format!("纳税人识别号: {}", invoice.buyer_tax_no), 10.0),
+        (String::new(), 10.0),
+        ("商品名称 / 数量 / 单价 / 金额".to_string(), 11.0),
+    ];
+    for item in invoice.lines.iter() {
+        lines.push((
+            format!(
+                "{} / {} / {} / {}",
+                item.title,
+                item.amount,
+                yuan(item.price),
+                yuan(item.price * item.amount)
+            ),
+            10.0,
+        ));
+    }
+    lines.extend([
+        (String::new(), 10.0),
+        (format!("金额: {}", yuan(net)), 10.0),
+        (
+            format!("税率: {}%  税额: {}", cfg.tax_rate * 100.0, yuan(tax)),
+            10.0,
+        ),
+        (format!("价税合计: {}", yuan(invoice.total)), 11.0),
+        (String::new(), 10.0),
+        (format!("销售方: {}", cfg.company_name), 11.0),
+        (format!("纳税人识别号: {}", cfg.tax_no), 10.0),
+        (format!("地址、电话: {} {}", cfg.address, cfg.phone), 10.0),
+        (format!("开户行及账号: {}", cfg.bank), 10.0),
+    ]);
+
+    // 从页面顶部逐行输出
+    let mut y = 280.0;
+    for (text, size) in lines.iter() {
+        layer.use_text(text.as_str(), *size, Mm(20.0), Mm(y), &font);
+        y -= size * 0.6;
+    }
+
+    Ok(doc.save_to_bytes()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn breakdown() {
+        assert_eq!(tax_breakdown(11300, 0.13), (10000, 1300));
+        assert_eq!(tax_breakdown(10000, 0.0), (10000, 0));
+        let (net, tax) = tax_breakdown(999, 0.06);
+        assert_eq!(net + tax, 999);
+    }
+
+    #[test]
+    fn balance_lines() {
+        let mut lines = vec![
+            InvoiceLine {
+                title: "Phone".to_string(),
+                amount: 2,
+                price: 5650,
+            },
+            InvoiceLine {
+                title: "运费".to_string(),
+                amount: 1,
+                price: 1000,
+            },
+        ];
+        balance(&mut lines, 12300);
+        assert_eq!(lines.len(), 2);
+
+        // 使用优惠券后实付金额减少
+        balance(&mut lines, 11300);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2].price, -1000);
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| line.price * line.amount)
+                .sum::<i64>(),
+            11300
+        );
+    }
+
+    #[test]
+    fn render_without_font() {
+        let cfg = InvoiceConfig {
+            company_name: "Shop".to_string(),
+            tax_no: "91110000000000000X".to_string(),
+            address: "Beijing".to_string(),
+            phone: "010-00000000".to_string(),
+            bank: "Bank 6222".to_string(),
+            tax_rate: 0.13,
+            font_path: Some(String::new()),
+        };
+        let invoice = InvoiceDocument {
+            no: "INV001".to_string(),
+            order_no: "1001".to_string(),
+            buyer_title: "Buyer".to_string(),
+            buyer_tax_no: "91310000000000000Y".to_string(),
+            lines: vec![InvoiceLine {
+                title: "Phone".to_string(),
+                amount: 2,
+                price: 5650,
+            }],
+            total: 11300,
+            issued_at: chrono::Local::now().naive_local(),
+        };
+
+        // 内置字体无法显示中文, 未配置字体时不生成发票
+        assert!(render(&cfg, &invoice).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use std::net::IpAddr;
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{ApiError, ApiResult};
+
+/// 登录防护配置
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoginGuardConfig {
+    // 失败次数统计周期(秒)
+    pub window: usize,
+    // 同一账号失败多少次后锁定
+    pub account_max_failures: i64,
+    // 同一ip失败多少次后锁定
+    pub ip_max_failures: i64,
+    // 首次锁定时长(秒), 之后每失败一次翻倍
+    pub lock_seconds: usize,
+    // 最长锁定时长(秒)
+    pub max_lock_seconds: usize,
+    // 同一账号或ip失败多少次后需要人机验证
+    pub captcha_after: i64,
+    pub captcha: CaptchaConfig,
+}
+
+/// 人机验证配置
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CaptchaConfig {
+    // static: 固定验证码, 用于本地开发及测试; http: reCAPTCHA/hCaptcha/Turnstile 等 siteverify 接口
+    pub driver: String,
+    // static 驱动为验证码, http 驱动为服务端密钥
+    pub secret: String,
+    // http 驱动的校验地址
+    pub verify_url: Option<String>,
+}
+
+/// 人机验证接口
+#[async_trait]
+pub trait CaptchaVerifier: Send + Sync {
+    async fn verify(&self, token: &str, ip: &str) -> ApiResult<bool>;
+}
+
+/// 根据配置创建人机验证
+pub fn from_config(cfg: &CaptchaConfig) -> ApiResult<Box<dyn CaptchaVerifier>> {
+    if cfg.secret.is_empty() {
+        return Err(ApiError::Error(
+            "login_guard.captcha.secret 未配置".to_string(),
+        ));
+    }
+
+    match cfg.driver.as_str() {
+        "static" => Ok(Box::new(StaticCaptcha(cfg.secret.clone()))),
+        "http" => {
+            let verify_url =
+                cfg.verify_url
+                    .clone()
+                    .filter(|url| !url.is_empty())
+                    .ok_or(ApiError::Error(
+                        "login_guard.captcha.verify_url 配置不存在".to_string(),
+                    ))?;
+            Ok(Box::new(HttpCaptcha {
+                secret: cfg.secret.clone(),
+                verify_url,
+            }))
+        }
+        driver => Err(ApiError::Error(format!("不支持的人机验证驱动: {}", driver))),
+    }
+}
+
+/// 锁定时长(秒), 达到次数后首次锁定 base 秒, 之后每失败一次翻倍, 不超过 max
+pub fn lock_seconds(failures: i64, max_failures: i64, base: usize, max: usize) -> usize {
+    if max_failures <= 0 || failures < max_failures {
+        return 0;
+    }
+
+    let exp = (failures - max_failures).min(16) as u32;
+    base.saturating_mul(2usize.pow(exp)).min(max)
+}
+
+/// ip所在网段, 用于判断登录地点是否变化: ipv4 取 /24, ipv6 取 /48
+pub fn ip_network(ip: &str) -> String {
+    match ip.trim().parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        Ok(IpAddr::V6(ip)) => {
+            let segments = ip.segments();
+            format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2])
+        }
+        Err(_) => "".to_string(),
+    }
+}
+
+/// 固定验证码
+pub struct StaticCaptcha(String);
+
+#[async_trait]
+impl CaptchaVerifier for StaticCaptcha {
+    async fn verify(&self, token: &str, _ip: &str) -> ApiResult<bool> {
+        Ok(!self.0.is_empty() && self.0 == token)
+    }
+}
+
+/// siteverify 接口, 提交 secret、response 及 remoteip, 返回 {"success": bool}
+pub struct HttpCaptcha {
+    secret: String,
+    verify_url: String,
+}
+
+#[async_trait]
+impl CaptchaVerifier for HttpCaptcha {
+    async fn verify(&self, token: &str, ip: &str) -> ApiResult<bool> {
+        let mut form = vec![("secret", self.secret.as_str()), ("response", token)];
+        if !ip.is_empty() {
+            form.push(("remoteip", ip));
+        }
+
+        let result = reqwest::Client::new()
+            .post(&self.verify_url)
+            .form(&form)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        Ok(result["success"].as_bool().unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn progressive_lock() {
+        assert_eq!(lock_seconds(4, 5, 60, 3600), 0);
+        assert_eq!(lock_seconds(5, 5, 60, 3600), 60);
+        assert_eq!(lock_seconds(6, 5, 60, 3600), 120);
+        assert_eq!(lock_seconds(9, 5, 60, 3600), 960);
+        assert_eq!(lock_seconds(11, 5, 60, 3600), 3600);
+        assert_eq!(lock_seconds(1000, 5, 60, 3600), 3600);
+        // 未配置次数时不锁定
+        assert_eq!(lock_seconds(100, 0, 60, 3600), 0);
+    }
+
+    #[test]
+    fn network() {
+        assert_eq!(ip_network("192.168.1.23"), "192.168.1.0/24");
+        assert_eq!(ip_network(" 10.0.0.1 "), "10.0.0.0/24");
+        assert_eq!(ip_network("2001:db8:abcd:12::1"), "2001:db8:abcd::/48");
+        assert_eq!(ip_network("unknown"), "");
+    }
+
+    #[tokio::test]
+    async fn static_captcha() {
+        let cfg = CaptchaConfig {
+            driver: "static".to_string(),
+            secret: "1234".to_string(),
+            verify_url: None,
+        };
+        let captcha = from_config(&cfg).unwrap();
+        assert!(captcha.verify("1234", "").await.unwrap());
+        assert!(!captcha.verify("0000", "").await.unwrap());
+
+        let cfg = CaptchaConfig {
+            driver: "http".to_string(),
+            secret: "secret".to_string(),
+            verify_url: None,
+        };
+        assert!(from_config(&cfg).is_err());
+
+        // 未配置密钥时不能启动
+        let cfg = CaptchaConfig {
+            driver: "http".to_string(),
+            secret: "".to_string(),
+            verify_url: Some("https://example.com/siteverify".to_string()),
+        };
+        assert!(from_config(&cfg).is_err());
+    }
+}
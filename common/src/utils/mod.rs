@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::ops::Deref;
 use std::sync::Arc;
 
@@ -13,17 +14,38 @@ use url::form_urlencoded::{byte_serialize, parse};
 
 use crate::casbin::PgSqlAdapter;
 use crate::error::ApiResult;
+use crate::login_guard::CaptchaVerifier;
+use crate::sms::SmsProvider;
+use crate::storage::Storage;
 
+pub mod allocation;
+pub mod cart;
+pub mod carrier;
 pub mod casbin;
+pub mod cookie;
 pub mod elasticsearch;
+pub mod freight;
+pub mod invoice_pdf;
 pub mod jwt;
+pub mod login_guard;
+pub mod oauth;
+pub mod picture;
 pub mod pwd;
 pub mod rabbitmq;
 pub mod redis;
+pub mod reorder;
+pub mod route_catalog;
+pub mod session;
+pub mod settlement;
+pub mod sku;
+pub mod sms;
 pub(crate) mod snowflake;
+pub mod spreadsheet;
+pub mod storage;
+pub mod totp;
 pub mod tree;
 
-/// 图片存储跟路径
+/// 图片存储跟路径, 本地存储时使用
 pub const IMAGES_PATH: &str = "./files/images/";
 
 lazy_static! {
@@ -63,6 +85,27 @@ lazy_static! {
 
         Arc::new(lapin::Connection::connect(addr.as_str(), lapin::ConnectionProperties::default()).await.unwrap())
     });
+
+    // 文件存储
+    pub static ref STORAGE: AsyncOnce<Arc<Box<dyn Storage>>> = AsyncOnce::new(async {
+        let cfg = &crate::application_config().await.storage;
+
+        Arc::new(crate::storage::from_config(cfg).unwrap())
+    });
+
+    // 短信服务
+    pub static ref SMS: AsyncOnce<Arc<Box<dyn SmsProvider>>> = AsyncOnce::new(async {
+        let cfg = &crate::application_config().await.sms;
+
+        Arc::new(crate::sms::from_config(cfg).unwrap())
+    });
+
+    // 人机验证
+    pub static ref CAPTCHA: AsyncOnce<Arc<Box<dyn CaptchaVerifier>>> = AsyncOnce::new(async {
+        let cfg = &crate::application_config().await.login_guard.captcha;
+
+        Arc::new(crate::login_guard::from_config(cfg).unwrap())
+    });
 }
 
 /// 解析任意数据数据
@@ -71,18 +114,26 @@ pub fn parse_field<T: DeserializeOwned>(json: &Value, field: &str) -> Option<T>
         .and_then(|v| serde_json::from_value(v.clone()).ok())
 }
 
-/// url_encode 预览地址
+/// 预览地址, 配置了CDN时返回CDN地址, 否则由存储生成(本地地址或预签名地址)
 pub async fn image_preview_url(path: String) -> (String, String) {
     if &true == &path.starts_with("http://") || &true == &path.starts_with("https://") {
         return (path.clone(), path);
     }
 
-    let url_encode = byte_serialize(&path.as_bytes()).collect::<String>();
+    let cfg = crate::application_config().await;
+    if let Some(cdn_host) = cfg.storage.cdn_host.as_ref().filter(|host| !host.is_empty()) {
+        let url = format!("{}/{}", cdn_host.trim_end_matches('/'), path);
+        return (path, url);
+    }
 
-    (
-        path,
-        format!("{}/api/public/{}", server_host().await, url_encode),
-    )
+    match storage().await.url(&path).await {
+        Ok(url) => (path, url),
+        Err(_e) => {
+            let url_encode = byte_serialize(&path.as_bytes()).collect::<String>();
+            let url = format!("{}/api/public/{}", server_host().await, url_encode);
+            (path, url)
+        }
+    }
 }
 
 /// url_decode
@@ -131,6 +182,18 @@ pub async fn rabbit_mq() -> Arc<lapin::Connection> {
     RABBITMQ.get().await.clone()
 }
 
+pub async fn storage() -> Arc<Box<dyn Storage>> {
+    STORAGE.get().await.clone()
+}
+
+pub async fn sms() -> Arc<Box<dyn SmsProvider>> {
+    SMS.get().await.clone()
+}
+
+pub async fn captcha() -> Arc<Box<dyn CaptchaVerifier>> {
+    CAPTCHA.get().await.clone()
+}
+
 // 格式化年月日,时分秒
 pub fn time_ymd_his(date_time: chrono::NaiveDateTime) -> String {
     date_time.format("%F %T").to_string()
@@ -173,3 +236,107 @@ pub fn get_random_str(len: usize) -> String {
         .collect::<String>();
     value.to_uppercase()
 }
+
+/// 批量插入的占位符, rows 行 columns 列: ($1, $2), ($3, $4)
+pub fn values_placeholders(rows: usize, columns: usize) -> String {
+    (0..rows)
+        .map(|row| {
+            let fields = (1..=columns)
+                .map(|column| format!("${}", row * columns + column))
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!("({})", fields)
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// 按连接地址确定客户端ip, 只有来自可信反向代理的请求才读取 X-Forwarded-For / X-Real-IP
+///
+/// X-Forwarded-For 从右往左跳过可信代理, 第一个不可信的地址为客户端ip
+pub fn real_ip(headers: &http::HeaderMap, peer: IpAddr, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim().parse::<IpAddr>().ok())
+        .collect::<Vec<Option<IpAddr>>>();
+    for ip in forwarded.into_iter().rev() {
+        match ip {
+            Some(ip) if trusted_proxies.contains(&ip) => continue,
+            Some(ip) => return ip,
+            // 无法解析的地址之前的内容不可信
+            None => return peer,
+        }
+    }
+
+    headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<IpAddr>().ok())
+        .unwrap_or(peer)
+}
+
+/// 客户端ip, 请求头由 real_ip 中间件按连接地址重写, 不能直接信任客户端传入的请求头
+pub fn client_ip(headers: &http::HeaderMap) -> String {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .or_else(|| {
+            headers
+                .get("x-real-ip")
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(|value| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_placeholders() {
+        assert_eq!(values_placeholders(1, 3), "($1, $2, $3)");
+        assert_eq!(
+            values_placeholders(3, 3),
+            "($1, $2, $3), ($4, $5, $6), ($7, $8, $9)"
+        );
+        assert_eq!(values_placeholders(0, 3), "");
+    }
+
+    #[test]
+    fn test_real_ip() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let peer: IpAddr = "1.1.1.1".parse().unwrap();
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "9.9.9.9, 2.2.2.2, 10.0.0.1".parse().unwrap(),
+        );
+        headers.insert("x-real-ip", "8.8.8.8".parse().unwrap());
+
+        // 非代理的请求忽略请求头
+        assert_eq!(real_ip(&headers, peer, &[proxy]), peer);
+        assert_eq!(real_ip(&headers, proxy, &[]), proxy);
+        // 跳过可信代理, 客户端伪造的最左侧地址无效
+        assert_eq!(
+            real_ip(&headers, proxy, &[proxy]),
+            "2.2.2.2".parse::<IpAddr>().unwrap()
+        );
+
+        headers.remove("x-forwarded-for");
+        assert_eq!(
+            real_ip(&headers, proxy, &[proxy]),
+            "8.8.8.8".parse::<IpAddr>().unwrap()
+        );
+        headers.insert("x-forwarded-for", "unknown".parse().unwrap());
+        assert_eq!(real_ip(&headers, proxy, &[proxy]), proxy);
+    }
+}
@@ -0,0 +1,331 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{ApiError, ApiResult};
+use crate::jwt::UserSource;
+
+/// 微信网页授权(公众号)
+pub const WECHAT: &str = "wechat";
+
+/// 微信小程序
+pub const WXAPP: &str = "wxapp";
+
+/// 支付宝
+pub const ALIPAY: &str = "alipay";
+
+const WECHAT_API: &str = "https://api.weixin.qq.com";
+
+/// 第三方应用
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OAuthApp {
+    pub app_id: String,
+    pub secret: String,
+}
+
+/// 支付宝授权, app_id 及应用私钥使用 alipay 配置
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AlipayOAuthConfig {
+    // 公钥证书模式的证书路径, 不配置时使用普通公钥模式
+    pub app_cert_path: Option<String>,
+    pub root_cert_path: Option<String>,
+}
+
+/// 第三方登录配置, 未配置的平台不能登录
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OAuthConfig {
+    pub wechat: Option<OAuthApp>,
+    pub wxapp: Option<OAuthApp>,
+    pub alipay: Option<AlipayOAuthConfig>,
+}
+
+/// 第三方用户信息
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OAuthUser {
+    pub provider: String,
+    pub openid: String,
+    // 微信开放平台下同一主体的应用共享 unionid
+    pub unionid: Option<String>,
+    pub nickname: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// 第三方授权登录
+#[async_trait]
+pub trait OAuthProvider: Send + Sync {
+    /// 用授权码换取用户信息
+    async fn user_info(&self, code: &str) -> ApiResult<OAuthUser>;
+}
+
+/// 登录来源
+pub fn source(provider: &str) -> ApiResult<UserSource> {
+    match provider {
+        WECHAT => Ok(UserSource::Wechat),
+        WXAPP => Ok(UserSource::WxApp),
+        ALIPAY => Ok(UserSource::Alipay),
+        provider => Err(ApiError::Error(format!("不支持的登录方式: {}", provider))),
+    }
+}
+
+/// 根据配置创建第三方登录
+pub async fn provider(name: &str) -> ApiResult<Box<dyn OAuthProvider>> {
+    let config = crate::application_config().await;
+    let disabled = || ApiError::Error(format!("未开启登录方式: {}", name));
+
+    match name {
+        WECHAT => Ok(Box::new(WechatWeb(
+            config.oauth.wechat.clone().ok_or_else(disabled)?,
+        ))),
+        WXAPP => Ok(Box::new(WechatMini(
+            config.oauth.wxapp.clone().ok_or_else(disabled)?,
+        ))),
+        ALIPAY => Ok(Box::new(Alipay {
+            app_id: config.alipay.app_id.clone(),
+            private_key: config.alipay.private_key.clone(),
+            cert: config.oauth.alipay.clone().ok_or_else(disabled)?,
+        })),
+        name => Err(ApiError::Error(format!("不支持的登录方式: {}", name))),
+    }
+}
+
+/// 微信接口错误时返回 errcode 及 errmsg
+fn wechat_result(value: Value) -> ApiResult<Value> {
+    match value.get("errcode").and_then(Value::as_i64) {
+        Some(code) if code != 0 => Err(ApiError::Error(format!(
+            "微信授权失败: {}",
+            value["errmsg"].as_str().unwrap_or_default()
+        ))),
+        _ => Ok(value),
+    }
+}
+
+/// 支付宝返回 {method}_response, 出错时返回 error_response 或 code 不为 10000
+fn alipay_result(value: Value, method: &str) -> ApiResult<Value> {
+    let key = format!("{}_response", method.replace('.', "_"));
+    let body = match value.get(&key) {
+        Some(body) => body,
+        None => value.get("error_response").unwrap_or(&Value::Null),
+    };
+
+    match body.get("code").and_then(Value::as_str) {
+        Some("10000") | None if value.get(&key).is_some() => Ok(body.clone()),
+        _ => Err(ApiError::Error(format!(
+            "支付宝授权失败: {}",
+            body["sub_msg"]
+                .as_str()
+                .or(body["msg"].as_str())
+                .unwrap_or_default()
+        ))),
+    }
+}
+
+fn string_field(value: &Value, field: &str) -> Option<String> {
+    value[field]
+        .as_str()
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+async fn wechat_get(path: &str, query: &[(&str, &str)]) -> ApiResult<Value> {
+    // 小程序接口返回的 Content-Type 为 text/plain, 不能依赖响应头
+    let value = reqwest::Client::new()
+        .get(format!("{}{}", WECHAT_API, path))
+        .query(query)
+        .send()
+        .await?
+        .json::<Value>()
+        .await?;
+
+    wechat_result(value)
+}
+
+/// 微信网页授权, scope 为 snsapi_userinfo 时获取昵称头像
+pub struct WechatWeb(OAuthApp);
+
+#[async_trait]
+impl OAuthProvider for WechatWeb {
+    async fn user_info(&self, code: &str) -> ApiResult<OAuthUser> {
+        let token = wechat_get(
+            "/sns/oauth2/access_token",
+            &[
+                ("appid", self.0.app_id.as_str()),
+                ("secret", self.0.secret.as_str()),
+                ("code", code),
+                ("grant_type", "authorization_code"),
+            ],
+        )
+        .await?;
+        let openid = string_field(&token, "openid")
+            .ok_or(ApiError::Error("微信授权失败: openid 不存在".to_string()))?;
+        let mut user = OAuthUser {
+            provider: WECHAT.to_string(),
+            openid,
+            unionid: string_field(&token, "unionid"),
+            ..Default::default()
+        };
+
+        if token["scope"]
+            .as_str()
+            .map_or(false, |scope| scope.contains("snsapi_userinfo"))
+        {
+            let info = wechat_get(
+                "/sns/userinfo",
+                &[
+                    (
+                        "access_token",
+                        token["access_token"].as_str().unwrap_or_default(),
+                    ),
+                    ("openid", user.openid.as_str()),
+                    ("lang", "zh_CN"),
+                ],
+            )
+            .await?;
+            user.unionid = user.unionid.or(string_field(&info, "unionid"));
+            user.nickname = string_field(&info, "nickname");
+            user.avatar = string_field(&info, "headimgurl");
+        }
+
+        Ok(user)
+    }
+}
+
+/// 微信小程序 code2session, 不返回昵称头像
+pub struct WechatMini(OAuthApp);
+
+#[async_trait]
+impl OAuthProvider for WechatMini {
+    async fn user_info(&self, code: &str) -> ApiResult<OAuthUser> {
+        let session = wechat_get(
+            "/sns/jscode2session",
+            &[
+                ("appid", self.0.app_id.as_str()),
+                ("secret", self.0.secret.as_str()),
+                ("js_code", code),
+                ("grant_type", "authorization_code"),
+            ],
+        )
+        .await?;
+
+        Ok(OAuthUser {
+            provider: WXAPP.to_string(),
+            openid: string_field(&session, "openid")
+                .ok_or(ApiError::Error("微信授权失败: openid 不存在".to_string()))?,
+            unionid: string_field(&session, "unionid"),
+            ..Default::default()
+        })
+    }
+}
+
+/// 支付宝用户授权, auth_user 授权时获取昵称头像
+pub struct Alipay {
+    app_id: String,
+    private_key: String,
+    cert: AlipayOAuthConfig,
+}
+
+impl Alipay {
+    async fn call(&self, method: &str, params: Vec<(&str, &str)>) -> ApiResult<Value> {
+        let mut app_cert = None;
+        if let Some(path) = &self.cert.app_cert_path {
+            app_cert = Some(tokio::fs::read_to_string(path).await?);
+        }
+        let mut root_cert = None;
+        if let Some(path) = &self.cert.root_cert_path {
+            root_cert = Some(tokio::fs::read_to_string(path).await?);
+        }
+
+        let response = pay::AliPay::new("", &self.private_key)
+            .request(&self.app_id)
+            .add_cert(app_cert.as_deref(), root_cert.as_deref())
+            .add_request(params)
+            .post(method, None)
+            .await?;
+
+        alipay_result(response.json::<Value>().await?, method)
+    }
+}
+
+#[async_trait]
+impl OAuthProvider for Alipay {
+    async fn user_info(&self, code: &str) -> ApiResult<OAuthUser> {
+        let token = self
+            .call(
+                "alipay.system.oauth.token",
+                vec![("grant_type", "authorization_code"), ("code", code)],
+            )
+            .await?;
+        // 新应用返回 open_id, 旧应用返回 user_id
+        let openid = string_field(&token, "open_id")
+            .or(string_field(&token, "user_id"))
+            .ok_or(ApiError::Error(
+                "支付宝授权失败: 用户标识不存在".to_string(),
+            ))?;
+        let mut user = OAuthUser {
+            provider: ALIPAY.to_string(),
+            openid,
+            ..Default::default()
+        };
+
+        // auth_base 授权无法获取用户信息, 忽略错误
+        let access_token = token["access_token"].as_str().unwrap_or_default();
+        if let Ok(info) = self
+            .call("alipay.user.info.share", vec![("auth_token", access_token)])
+            .await
+        {
+            user.nickname = string_field(&info, "nick_name");
+            user.avatar = string_field(&info, "avatar");
+        }
+
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn wechat_errors() {
+        let ok = json!({ "openid": "o1", "session_key": "k" });
+        assert_eq!(wechat_result(ok.clone()).unwrap(), ok);
+        assert!(wechat_result(json!({ "errcode": 0, "openid": "o1" })).is_ok());
+
+        let err = wechat_result(json!({ "errcode": 40029, "errmsg": "invalid code" }));
+        assert_eq!(err.unwrap_err().to_string(), "微信授权失败: invalid code");
+    }
+
+    #[test]
+    fn alipay_errors() {
+        let method = "alipay.system.oauth.token";
+        let ok = json!({
+            "alipay_system_oauth_token_response": { "open_id": "o1", "access_token": "t" },
+            "sign": "xx",
+        });
+        assert_eq!(alipay_result(ok, method).unwrap()["open_id"], "o1");
+
+        let err = json!({
+            "error_response": { "code": "40002", "msg": "Invalid Arguments", "sub_msg": "授权码已过期" },
+        });
+        assert_eq!(
+            alipay_result(err, method).unwrap_err().to_string(),
+            "支付宝授权失败: 授权码已过期"
+        );
+
+        let method = "alipay.user.info.share";
+        let ok =
+            json!({ "alipay_user_info_share_response": { "code": "10000", "nick_name": "n" } });
+        assert_eq!(alipay_result(ok, method).unwrap()["nick_name"], "n");
+        let err = json!({ "alipay_user_info_share_response": { "code": "20001", "msg": "Insufficient Token Permissions" } });
+        assert!(alipay_result(err, method).is_err());
+    }
+
+    #[test]
+    fn login_source() {
+        assert!(matches!(source(WXAPP).unwrap(), UserSource::WxApp));
+        assert!(matches!(source(WECHAT).unwrap(), UserSource::Wechat));
+        assert!(matches!(source(ALIPAY).unwrap(), UserSource::Alipay));
+        assert!(source("github").is_err());
+    }
+}
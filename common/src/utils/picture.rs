@@ -0,0 +1,222 @@
+use std::io::Cursor;
+
+use image::imageops::FilterType;
+use image::ImageOutputFormat;
+use sha2::{Digest, Sha256};
+
+use crate::error::{ApiError, ApiResult};
+
+/// 单张图片最大上传大小: 10M
+pub const IMAGE_MAX_SIZE: usize = 10 * 1024 * 1024;
+
+/// 缩略图宽度
+pub const THUMBNAIL_SIZES: [u32; 3] = [200, 400, 800];
+
+/// 支持的图片格式, 以文件头识别
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageKind {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+}
+
+impl ImageKind {
+    /// 根据文件头(magic bytes)识别图片格式
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0xFF, 0xD8, 0xFF, ..] => Some(Self::Jpeg),
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(Self::Png),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(Self::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn output_format(&self) -> ImageOutputFormat {
+        match self {
+            Self::Jpeg => ImageOutputFormat::Jpeg(85),
+            Self::Png => ImageOutputFormat::Png,
+            Self::Gif => ImageOutputFormat::Gif,
+            Self::Webp => ImageOutputFormat::WebP,
+        }
+    }
+}
+
+/// 图片内容的 sha256, 用作文件名去重
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// 校验并保存图片, 同时生成缩略图及webp格式, 返回存储中的相对路径
+///
+/// 相同内容的图片只会保存一次
+pub async fn save_image(bytes: Vec<u8>) -> ApiResult<String> {
+    if bytes.len() > IMAGE_MAX_SIZE {
+        return Err(ApiError::Error(format!(
+            "图片大小不能超过 {}M",
+            IMAGE_MAX_SIZE / 1024 / 1024
+        )));
+    }
+    let kind =
+        ImageKind::detect(&bytes).ok_or(ApiError::Error("不允许上传此类型文件".to_string()))?;
+
+    let hash = content_hash(&bytes);
+    let path = format!("{}/{}.{}", &hash[..2], hash, kind.extension());
+    let storage = crate::storage().await;
+    if storage.exists(&path).await? {
+        return Ok(path);
+    }
+
+    let variants =
+        tokio::task::spawn_blocking(move || -> ApiResult<(Vec<u8>, Vec<(String, Vec<u8>)>)> {
+            let variants = generate_variants(&bytes, kind)?;
+            Ok((bytes, variants))
+        })
+        .await
+        .map_err(|e| ApiError::Error(e.to_string()))??;
+
+    for (name, content) in variants.1 {
+        let content_type = ImageKind::detect(&content).unwrap_or(kind).content_type();
+        storage
+            .put(&format!("{}/{}", &hash[..2], name), content, content_type)
+            .await?;
+    }
+    // 原图最后写入, 原图存在即表示全部文件已生成
+    storage.put(&path, variants.0, kind.content_type()).await?;
+
+    Ok(path)
+}
+
+/// 生成缩略图及webp格式: {hash}_{width}.{ext}, {hash}_{width}.webp, {hash}.webp
+fn generate_variants(bytes: &[u8], kind: ImageKind) -> ApiResult<Vec<(String, Vec<u8>)>> {
+    let hash = content_hash(bytes);
+    let img = image::load_from_memory(bytes)?;
+    let mut variants = Vec::new();
+
+    for width in THUMBNAIL_SIZES {
+        // 不放大图片
+        if img.width() <= width {
+            continue;
+        }
+
+        let thumbnail = img.resize(width, u32::MAX, FilterType::Lanczos3);
+        for target in [kind, ImageKind::Webp] {
+            let mut content = Cursor::new(Vec::new());
+            thumbnail.write_to(&mut content, target.output_format())?;
+            variants.push((
+                format!("{}_{}.{}", hash, width, target.extension()),
+                content.into_inner(),
+            ));
+        }
+    }
+
+    if kind != ImageKind::Webp {
+        let mut content = Cursor::new(Vec::new());
+        img.write_to(&mut content, ImageKind::Webp.output_format())?;
+        variants.push((format!("{}.webp", hash), content.into_inner()));
+    }
+
+    Ok(variants)
+}
+
+/// 根据请求的宽度和格式选择对应的图片文件, 宽度向上取最接近的缩略图
+pub fn variant_path(path: &str, width: Option<u32>, webp: bool) -> String {
+    let (stem, ext) = match path.rsplit_once('.') {
+        Some(value) => value,
+        None => return path.to_string(),
+    };
+
+    let mut name = stem.to_string();
+    if let Some(width) = width {
+        if let Some(size) = THUMBNAIL_SIZES.iter().find(|&&size| size >= width) {
+            name.push_str(&format!("_{}", size));
+        }
+    }
+
+    format!("{}.{}", name, if webp { "webp" } else { ext })
+}
+
+/// 解析 Range 请求头, 仅支持单个区间: bytes=start-end, bytes=start-, bytes=-suffix
+pub fn parse_range(header: &str, len: u64) -> Option<(u64, u64)> {
+    let range = header.trim().strip_prefix("bytes=")?;
+    if range.contains(',') || len == 0 {
+        return None;
+    }
+
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix = suffix.parse::<u64>().ok()?.min(len);
+            (len - suffix, len - 1)
+        }
+        (start, "") => (start.parse::<u64>().ok()?, len - 1),
+        (start, end) => (
+            start.parse::<u64>().ok()?,
+            end.parse::<u64>().ok()?.min(len - 1),
+        ),
+    };
+
+    if start > end || start >= len {
+        return None;
+    }
+
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_kind() {
+        assert_eq!(
+            ImageKind::detect(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ImageKind::Jpeg)
+        );
+        assert_eq!(ImageKind::detect(b"GIF89a...."), Some(ImageKind::Gif));
+        assert_eq!(
+            ImageKind::detect(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageKind::Webp)
+        );
+        assert_eq!(ImageKind::detect(b"<svg></svg>"), None);
+    }
+
+    #[test]
+    fn select_variant() {
+        assert_eq!(
+            variant_path("ab/abc.jpg", Some(300), false),
+            "ab/abc_400.jpg"
+        );
+        assert_eq!(variant_path("ab/abc.jpg", Some(2000), true), "ab/abc.webp");
+        assert_eq!(variant_path("ab/abc.png", None, false), "ab/abc.png");
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=0-5000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+    }
+}
@@ -1,21 +1,201 @@
-pub struct Md5Encoder;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ApiResult;
+
+/// argon2id 参数配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasswordConfig {
+    // 内存开销(KiB)
+    pub memory_kib: u32,
+    // 迭代次数
+    pub iterations: u32,
+    // 并行度
+    pub parallelism: u32,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
 
 pub trait PassWordAnalyze {
     /// 字符加密
-    fn encode(raw_pwd: &str) -> String;
+    fn encode(&self, raw_pwd: &str) -> ApiResult<String>;
 
     /// 检验密码
-    /// new_pwd 新密码
-    /// old_pwd 旧密码
-    fn verify(new_pwd: &str, old_pwd: &str) -> bool;
+    /// hash 已保存的密码
+    /// raw_pwd 用户输入的密码
+    fn verify(&self, hash: &str, raw_pwd: &str) -> bool;
+
+    /// 是否需要重新加密
+    fn needs_rehash(&self, _hash: &str) -> bool {
+        false
+    }
+}
+
+/// 旧版本密码, 仅用于校验历史数据
+pub struct Md5Encoder;
+
+impl Md5Encoder {
+    /// 是否为md5格式的密码
+    pub fn is_legacy(hash: &str) -> bool {
+        hash.len() == 32 && hash.chars().all(|c| c.is_ascii_hexdigit())
+    }
 }
 
 impl PassWordAnalyze for Md5Encoder {
-    fn encode(raw_pwd: &str) -> String {
-        format!("{:x}", md5::compute(raw_pwd))
+    fn encode(&self, raw_pwd: &str) -> ApiResult<String> {
+        Ok(format!("{:x}", md5::compute(raw_pwd)))
+    }
+
+    fn verify(&self, hash: &str, raw_pwd: &str) -> bool {
+        Self::is_legacy(hash) && hash.eq_ignore_ascii_case(&format!("{:x}", md5::compute(raw_pwd)))
+    }
+
+    fn needs_rehash(&self, _hash: &str) -> bool {
+        true
+    }
+}
+
+/// argon2id 加密, PHC 格式保存: $argon2id$v=19$m=..,t=..,p=..$salt$hash
+pub struct Argon2Encoder {
+    params: Params,
+}
+
+impl Argon2Encoder {
+    pub fn new(cfg: &PasswordConfig) -> ApiResult<Self> {
+        Ok(Self {
+            params: Params::new(cfg.memory_kib, cfg.iterations, cfg.parallelism, None)?,
+        })
+    }
+
+    fn hasher(&self) -> Argon2<'_> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PassWordAnalyze for Argon2Encoder {
+    fn encode(&self, raw_pwd: &str) -> ApiResult<String> {
+        let salt = SaltString::b64_encode(&rand::random::<[u8; 16]>())?;
+
+        Ok(self
+            .hasher()
+            .hash_password(raw_pwd.as_bytes(), &salt)?
+            .to_string())
+    }
+
+    fn verify(&self, hash: &str, raw_pwd: &str) -> bool {
+        match PasswordHash::new(hash) {
+            Ok(parsed) => self
+                .hasher()
+                .verify_password(raw_pwd.as_bytes(), &parsed)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// 算法或参数与当前配置不一致时需要重新加密
+    fn needs_rehash(&self, hash: &str) -> bool {
+        let parsed = match PasswordHash::new(hash) {
+            Ok(parsed) => parsed,
+            Err(_) => return true,
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+
+        match Params::try_from(&parsed) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// 校验密码, 兼容旧的md5密码
+///
+/// 校验通过且密码需要升级时返回新的hash, 由调用方保存
+pub fn verify_upgrade(
+    cfg: &PasswordConfig,
+    hash: &str,
+    raw_pwd: &str,
+) -> ApiResult<(bool, Option<String>)> {
+    let encoder = Argon2Encoder::new(cfg)?;
+    let (valid, rehash) = if Md5Encoder::is_legacy(hash) {
+        (Md5Encoder.verify(hash, raw_pwd), true)
+    } else {
+        (encoder.verify(hash, raw_pwd), encoder.needs_rehash(hash))
+    };
+
+    if valid && rehash {
+        return Ok((true, Some(encoder.encode(raw_pwd)?)));
+    }
+
+    Ok((valid, None))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> PasswordConfig {
+        PasswordConfig {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn argon2_encode_verify() {
+        let encoder = Argon2Encoder::new(&config()).unwrap();
+        let hash = encoder.encode("secret-pwd").unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(encoder.verify(&hash, "secret-pwd"));
+        assert!(!encoder.verify(&hash, "secret-pwd2"));
+        // 明文与hash相同不能通过
+        assert!(!encoder.verify(&hash, &hash));
+        assert!(!encoder.needs_rehash(&hash));
     }
 
-    fn verify(new_pwd: &str, old_pwd: &str) -> bool {
-        new_pwd.eq(old_pwd) || new_pwd.eq(&Md5Encoder::encode(old_pwd))
+    #[test]
+    fn md5_not_accept_hash() {
+        let hash = Md5Encoder.encode("123456").unwrap();
+
+        assert!(Md5Encoder.verify(&hash, "123456"));
+        assert!(!Md5Encoder.verify(&hash, &hash));
+    }
+
+    #[test]
+    fn upgrade_legacy() {
+        let legacy = Md5Encoder.encode("123456").unwrap();
+
+        let (valid, rehash) = verify_upgrade(&config(), &legacy, "123456").unwrap();
+        assert!(valid);
+        let rehash = rehash.unwrap();
+        assert!(rehash.starts_with("$argon2id$"));
+
+        let (valid, rehash) = verify_upgrade(&config(), &legacy, "654321").unwrap();
+        assert!(!valid && rehash.is_none());
+
+        // 参数变化后重新加密
+        let mut cfg = config();
+        let hash = Argon2Encoder::new(&cfg).unwrap().encode("123456").unwrap();
+        assert_eq!(verify_upgrade(&cfg, &hash, "123456").unwrap(), (true, None));
+        cfg.iterations = 2;
+        let (valid, rehash) = verify_upgrade(&cfg, &hash, "123456").unwrap();
+        assert!(valid);
+        assert!(rehash.unwrap().contains("t=2"));
     }
 }
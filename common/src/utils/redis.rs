@@ -1,8 +1,11 @@
 use std::borrow::BorrowMut;
+use std::collections::HashMap;
+use std::ops::DerefMut;
 
 use async_once::AsyncOnce;
 use lazy_static::lazy_static;
 use r2d2_redis::r2d2::PooledConnection;
+use r2d2_redis::redis::Commands;
 use r2d2_redis::{r2d2, r2d2::Pool, redis, RedisConnectionManager};
 use serde::de::DeserializeOwned;
 
@@ -45,3 +48,115 @@ pub async fn json_get<T: DeserializeOwned>(
 
     Err(ApiError::Error("ket not found".to_string()))
 }
+
+/// 设置hash字段并刷新过期时间(秒)
+pub async fn hset_ex(key: &str, field: &str, value: &str, seconds: usize) -> ApiResult<()> {
+    let mut conn = get_conn_manager().await;
+    conn.deref_mut().hset::<_, _, _, ()>(key, field, value)?;
+    conn.deref_mut().expire::<_, ()>(key, seconds)?;
+
+    Ok(())
+}
+
+/// 读取hash全部字段
+pub async fn hget_all(key: &str) -> ApiResult<HashMap<String, String>> {
+    let mut conn = get_conn_manager().await;
+
+    Ok(conn.deref_mut().hgetall(key)?)
+}
+
+/// 读取hash字段
+pub async fn hget(key: &str, field: &str) -> ApiResult<Option<String>> {
+    let mut conn = get_conn_manager().await;
+
+    Ok(conn.deref_mut().hget(key, field)?)
+}
+
+/// 删除hash字段
+pub async fn hdel(key: &str, fields: &[String]) -> ApiResult<u64> {
+    if fields.is_empty() {
+        return Ok(0);
+    }
+    let mut conn = get_conn_manager().await;
+
+    Ok(conn.deref_mut().hdel(key, fields)?)
+}
+
+/// 删除key
+pub async fn del(key: &str) -> ApiResult<()> {
+    let mut conn = get_conn_manager().await;
+    conn.deref_mut().del::<_, ()>(key)?;
+
+    Ok(())
+}
+
+/// 读取字符串
+pub async fn get(key: &str) -> ApiResult<Option<String>> {
+    let mut conn = get_conn_manager().await;
+
+    Ok(conn.deref_mut().get(key)?)
+}
+
+/// 设置字符串及过期时间(秒)
+pub async fn set_ex(key: &str, value: &str, seconds: usize) -> ApiResult<()> {
+    let mut conn = get_conn_manager().await;
+    conn.deref_mut().set_ex::<_, _, ()>(key, value, seconds)?;
+
+    Ok(())
+}
+
+/// 集合添加成员并刷新过期时间(秒)
+pub async fn sadd_ex(key: &str, member: &str, seconds: usize) -> ApiResult<()> {
+    let mut conn = get_conn_manager().await;
+    conn.deref_mut().sadd::<_, _, ()>(key, member)?;
+    conn.deref_mut().expire::<_, ()>(key, seconds)?;
+
+    Ok(())
+}
+
+/// 读取集合全部成员
+pub async fn smembers(key: &str) -> ApiResult<Vec<String>> {
+    let mut conn = get_conn_manager().await;
+
+    Ok(conn.deref_mut().smembers(key)?)
+}
+
+/// 删除集合成员
+pub async fn srem(key: &str, members: &[String]) -> ApiResult<u64> {
+    if members.is_empty() {
+        return Ok(0);
+    }
+    let mut conn = get_conn_manager().await;
+
+    Ok(conn.deref_mut().srem(key, members)?)
+}
+
+/// 计数加1, 第一次计数时设置过期时间(秒), 返回计数
+pub async fn incr_ex(key: &str, seconds: usize) -> ApiResult<i64> {
+    let mut conn = get_conn_manager().await;
+    let total: i64 = conn.deref_mut().incr(key, 1)?;
+    if total == 1 {
+        conn.deref_mut().expire::<_, ()>(key, seconds)?;
+    }
+
+    Ok(total)
+}
+
+/// 执行lua脚本, 脚本中的多个命令原子执行
+pub async fn eval<T: redis::FromRedisValue>(
+    script: &str,
+    keys: &[&str],
+    args: &[String],
+) -> ApiResult<T> {
+    let mut conn = get_conn_manager().await;
+    let script = redis::Script::new(script);
+    let mut invocation = script.prepare_invoke();
+    for key in keys {
+        invocation.key(*key);
+    }
+    for arg in args {
+        invocation.arg(arg);
+    }
+
+    Ok(invocation.invoke(conn.deref_mut())?)
+}
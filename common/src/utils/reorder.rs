@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+/// 日均销量, 由两次销量快照的差值计算
+pub fn velocity(sold_now: i64, sold_before: i64, days: i64) -> f64 {
+    if days <= 0 || sold_now <= sold_before {
+        return 0.0;
+    }
+
+    (sold_now - sold_before) as f64 / days as f64
+}
+
+/// 建议采购量
+///
+/// 补货点为采购周期内的销量, 可用库存(含在途)低于补货点时, 补足到 采购周期 + 备货天数 的销量
+pub fn suggest(velocity: f64, stock: i64, on_order: i64, lead_days: u32, cover_days: u32) -> i64 {
+    if velocity <= 0.0 {
+        return 0;
+    }
+
+    let available = stock.max(0) + on_order.max(0);
+    let reorder_point = (velocity * lead_days as f64).ceil() as i64;
+    if available > reorder_point {
+        return 0;
+    }
+
+    let target = (velocity * (lead_days + cover_days) as f64).ceil() as i64;
+    (target - available).max(0)
+}
+
+/// 校验采购收货数量, lines 为采购明细 (明细id, 采购数量, 已收货数量), receive 为本次收货 (明细id, 数量)
+///
+/// 返回 明细id => 本次收货数量, 以及收货后是否全部完成; 明细不存在或超出可收货数量时返回 Err(明细id)
+pub fn receipt(
+    lines: &[(i64, i32, i32)],
+    receive: &[(i64, i32)],
+) -> Result<(HashMap<i64, i32>, bool), i64> {
+    let mut received: HashMap<i64, i32> = HashMap::new();
+    for (line_id, quantity) in receive.iter() {
+        let (_, ordered, done) = lines
+            .iter()
+            .find(|(id, _, _)| id == line_id)
+            .ok_or(*line_id)?;
+        let total = received.entry(*line_id).or_default();
+        *total += quantity;
+        if *quantity <= 0 || done + *total > *ordered {
+            return Err(*line_id);
+        }
+    }
+
+    let finished = lines.iter().all(|(id, ordered, done)| {
+        done + received.get(id).cloned().unwrap_or_default() >= *ordered
+    });
+
+    Ok((received, finished))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn daily_velocity() {
+        assert_eq!(velocity(130, 100, 10), 3.0);
+        assert_eq!(velocity(100, 100, 10), 0.0);
+        assert_eq!(velocity(100, 120, 10), 0.0);
+        assert_eq!(velocity(100, 0, 0), 0.0);
+    }
+
+    #[test]
+    fn suggest_quantity() {
+        // 补货点: 3 * 7 = 21
+        assert_eq!(suggest(3.0, 30, 0, 7, 30), 0);
+        assert_eq!(suggest(3.0, 15, 0, 7, 30), 96);
+        // 在途库存计入可用库存
+        assert_eq!(suggest(3.0, 15, 10, 7, 30), 0);
+        assert_eq!(suggest(0.5, 0, 0, 7, 30), 19);
+        assert_eq!(suggest(0.0, 0, 0, 7, 30), 0);
+    }
+
+    #[test]
+    fn receive_lines() {
+        let lines = [(1, 10, 0), (2, 5, 3)];
+
+        let (received, finished) = receipt(&lines, &[(1, 4), (2, 2)]).unwrap();
+        assert_eq!(received, HashMap::from([(1, 4), (2, 2)]));
+        assert!(!finished);
+
+        // 同一明细分多行收货时累加
+        let (received, finished) = receipt(&lines, &[(1, 4), (1, 6), (2, 2)]).unwrap();
+        assert_eq!(received[&1], 10);
+        assert!(finished);
+
+        assert_eq!(receipt(&lines, &[(2, 3)]), Err(2));
+        assert_eq!(receipt(&lines, &[(1, 6), (1, 6)]), Err(1));
+        assert_eq!(receipt(&lines, &[(1, 0)]), Err(1));
+        assert_eq!(receipt(&lines, &[(3, 1)]), Err(3));
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use axum::handler::Handler;
+use axum::routing::{self, MethodRouter};
+use axum::Router;
+use casbin::function_map::{key_match2, regex_match};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // 启动时登记的路由权限
+    static ref ROUTES: RwLock<Vec<RoutePermission>> = RwLock::new(vec![]);
+}
+
+/// 路由对应的权限, path 为完整路由, 与 casbin 规则中的路由一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePermission {
+    // 权限标识, 如 orders.index
+    pub name: String,
+    pub display_name: String,
+    pub group_name: String,
+    pub path: String,
+    pub method: String,
+}
+
+/// 注册路由的同时登记权限标识、说明及分组
+pub struct CatalogRouter {
+    router: Router,
+    group: String,
+    routes: Vec<RoutePermission>,
+}
+
+impl CatalogRouter {
+    /// group 为直接注册的路由所属分组
+    pub fn new(group: &str) -> Self {
+        Self {
+            router: Router::new(),
+            group: group.to_string(),
+            routes: vec![],
+        }
+    }
+
+    fn add(
+        mut self,
+        method: &str,
+        path: &str,
+        method_router: MethodRouter,
+        name: &str,
+        display_name: &str,
+    ) -> Self {
+        self.router = self.router.route(path, method_router);
+        self.routes.push(RoutePermission {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            group_name: self.group.clone(),
+            path: path.to_string(),
+            method: method.to_string(),
+        });
+
+        self
+    }
+
+    pub fn get<H, T>(self, path: &str, handler: H, name: &str, display_name: &str) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        self.add("GET", path, routing::get(handler), name, display_name)
+    }
+
+    pub fn post<H, T>(self, path: &str, handler: H, name: &str, display_name: &str) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        self.add("POST", path, routing::post(handler), name, display_name)
+    }
+
+    pub fn put<H, T>(self, path: &str, handler: H, name: &str, display_name: &str) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        self.add("PUT", path, routing::put(handler), name, display_name)
+    }
+
+    pub fn patch<H, T>(self, path: &str, handler: H, name: &str, display_name: &str) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        self.add("PATCH", path, routing::patch(handler), name, display_name)
+    }
+
+    pub fn delete<H, T>(self, path: &str, handler: H, name: &str, display_name: &str) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        self.add("DELETE", path, routing::delete(handler), name, display_name)
+    }
+
+    pub fn nest(mut self, prefix: &str, other: CatalogRouter) -> Self {
+        self.router = self.router.nest(prefix, other.router);
+        self.routes
+            .extend(other.routes.into_iter().map(|mut route| {
+                route.path = join_path(prefix, &route.path);
+                route
+            }));
+
+        self
+    }
+
+    pub fn merge(mut self, other: CatalogRouter) -> Self {
+        self.router = self.router.merge(other.router);
+        self.routes.extend(other.routes);
+
+        self
+    }
+
+    pub fn into_parts(self) -> (Router, Vec<RoutePermission>) {
+        (self.router, self.routes)
+    }
+}
+
+/// 拼接嵌套路由, 子路由为 / 时即为前缀本身
+pub fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match path {
+        "" | "/" if prefix.is_empty() => "/".to_string(),
+        "" | "/" => prefix.to_string(),
+        _ => format!("{}{}", prefix, path),
+    }
+}
+
+/// 登记路由权限, 权限标识重复时 panic
+pub fn register(routes: Vec<RoutePermission>) {
+    let mut names = HashSet::new();
+    for route in routes.iter() {
+        if !names.insert(route.name.as_str()) {
+            panic!("路由权限标识重复: {}", route.name);
+        }
+    }
+
+    ROUTES.write().unwrap().extend(routes);
+}
+
+/// 已登记的路由权限
+pub fn routes() -> Vec<RoutePermission> {
+    ROUTES.read().unwrap().clone()
+}
+
+/// casbin 规则是否匹配某个路由, 规则格式: [角色, 域名, 路由, 请求方式]
+pub fn rule_matched(rule: &[String], routes: &[RoutePermission]) -> bool {
+    if rule.len() < 4 {
+        return false;
+    }
+
+    routes
+        .iter()
+        .any(|route| key_match2(&route.path, &rule[2]) && regex_match(&route.method, &rule[3]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(path: &str, method: &str) -> RoutePermission {
+        RoutePermission {
+            name: format!("{} {}", method, path),
+            display_name: String::new(),
+            group_name: String::new(),
+            path: path.to_string(),
+            method: method.to_string(),
+        }
+    }
+
+    fn rule(path: &str, method: &str) -> Vec<String> {
+        vec![
+            "admin".to_string(),
+            "localhost".to_string(),
+            path.to_string(),
+            method.to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_join_path() {
+        assert_eq!(join_path("/orders", "/"), "/orders");
+        assert_eq!(join_path("/orders", "/:id"), "/orders/:id");
+        assert_eq!(join_path("/api/admin/", "/orders"), "/api/admin/orders");
+        assert_eq!(join_path("", "/"), "/");
+        assert_eq!(join_path("", "/orders"), "/orders");
+    }
+
+    #[test]
+    fn test_rule_matched() {
+        let routes = vec![
+            route("/api/admin/orders", "GET"),
+            route("/api/admin/orders/:id", "POST"),
+        ];
+
+        assert!(rule_matched(&rule("/api/admin/orders", "GET"), &routes));
+        assert!(rule_matched(
+            &rule("/api/admin/orders/:id", "POST"),
+            &routes
+        ));
+        assert!(rule_matched(
+            &rule("/api/admin/orders/*", "(GET)|(POST)"),
+            &routes
+        ));
+        assert!(!rule_matched(
+            &rule("/api/admin/orders/:id", "DELETE"),
+            &routes
+        ));
+        assert!(!rule_matched(&rule("/api/admin/coupons", "GET"), &routes));
+        assert!(!rule_matched(&["admin".to_string()], &routes));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::error::{ApiError, ApiResult};
+
+/// 刷新token时会话的校验结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum RefreshCheck {
+    Valid,
+    // 会话不存在或不属于该用户
+    Invalid,
+    // refresh_token 已被使用过
+    Reused,
+}
+
+/// 会话只能由所属用户注销, 会话已过期时视为已注销
+pub fn check_owner(owner: Option<i64>, user_id: i64) -> ApiResult<()> {
+    match owner {
+        Some(owner) if owner != user_id => Err(ApiError::Error("会话不存在".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// 校验refresh_token, session 为会话的 (所属用户, 当前有效的refresh_token jti)
+pub fn check_refresh(session: Option<(i64, &str)>, user_id: i64, jti: &str) -> RefreshCheck {
+    match session {
+        Some((owner, _)) if owner != user_id => RefreshCheck::Invalid,
+        Some((_, current)) if current != jti => RefreshCheck::Reused,
+        Some(_) => RefreshCheck::Valid,
+        None => RefreshCheck::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn revoke_owner() {
+        assert!(check_owner(Some(1), 1).is_ok());
+        assert!(check_owner(None, 1).is_ok());
+        // 不能注销其他用户的会话
+        assert!(check_owner(Some(2), 1).is_err());
+    }
+
+    #[test]
+    fn refresh_session() {
+        assert_eq!(check_refresh(Some((1, "a")), 1, "a"), RefreshCheck::Valid);
+        assert_eq!(check_refresh(Some((1, "a")), 1, "b"), RefreshCheck::Reused);
+        assert_eq!(check_refresh(Some((2, "a")), 1, "a"), RefreshCheck::Invalid);
+        assert_eq!(check_refresh(None, 1, "a"), RefreshCheck::Invalid);
+    }
+}
@@ -0,0 +1,81 @@
+use sqlx::postgres::types::PgMoney;
+
+/// 订单金额 = 商品金额 + 运费(分); money 类型字段必须绑定 PgMoney, 直接绑定整数时会按元保存
+pub fn order_amount(goods: i64, shipping_fee: i64) -> PgMoney {
+    PgMoney(goods + shipping_fee)
+}
+
+/// 按商品标价金额比例分摊实付金额(分), 尾差计入最后一项
+pub fn apportion(paid: i64, amounts: &[i64]) -> Vec<i64> {
+    let paid = paid.max(0);
+    let total = amounts.iter().map(|amount| (*amount).max(0)).sum::<i64>();
+    let mut result = vec![0i64; amounts.len()];
+    if amounts.is_empty() {
+        return result;
+    }
+    if total == 0 {
+        *result.last_mut().unwrap() = paid;
+        return result;
+    }
+
+    let mut rest = paid;
+    for (idx, amount) in amounts.iter().enumerate().take(amounts.len() - 1) {
+        let share = (paid as i128 * (*amount).max(0) as i128 / total as i128) as i64;
+        result[idx] = share;
+        rest -= share;
+    }
+    *result.last_mut().unwrap() = rest;
+
+    result
+}
+
+/// 售后可退金额(分)
+///
+/// paid 为订单商品实付金额, bought 为购买数量, used 为已申请售后的数量, committed 为已占用的退款金额;
+/// 最后一次售后退回剩余的全部金额, 避免分摊尾差
+pub fn refundable(paid: i64, bought: i64, used: i64, amount: i64, committed: i64) -> i64 {
+    let rest = (paid - committed).max(0);
+    if bought <= 0 || amount <= 0 {
+        return 0;
+    }
+    if used + amount >= bought {
+        return rest;
+    }
+
+    ((paid as i128 * amount as i128 / bought as i128) as i64).min(rest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn order_amount_in_cents() {
+        // 19.99 元商品 + 10.00 元运费
+        let goods = crate::cart::line_money(19.99, 1);
+        assert_eq!(order_amount(goods, 1000).0, 2999);
+    }
+
+    #[test]
+    fn apportion_paid() {
+        // 无优惠
+        assert_eq!(apportion(3000, &[1000, 2000]), vec![1000, 2000]);
+        // 优惠 10 元按比例分摊, 尾差计入最后一项
+        assert_eq!(apportion(2000, &[1000, 2000]), vec![666, 1334]);
+        assert_eq!(apportion(2000, &[0, 0]), vec![0, 2000]);
+        assert_eq!(apportion(-1, &[1000]), vec![0]);
+        assert!(apportion(1000, &[]).is_empty());
+    }
+
+    #[test]
+    fn refundable_amount() {
+        // 实付 10 元买 3 件, 分三次退
+        assert_eq!(refundable(1000, 3, 0, 1, 0), 333);
+        assert_eq!(refundable(1000, 3, 1, 1, 333), 333);
+        assert_eq!(refundable(1000, 3, 2, 1, 666), 334);
+        // 已退金额被修改过时不超过剩余可退金额
+        assert_eq!(refundable(1000, 3, 1, 1, 900), 100);
+        assert_eq!(refundable(1000, 3, 0, 3, 0), 1000);
+        assert_eq!(refundable(1000, 3, 0, 0, 0), 0);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+/// 保存商品时匹配已有的sku, 匹配到的sku原地修改以保证sku id不变(订单、购物车、仓库库存均以sku id关联)
+///
+/// existing 为已有的 (id, 名称), incoming 为提交的 (id, 名称), 优先按id匹配, id 为0或不属于该商品时按名称匹配;
+/// 返回每个提交的sku对应的已有sku id, 未匹配时为 None 即新增的sku
+pub fn match_ids(existing: &[(i64, &str)], incoming: &[(i64, &str)]) -> Vec<Option<i64>> {
+    let mut used = HashSet::new();
+    let mut result = incoming
+        .iter()
+        .map(|(id, _)| {
+            existing
+                .iter()
+                .find(|(existing_id, _)| *id > 0 && existing_id == id)
+                .map(|(existing_id, _)| *existing_id)
+                .filter(|existing_id| used.insert(*existing_id))
+        })
+        .collect::<Vec<Option<i64>>>();
+
+    for (idx, (_, title)) in incoming.iter().enumerate() {
+        if result[idx].is_some() {
+            continue;
+        }
+        result[idx] = existing
+            .iter()
+            .find(|(existing_id, existing_title)| {
+                existing_title == title && !used.contains(existing_id)
+            })
+            .map(|(existing_id, _)| *existing_id);
+        if let Some(existing_id) = result[idx] {
+            used.insert(existing_id);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_by_title() {
+        let existing = [(1, "红色"), (2, "蓝色")];
+
+        // 修改价格、库存时sku不变, 新名称的sku为新增
+        assert_eq!(
+            match_ids(&existing, &[(0, "蓝色"), (0, "红色"), (0, "绿色")]),
+            vec![Some(2), Some(1), None]
+        );
+        // 同名的sku只匹配一次
+        assert_eq!(
+            match_ids(&existing, &[(0, "红色"), (0, "红色")]),
+            vec![Some(1), None]
+        );
+    }
+
+    #[test]
+    fn match_by_id() {
+        let existing = [(1, "红色"), (2, "蓝色")];
+
+        // 按id匹配时可以修改sku名称
+        assert_eq!(
+            match_ids(&existing, &[(1, "大红"), (0, "蓝色")]),
+            vec![Some(1), Some(2)]
+        );
+        // 名称互换
+        assert_eq!(
+            match_ids(&existing, &[(1, "蓝色"), (2, "红色")]),
+            vec![Some(1), Some(2)]
+        );
+        // 其他商品的sku id按名称匹配
+        assert_eq!(
+            match_ids(&existing, &[(9, "蓝色"), (0, "红色")]),
+            vec![Some(2), Some(1)]
+        );
+        // 按id匹配优先于按名称匹配
+        assert_eq!(
+            match_ids(&existing, &[(0, "红色"), (1, "大红")]),
+            vec![None, Some(1)]
+        );
+    }
+}
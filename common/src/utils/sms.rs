@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+use crate::error::{ApiError, ApiResult};
+
+/// 短信内容, 由服务商按模板渲染
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SmsMessage {
+    pub phone: String,
+    // 模板编号
+    pub template: String,
+    pub params: HashMap<String, String>,
+}
+
+/// 短信发送接口
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    async fn send(&self, message: &SmsMessage) -> ApiResult<()>;
+}
+
+/// 短信配置
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SmsConfig {
+    // console: 输出到日志, file: 写入文件, 用于本地开发及测试
+    pub driver: String,
+    // file 驱动的文件路径
+    pub file_path: Option<String>,
+    // 验证码模板
+    pub code_template: String,
+    // 验证码有效期(秒)
+    pub code_expires: usize,
+    // 同一手机号发送间隔(秒)
+    pub resend_interval: usize,
+    // 同一手机号每天最多发送次数
+    pub phone_daily_limit: i64,
+    // 同一ip每小时最多发送次数
+    pub ip_hourly_limit: i64,
+    // 验证码最多校验次数
+    pub max_attempts: u8,
+}
+
+/// 根据配置创建短信服务
+pub fn from_config(cfg: &SmsConfig) -> ApiResult<Box<dyn SmsProvider>> {
+    match cfg.driver.as_str() {
+        "console" => Ok(Box::new(ConsoleSms)),
+        "file" => {
+            let path = cfg
+                .file_path
+                .clone()
+                .filter(|path| !path.is_empty())
+                .ok_or(ApiError::Error("sms.file_path 配置不存在".to_string()))?;
+            Ok(Box::new(FileSms::new(path)))
+        }
+        driver => Err(ApiError::Error(format!("不支持的短信驱动: {}", driver))),
+    }
+}
+
+/// 数字验证码
+pub fn random_code(len: usize) -> String {
+    let mut rng = rand::thread_rng();
+
+    (0..len)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+/// 输出到日志
+pub struct ConsoleSms;
+
+#[async_trait]
+impl SmsProvider for ConsoleSms {
+    async fn send(&self, message: &SmsMessage) -> ApiResult<()> {
+        info!(
+            "短信发送: {} 模板: {} 参数: {:?}",
+            message.phone, message.template, message.params
+        );
+
+        Ok(())
+    }
+}
+
+/// 按行写入文件, 每行一条json
+pub struct FileSms {
+    path: String,
+}
+
+impl FileSms {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl SmsProvider for FileSms {
+    async fn send(&self, message: &SmsMessage) -> ApiResult<()> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        let line = format!("{}\n", serde_json::to_string(message)?);
+        file.write_all(line.as_bytes()).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digit_code() {
+        let code = random_code(6);
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[tokio::test]
+    async fn file_provider() {
+        let path = std::env::temp_dir().join(format!("sms-{}.log", random_code(8)));
+        let sms = FileSms::new(path.to_string_lossy().to_string());
+        let message = SmsMessage {
+            phone: "13800000000".to_string(),
+            template: "SMS_CODE".to_string(),
+            params: HashMap::from([("code".to_string(), "123456".to_string())]),
+        };
+        sms.send(&message).await.unwrap();
+        sms.send(&message).await.unwrap();
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        let lines = content.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        let sent: SmsMessage = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(sent.params["code"], "123456");
+        tokio::fs::remove_file(path).await.unwrap();
+    }
+}
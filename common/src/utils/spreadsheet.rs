@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use calamine::{open_workbook_from_rs, Reader, Xlsx};
+use rust_xlsxwriter::Workbook;
+
+use crate::error::{ApiError, ApiResult};
+
+/// 表格文件格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SheetFormat {
+    Csv,
+    Xlsx,
+}
+
+impl SheetFormat {
+    /// 根据文件名后缀识别格式
+    pub fn from_filename(filename: &str) -> ApiResult<Self> {
+        match filename.rsplit('.').next().map(|ext| ext.to_lowercase()) {
+            Some(ext) if ext == "csv" => Ok(Self::Csv),
+            Some(ext) if ext == "xlsx" => Ok(Self::Xlsx),
+            _ => Err(ApiError::Error("仅支持 csv, xlsx 格式文件".to_string())),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=UTF-8",
+            Self::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// 读取表格内容, 第一行为表头, 返回的每一行以表头作为key
+pub fn read_rows(format: SheetFormat, content: &[u8]) -> ApiResult<Vec<HashMap<String, String>>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    match format {
+        SheetFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(content);
+            for record in reader.records() {
+                rows.push(record?.iter().map(|v| v.trim().to_string()).collect());
+            }
+        }
+        SheetFormat::Xlsx => {
+            let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(content))?;
+            let range = workbook
+                .worksheet_range_at(0)
+                .ok_or(ApiError::Error("表格中没有工作表".to_string()))??;
+            for row in range.rows() {
+                rows.push(row.iter().map(|v| v.to_string().trim().to_string()).collect());
+            }
+        }
+    }
+
+    let mut rows = rows.into_iter();
+    let headers = match rows.next() {
+        Some(headers) => headers,
+        None => return Ok(Vec::new()),
+    };
+
+    Ok(rows
+        .filter(|row| row.iter().any(|v| !v.is_empty()))
+        .map(|row| {
+            headers
+                .iter()
+                .enumerate()
+                .map(|(idx, key)| (key.clone(), row.get(idx).cloned().unwrap_or_default()))
+                .collect::<HashMap<String, String>>()
+        })
+        .collect())
+}
+
+/// 生成表格文件内容
+pub fn write_rows(format: SheetFormat, headers: &[&str], rows: &[Vec<String>]) -> ApiResult<Vec<u8>> {
+    match format {
+        SheetFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(headers)?;
+            for row in rows {
+                writer.write_record(row)?;
+            }
+
+            writer
+                .into_inner()
+                .map_err(|e| ApiError::Error(e.to_string()))
+        }
+        SheetFormat::Xlsx => {
+            let mut workbook = Workbook::new();
+            let worksheet = workbook.add_worksheet();
+            for (col, header) in headers.iter().enumerate() {
+                worksheet.write_string(0, col as u16, *header)?;
+            }
+            for (idx, row) in rows.iter().enumerate() {
+                for (col, value) in row.iter().enumerate() {
+                    worksheet.write_string(idx as u32 + 1, col as u16, value)?;
+                }
+            }
+
+            Ok(workbook.save_to_buffer()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_round_trip() {
+        let content = write_rows(
+            SheetFormat::Csv,
+            &["title", "price"],
+            &[vec!["iphone, 15".to_string(), "5999".to_string()]],
+        )
+        .unwrap();
+
+        let rows = read_rows(SheetFormat::Csv, &content).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get("title").unwrap(), "iphone, 15");
+        assert_eq!(rows[0].get("price").unwrap(), "5999");
+    }
+
+    #[test]
+    fn format_from_filename() {
+        assert_eq!(SheetFormat::from_filename("a.CSV").unwrap(), SheetFormat::Csv);
+        assert_eq!(SheetFormat::from_filename("a.b.xlsx").unwrap(), SheetFormat::Xlsx);
+        assert!(SheetFormat::from_filename("a.xls").is_err());
+    }
+}
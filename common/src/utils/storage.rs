@@ -0,0 +1,253 @@
+use std::path::{Component, Path, PathBuf};
+
+use axum::async_trait;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded::byte_serialize;
+
+use crate::error::{ApiError, ApiResult};
+
+/// 文件存储接口, 路径均为相对路径, 如: ab/abcdef.jpg
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, path: &str, content: Vec<u8>, content_type: &str) -> ApiResult<()>;
+
+    async fn get(&self, path: &str) -> ApiResult<Vec<u8>>;
+
+    async fn exists(&self, path: &str) -> ApiResult<bool>;
+
+    async fn delete(&self, path: &str) -> ApiResult<()>;
+
+    /// 文件访问地址
+    async fn url(&self, path: &str) -> ApiResult<String>;
+}
+
+/// 存储配置
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StorageConfig {
+    // local: 本地磁盘, s3: 兼容S3的对象存储
+    pub driver: String,
+    // CDN域名, 配置后预览地址直接使用CDN地址
+    pub cdn_host: Option<String>,
+    // 预签名地址有效期(秒)
+    pub presign_expires: u32,
+    pub s3: Option<S3Config>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// 根据配置创建存储
+pub fn from_config(cfg: &StorageConfig) -> ApiResult<Box<dyn Storage>> {
+    match cfg.driver.as_str() {
+        "local" => Ok(Box::new(LocalStorage::new(crate::IMAGES_PATH))),
+        "s3" => {
+            let s3 = cfg
+                .s3
+                .as_ref()
+                .ok_or(ApiError::Error("storage.s3 配置不存在".to_string()))?;
+            Ok(Box::new(S3Storage::new(s3, cfg.presign_expires)?))
+        }
+        driver => Err(ApiError::Error(format!("不支持的存储驱动: {}", driver))),
+    }
+}
+
+/// 校验相对路径, 拒绝 `..`、绝对路径等
+pub fn check_path(path: &str) -> ApiResult<&Path> {
+    let relative = Path::new(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(ApiError::Error("非法的文件路径".to_string()));
+    }
+
+    Ok(relative)
+}
+
+/// 本地磁盘存储
+pub struct LocalStorage {
+    root: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: &str) -> Self {
+        LocalStorage {
+            root: root.to_string(),
+        }
+    }
+
+    /// 将路径限制在存储根目录下
+    fn confine(&self, path: &str) -> ApiResult<PathBuf> {
+        let relative = check_path(path)?;
+        let root = Path::new(&self.root).canonicalize()?;
+        let filepath = root.join(relative).canonicalize()?;
+        if !filepath.starts_with(&root) {
+            return Err(ApiError::Error("非法的文件路径".to_string()));
+        }
+
+        Ok(filepath)
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, path: &str, content: Vec<u8>, _content_type: &str) -> ApiResult<()> {
+        let filepath = Path::new(&self.root).join(check_path(path)?);
+        if let Some(dir) = filepath.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        Ok(tokio::fs::write(filepath, content).await?)
+    }
+
+    async fn get(&self, path: &str) -> ApiResult<Vec<u8>> {
+        Ok(tokio::fs::read(self.confine(path)?).await?)
+    }
+
+    async fn exists(&self, path: &str) -> ApiResult<bool> {
+        Ok(self.confine(path).is_ok())
+    }
+
+    async fn delete(&self, path: &str) -> ApiResult<()> {
+        Ok(tokio::fs::remove_file(self.confine(path)?).await?)
+    }
+
+    async fn url(&self, path: &str) -> ApiResult<String> {
+        let url_encode = byte_serialize(path.as_bytes()).collect::<String>();
+
+        Ok(format!(
+            "{}/api/public/{}",
+            crate::server_host().await,
+            url_encode
+        ))
+    }
+}
+
+/// 兼容S3的对象存储, 如: AWS S3, MinIO
+pub struct S3Storage {
+    bucket: Bucket,
+    presign_expires: u32,
+}
+
+impl S3Storage {
+    pub fn new(cfg: &S3Config, presign_expires: u32) -> ApiResult<Self> {
+        let region = Region::Custom {
+            region: cfg.region.clone(),
+            endpoint: cfg.endpoint.clone(),
+        };
+        let credentials = Credentials::new(
+            Some(&cfg.access_key),
+            Some(&cfg.secret_key),
+            None,
+            None,
+            None,
+        )
+        .map_err(|e| ApiError::Error(e.to_string()))?;
+
+        // MinIO 需要使用 path-style 访问
+        let bucket = Bucket::new(&cfg.bucket, region, credentials)?.with_path_style();
+
+        Ok(S3Storage {
+            bucket,
+            presign_expires,
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, path: &str, content: Vec<u8>, content_type: &str) -> ApiResult<()> {
+        check_path(path)?;
+        self.bucket
+            .put_object_with_content_type(path, &content, content_type)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, path: &str) -> ApiResult<Vec<u8>> {
+        check_path(path)?;
+        let response = self.bucket.get_object(path).await?;
+        if response.status_code() != 200 {
+            return Err(ApiError::Error("读取文件失败".to_string()));
+        }
+
+        Ok(response.bytes().to_vec())
+    }
+
+    async fn exists(&self, path: &str) -> ApiResult<bool> {
+        check_path(path)?;
+        match self.bucket.head_object(path).await {
+            Ok((_, code)) => Ok(code == 200),
+            Err(s3::error::S3Error::Http(404, _)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, path: &str) -> ApiResult<()> {
+        check_path(path)?;
+        self.bucket.delete_object(path).await?;
+
+        Ok(())
+    }
+
+    async fn url(&self, path: &str) -> ApiResult<String> {
+        check_path(path)?;
+
+        Ok(self.bucket.presign_get(path, self.presign_expires, None)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reject_traversal() {
+        assert!(check_path("ab/abc.jpg").is_ok());
+        assert!(check_path("").is_err());
+        assert!(check_path("../Cargo.toml").is_err());
+        assert!(check_path("/etc/passwd").is_err());
+        assert!(check_path("ab/../../x.jpg").is_err());
+    }
+
+    /// 需要本地启动 MinIO: docker run -p 9000:9000 minio/minio server /data
+    #[tokio::test]
+    #[ignore]
+    async fn minio() {
+        let storage = S3Storage::new(
+            &S3Config {
+                endpoint: "http://127.0.0.1:9000".to_string(),
+                region: "us-east-1".to_string(),
+                bucket: "test".to_string(),
+                access_key: "minioadmin".to_string(),
+                secret_key: "minioadmin".to_string(),
+            },
+            60,
+        )
+        .unwrap();
+
+        storage
+            .put("ab/test.txt", b"hello".to_vec(), "text/plain")
+            .await
+            .unwrap();
+        assert!(storage.exists("ab/test.txt").await.unwrap());
+        assert_eq!(storage.get("ab/test.txt").await.unwrap(), b"hello".to_vec());
+        assert!(storage
+            .url("ab/test.txt")
+            .await
+            .unwrap()
+            .contains("X-Amz-Signature"));
+        storage.delete("ab/test.txt").await.unwrap();
+        assert!(!storage.exists("ab/test.txt").await.unwrap());
+    }
+}
@@ -0,0 +1,204 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use url::form_urlencoded::byte_serialize;
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// 时间步长(秒)
+pub const PERIOD: i64 = 30;
+
+/// 验证码位数
+pub const DIGITS: u32 = 6;
+
+/// 允许的时间偏差(步数)
+const SKEW: i64 = 1;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// 两步验证配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MfaConfig {
+    // 验证器中显示的发行方
+    pub issuer: String,
+    // 管理员必须开启两步验证
+    pub require_admin: bool,
+}
+
+/// base32 编码(RFC 4648, 无填充)
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut result = String::new();
+    for chunk in data.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        let chars = (chunk.len() * 8 + 4) / 5;
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            result.push(BASE32_ALPHABET[index as usize] as char);
+        }
+    }
+
+    result
+}
+
+/// base32 解码, 忽略空格、填充及大小写
+pub fn base32_decode(value: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::new();
+    let mut bits = 0u64;
+    let mut len = 0;
+    for c in value.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let index = BASE32_ALPHABET
+            .iter()
+            .position(|b| *b as char == c.to_ascii_uppercase())?;
+        bits = ((bits << 5) | index as u64) & 0xffff;
+        len += 5;
+        if len >= 8 {
+            len -= 8;
+            result.push((bits >> len) as u8);
+        }
+    }
+
+    Some(result)
+}
+
+/// 生成随机密钥(base32)
+pub fn generate_secret() -> String {
+    base32_encode(&rand::random::<[u8; 20]>())
+}
+
+/// 计算指定时间步的验证码(RFC 6238, HMAC-SHA1)
+pub fn code_at(secret: &[u8], step: i64, digits: u32) -> String {
+    let mut mac = HmacSha1::new_from_slice(secret).expect("HMAC can take key of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        value % 10u32.pow(digits),
+        width = digits as usize
+    )
+}
+
+/// 校验验证码, 成功时返回匹配的时间步
+///
+/// last_step 为上次使用的时间步, 同一时间步的验证码不能重复使用
+pub fn verify(secret: &str, code: &str, timestamp: i64, last_step: Option<i64>) -> Option<i64> {
+    let secret = base32_decode(secret)?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+
+    let current = timestamp / PERIOD;
+    (current - SKEW..=current + SKEW)
+        .filter(|step| last_step.map_or(true, |last| *step > last))
+        .find(|step| code_at(&secret, *step, DIGITS) == code)
+}
+
+/// 验证器扫码地址, 前端生成二维码
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let encode = |value: &str| {
+        byte_serialize(value.as_bytes())
+            .collect::<String>()
+            .replace('+', "%20")
+    };
+
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode(issuer),
+        encode(account),
+        secret,
+        encode(issuer),
+        DIGITS,
+        PERIOD
+    )
+}
+
+/// 生成恢复码, 格式: XXXXX-XXXXX
+pub fn recovery_codes(total: usize) -> Vec<String> {
+    (0..total)
+        .map(|_| {
+            let code = crate::get_random_str(10);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// 恢复码hash, 忽略大小写及分隔符
+pub fn hash_recovery_code(code: &str) -> String {
+    let code = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_uppercase();
+
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn base32() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw 6ytb oi======").unwrap(), b"foobar");
+        assert!(base32_decode("MZXW1").is_none());
+
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(base32_decode(&secret).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn rfc6238_vectors() {
+        let secret = b"12345678901234567890";
+        assert_eq!(code_at(secret, 59 / PERIOD, 8), "94287082");
+        assert_eq!(code_at(secret, 1111111109 / PERIOD, 8), "07081804");
+        assert_eq!(code_at(secret, 1234567890 / PERIOD, 8), "89005924");
+        assert_eq!(code_at(secret, 59 / PERIOD, 6), "287082");
+    }
+
+    #[test]
+    fn verify_code() {
+        let secret = base32_encode(b"12345678901234567890");
+        let now = 1111111109;
+        let code = code_at(b"12345678901234567890", now / PERIOD, DIGITS);
+
+        let step = verify(&secret, &code, now, None).unwrap();
+        assert_eq!(step, now / PERIOD);
+        // 允许前后一个时间步
+        assert!(verify(&secret, &code, now + PERIOD, None).is_some());
+        assert!(verify(&secret, &code, now + PERIOD * 3, None).is_none());
+        // 已使用过的验证码不能再次使用
+        assert!(verify(&secret, &code, now, Some(step)).is_none());
+        assert!(verify(&secret, "12345", now, None).is_none());
+    }
+
+    #[test]
+    fn uri_and_recovery() {
+        assert_eq!(
+            provisioning_uri("My Shop", "a@b.com", "ABC"),
+            "otpauth://totp/My%20Shop:a%40b.com?secret=ABC&issuer=My%20Shop&algorithm=SHA1&digits=6&period=30"
+        );
+
+        let codes = recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        assert_eq!(codes[0].len(), 11);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&codes[0].replace('-', "").to_lowercase())
+        );
+    }
+}
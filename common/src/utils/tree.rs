@@ -37,3 +37,62 @@ pub trait NodeTrait<T: Clone + Node> {
         tree
     }
 }
+
+/// 按先序排列节点, 子节点排在父节点之后, 同级节点保持原有顺序
+///
+/// [`NodeTrait::build_tree`] 只在当前节点之后查找子节点, 数据需先按此顺序排列
+pub fn preorder<T: Clone + Node>(data: &[T], pid: i64) -> Vec<T> {
+    let mut result = Vec::with_capacity(data.len());
+    for node in data.iter().filter(|node| node.is_root(pid)) {
+        result.push(node.clone());
+        result.extend(preorder(data, node.get_id()));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Item(i64, i64);
+
+    impl Node for Item {
+        fn is_root(&self, pid: i64) -> bool {
+            self.1 == pid
+        }
+
+        fn get_pid(&self) -> i64 {
+            self.1
+        }
+
+        fn get_id(&self) -> i64 {
+            self.0
+        }
+
+        fn get_data(&self) -> Value {
+            json!({ "id": self.0 })
+        }
+    }
+
+    impl NodeTrait<Item> for Item {}
+
+    #[test]
+    fn preorder_tree() {
+        // 子节点在父节点之前时也能正确排列
+        let data = vec![Item(3, 2), Item(2, 0), Item(1, 0), Item(4, 1), Item(5, 3)];
+        let ids = preorder(&data, 0)
+            .iter()
+            .map(|item| item.0)
+            .collect::<Vec<i64>>();
+        assert_eq!(ids, vec![2, 3, 5, 1, 4]);
+
+        let mut sorted = preorder(&data, 0);
+        let tree = Item::build_tree(&mut sorted, 0);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0]["id"], 2);
+        assert_eq!(tree[0]["children"][0]["children"][0]["id"], 5);
+        assert_eq!(tree[1]["children"][0]["id"], 4);
+    }
+}